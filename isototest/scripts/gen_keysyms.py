@@ -0,0 +1,63 @@
+#!/usr/bin/env python3
+# SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+# SPDX-LicenseIdentifier: GPL-2.0-or-later
+"""Generate `src/keysym/defs.rs` from the X11 `keysymdef.h` header.
+
+Usage: scripts/gen_keysyms.py [/usr/include/X11/keysymdef.h] > src/keysym/defs.rs
+"""
+import re
+import sys
+
+# Patterns as documented at the top of keysymdef.h.
+DEFINE = re.compile(r"^#define XK_([a-zA-Z_0-9]+)\s+0x([0-9a-f]+)\s*(/\*.*\*/)?\s*$")
+UNICODE = re.compile(r"^/\* U\+([0-9A-F]{4,6}) ")
+
+
+def main():
+    path = sys.argv[1] if len(sys.argv) > 1 else "/usr/include/X11/keysymdef.h"
+    keysyms = []
+    unicode = {}
+    with open(path, encoding="utf-8") as header:
+        for line in header:
+            m = DEFINE.match(line)
+            if not m:
+                continue
+            name, value, comment = m.group(1), int(m.group(2), 16), m.group(3) or ""
+            keysyms.append((name, value))
+            u = UNICODE.match(comment)
+            # Keep the first keysym for each character, as later ones are aliases or keypad keys.
+            if u and value < 0x01000000:
+                unicode.setdefault(int(u.group(1), 16), value)
+
+    out = sys.stdout
+    out.write(HEADER)
+    for name, value in keysyms:
+        out.write(f"pub const XK_{name}: u32 = {value:#x};\n")
+
+    out.write("\n/// All keysym names without the `XK_` prefix, sorted by name.\n")
+    out.write("#[rustfmt::skip]\n")
+    out.write("pub(crate) const KEYSYM_NAMES: &[(&str, u32)] = &[\n")
+    for name, _ in sorted(keysyms, key=lambda k: k[0].encode()):
+        out.write(f'    ("{name}", XK_{name}),\n')
+    out.write("];\n")
+
+    out.write("\n/// Legacy keysyms of Unicode code points, sorted by code point.\n")
+    out.write("#[rustfmt::skip]\n")
+    out.write("pub(crate) const UNICODE_KEYSYMS: &[(u32, u32)] = &[\n")
+    for codepoint, value in sorted(unicode.items()):
+        out.write(f"    ({codepoint:#06x}, {value:#x}),\n")
+    out.write("];\n")
+
+
+HEADER = """// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+
+// @generated by scripts/gen_keysyms.py from X11 keysymdef.h. Do not edit by hand.
+
+//! Keysym constants and lookup tables generated from X11's `keysymdef.h`.
+#![allow(non_upper_case_globals)]
+
+"""
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,324 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Keyboard layout module
+//!
+//! VNC servers like QEMU translate the keysyms they receive into scancodes using their own keymap,
+//! which is `en-us` by default. The remote machine then interprets these scancodes according to
+//! its configured keyboard layout. To type a character on a machine with a different layout, the
+//! keysym of the US key at the same physical position has to be sent instead, together with the
+//! modifiers the remote layout requires for the character.
+//!
+//! A [`KeyboardLayout`] describes which keys and modifiers produce a character on the remote
+//! machine. AltGr is sent as the right Alt key, which `en-us` keymaps map to the scancode of
+//! AltGr. Characters a layout does not know are sent as their own keysym, see
+//! [`crate::keysym::from_char`]. Printable ASCII characters are the exception: their keysyms
+//! would type the character at the same position of the US keyboard, so they cannot be typed if
+//! the layout does not know them.
+use std::fmt;
+
+use crate::keysym;
+
+/// Modifier combinations required by a character.
+const NONE: &[u32] = &[];
+const SHIFT: &[u32] = &[keysym::XK_Shift_L];
+const ALTGR: &[u32] = &[keysym::XK_Alt_R];
+const SHIFT_ALTGR: &[u32] = &[keysym::XK_Shift_L, keysym::XK_Alt_R];
+const CTRL: &[u32] = &[keysym::XK_Control_L];
+
+/// Keys whose characters are described by the layout tables, by their unshifted US character.
+///
+/// The last entry is the additional key left of `Z` on ISO keyboards, which US keymaps know as
+/// `less`.
+const KEY_POSITIONS: &str = concat!(
+    "`1234567890-=",
+    "qwertyuiop[]\\",
+    "asdfghjkl;'",
+    "zxcvbnm,./",
+    "<"
+);
+
+/// A level of a layout table without any symbols. Spaces mark keys without a symbol.
+const BLANK: &str = concat!(
+    "             ",
+    "             ",
+    "           ",
+    "          ",
+    " "
+);
+
+/// Characters typed by each key of [`KEY_POSITIONS`] on a keyboard layout.
+struct LayoutTable {
+    /// Characters of the levels: plain, Shift, AltGr and Shift + AltGr.
+    levels: [&'static str; 4],
+    /// Characters produced by dead keys, which are completed by a following space.
+    dead: &'static str,
+}
+
+const DE: LayoutTable = LayoutTable {
+    levels: [
+        concat!(
+            "^1234567890ß´",
+            "qwertzuiopü+#",
+            "asdfghjklöä",
+            "yxcvbnm,.-",
+            "<"
+        ),
+        concat!(
+            "°!\"§$%&/()=?`",
+            "QWERTZUIOPÜ*'",
+            "ASDFGHJKLÖÄ",
+            "YXCVBNM;:_",
+            ">"
+        ),
+        concat!(
+            " ¹²³¼½¬{[]}\\ ",
+            "@ €        ~ ",
+            "           ",
+            "      µ   ",
+            "|"
+        ),
+        BLANK,
+    ],
+    dead: "^´`",
+};
+
+const FR: LayoutTable = LayoutTable {
+    levels: [
+        concat!(
+            "²&é\"'(-è_çà)=",
+            "azertyuiop^$*",
+            "qsdfghjklmù",
+            "wxcvbn,;:!",
+            "<"
+        ),
+        concat!(
+            " 1234567890°+",
+            "AZERTYUIOP¨£µ",
+            "QSDFGHJKLM%",
+            "WXCVBN?./§",
+            ">"
+        ),
+        concat!(
+            "  ~#{[|`\\^@]}",
+            "  €        ¤ ",
+            "           ",
+            "          ",
+            " "
+        ),
+        BLANK,
+    ],
+    dead: "^¨~`",
+};
+
+const CZ: LayoutTable = LayoutTable {
+    levels: [
+        concat!(
+            ";+ěščřžýáíé=´",
+            "qwertzuiopú)¨",
+            "asdfghjklů§",
+            "yxcvbnm,.-",
+            "\\"
+        ),
+        concat!(
+            "°1234567890%ˇ",
+            "QWERTZUIOP/('",
+            "ASDFGHJKL\"!",
+            "YXCVBNM?:_",
+            "|"
+        ),
+        concat!(
+            "`!@#$%^&*{}  ",
+            "\\|€       []\\",
+            "           ",
+            "       <>*",
+            "/"
+        ),
+        concat!(
+            "~ ĚŠČŘŽÝÁÍÉ  ",
+            "             ",
+            "           ",
+            "          ",
+            " "
+        ),
+    ],
+    dead: "°´ˇ¨",
+};
+
+/// The JIS keys for `¥` and `ろ` have no equivalent in the `en-us` keymap, so `\`, `|` and `_`
+/// cannot be typed with this layout.
+const JP: LayoutTable = LayoutTable {
+    levels: [
+        concat!(
+            " 1234567890-^",
+            "qwertyuiop@[]",
+            "asdfghjkl;:",
+            "zxcvbnm,./",
+            " "
+        ),
+        concat!(
+            " !\"#$%&'() =~",
+            "QWERTYUIOP`{}",
+            "ASDFGHJKL+*",
+            "ZXCVBNM<>?",
+            " "
+        ),
+        BLANK,
+        BLANK,
+    ],
+    dead: "",
+};
+
+/// Keyboard layouts of the remote machine.
+///
+/// # Members
+///
+/// * `Us` - US English (QWERTY). Characters are sent as their own keysyms.
+/// * `De` - German (QWERTZ).
+/// * `Fr` - French (AZERTY).
+/// * `Cz` - Czech (QWERTZ).
+/// * `Jp` - Japanese (JIS, 106 keys).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyboardLayout {
+    #[default]
+    Us,
+    De,
+    Fr,
+    Cz,
+    Jp,
+}
+
+/// The keys to press to type a character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyStroke {
+    /// The keysym of the key producing the character.
+    pub keysym: u32,
+    /// The keysyms of the modifiers to hold while the key is tapped.
+    pub modifiers: &'static [u32],
+    /// Whether the key is a dead key, which must be followed by a space to produce the character.
+    pub dead: bool,
+}
+
+impl KeyboardLayout {
+    /// Look up a layout by its name as used by `loadkeys` and openQA, e.g. `"de"`.
+    ///
+    /// # Parameters
+    ///
+    /// * name: `&str` - The name of the layout.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(KeyboardLayout)` - The layout.
+    /// * `None` - If the layout is not supported.
+    pub fn from_name(name: &str) -> Option<KeyboardLayout> {
+        match name.to_ascii_lowercase().as_str() {
+            "us" | "en-us" => Some(KeyboardLayout::Us),
+            "de" | "de-de" => Some(KeyboardLayout::De),
+            "fr" | "fr-fr" => Some(KeyboardLayout::Fr),
+            "cz" | "cs" => Some(KeyboardLayout::Cz),
+            "jp" | "ja" | "jp106" => Some(KeyboardLayout::Jp),
+            _ => None,
+        }
+    }
+
+    /// The name of the layout.
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyboardLayout::Us => "us",
+            KeyboardLayout::De => "de",
+            KeyboardLayout::Fr => "fr",
+            KeyboardLayout::Cz => "cz",
+            KeyboardLayout::Jp => "jp",
+        }
+    }
+
+    /// Determine the keys to press to type a character on this layout.
+    ///
+    /// ASCII control characters without a key of their own, like `'\x03'`, are typed as Ctrl and
+    /// the corresponding letter. Printable ASCII characters missing from the layout cannot be
+    /// typed, as their US keys produce other characters on the remote machine.
+    ///
+    /// # Parameters
+    ///
+    /// * c: `char` - The character to type.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(KeyStroke)` - The keys producing the character.
+    /// * `None` - If the character cannot be typed.
+    pub fn lookup(&self, c: char) -> Option<KeyStroke> {
+        if let Some(table) = self.table() {
+            match table.lookup(c) {
+                Some(stroke) => return Some(stroke),
+                None if c.is_ascii_graphic() => return None,
+                None => {}
+            }
+        }
+
+        if let Some(keysym) = keysym::from_char(c) {
+            let modifiers: &'static [u32] = match self {
+                KeyboardLayout::Us if c.is_ascii_uppercase() || US_SHIFT_CHARS.contains(c) => SHIFT,
+                _ => NONE,
+            };
+            return Some(KeyStroke {
+                keysym,
+                modifiers,
+                dead: false,
+            });
+        }
+
+        match c as u32 {
+            ctrl @ 0x01..=0x1a => {
+                let letter: char = char::from_u32('a' as u32 + ctrl - 1)?;
+                let stroke: KeyStroke = self.lookup(letter)?;
+                Some(KeyStroke {
+                    modifiers: CTRL,
+                    ..stroke
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn table(&self) -> Option<&'static LayoutTable> {
+        match self {
+            KeyboardLayout::Us => None,
+            KeyboardLayout::De => Some(&DE),
+            KeyboardLayout::Fr => Some(&FR),
+            KeyboardLayout::Cz => Some(&CZ),
+            KeyboardLayout::Jp => Some(&JP),
+        }
+    }
+}
+
+impl fmt::Display for KeyboardLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Characters typed with Shift on a US keyboard.
+const US_SHIFT_CHARS: &str = "~!@#$%^&*()_+{}|:\"<>?";
+
+impl LayoutTable {
+    fn lookup(&self, c: char) -> Option<KeyStroke> {
+        if c == ' ' {
+            return None;
+        }
+        let modifiers: [&'static [u32]; 4] = [NONE, SHIFT, ALTGR, SHIFT_ALTGR];
+        for (level, chars) in self.levels.iter().enumerate() {
+            if let Some(pos) = chars.chars().position(|x| x == c) {
+                let key: char = KEY_POSITIONS.chars().nth(pos)?;
+                let keysym: u32 = match key {
+                    '<' => keysym::XK_less,
+                    key => key as u32,
+                };
+                return Some(KeyStroke {
+                    keysym,
+                    modifiers: modifiers[level],
+                    dead: self.dead.contains(c),
+                });
+            }
+        }
+        None
+    }
+}
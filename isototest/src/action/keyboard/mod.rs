@@ -0,0 +1,517 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Keyboard Module
+//!
+//! This module handles text-based interactions between the VncClient and VncServer.
+//!
+//! It uses [`X11Event::KeyEvent`](https://docs.rs/vnc-rs/0.5.1/vnc/event/struct.ClientKeyEvent.html) to send
+//! individual key press or release events to the VNC server.
+//!
+//! Text is typed with [`write_to_console`], or with [`type_string`] which waits for the remote
+//! machine to echo the input. Key combinations like `ctrl-alt-f2` are sent with [`send_key`],
+//! using the same key names as openQA.
+//!
+//! Characters and key names are translated to X11 keysyms, see [`crate::keysym`]. Any Unicode
+//! character can be typed, as long as the remote machine's keyboard layout supports it. The keys
+//! producing a character depend on the keyboard layout of the remote machine, see [`layout`].
+//! Key events are paced according to a [`speed::TypingSpeed`].
+pub mod layout;
+pub mod speed;
+
+extern crate proc_macro;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use tokio::sync::watch;
+use vnc::{client::VncClient, ClientKeyEvent, X11Event};
+
+use crate::action::view::{
+    assert_screen, check_screen,
+    compare::Region,
+    needle::{Needle, NeedleMatch},
+};
+use crate::errors::Error;
+use crate::keysym;
+use crate::logging::LOG_TARGET;
+use crate::session::Session;
+use crate::types::KeyEventType;
+use layout::{KeyStroke, KeyboardLayout};
+use speed::TypingSpeed;
+
+/// Send given text to VNC server.
+///
+/// Uses `X11Event`s to send keypresses to the server. According to the [RFC](https://www.rfc-editor.org/rfc/rfc6143.html#section-7.5.4)
+/// it does not matter whether the X-Window System is running or not.
+///
+/// Modifiers pressed for a character are released even if sending the character fails.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections
+/// * text: `String` - The text to write.
+/// * layout: `KeyboardLayout` - The keyboard layout of the remote machine.
+/// * speed: `Option<TypingSpeed>` - The speed at which keys are sent. If `None`, the default
+///   speed is used, see [`speed::DEFAULT_CHARS_PER_SEC`].
+///
+/// # Returns
+///
+/// * `Ok(())` - If the transaction has been successfully completed.
+/// * `Err(Error)` - If a character cannot be typed with the layout or the transaction fails.
+pub async fn write_to_console(
+    client: &VncClient,
+    text: String,
+    layout: KeyboardLayout,
+    speed: Option<TypingSpeed>,
+) -> Result<(), Error> {
+    let speed: TypingSpeed = speed.unwrap_or_default();
+    info!(target: LOG_TARGET, "Sending text '{}' with layout '{}' at {} characters per second...", text, layout, speed.chars_per_sec());
+
+    for ch in text.chars() {
+        // Translate each character to the keys producing it on the remote machine.
+        let stroke: KeyStroke = layout.lookup(ch).ok_or_else(|| {
+            Error::KeyboardMappingError(format!(
+                "Unable to type character '{}' with keyboard layout '{}'",
+                ch.escape_default(),
+                layout
+            ))
+        })?;
+
+        // Hold the modifiers while tapping the key corresponding to the character.
+        let mut keys: Vec<u32> = stroke.modifiers.to_vec();
+        keys.push(stroke.keysym);
+        press_combination(client, &keys, &speed).await?;
+
+        // Dead keys only produce their character when followed by a space.
+        if stroke.dead {
+            press_button(client, keysym::XK_space, KeyEventType::Tap, &speed).await?;
+        }
+    }
+    info!(target: LOG_TARGET, "Text '{}' sent.", text);
+    Ok(())
+}
+
+/// Type text in chunks, verifying that the remote machine echoes each chunk.
+///
+/// After each chunk of `chunk_size` characters, typing pauses until the session's framebuffer
+/// has changed, so slow machines are not flooded with keys they would drop. Typing aborts if the
+/// screen does not change within `timeout` after a chunk. Use [`write_to_console`] for input
+/// which is not echoed, like passwords.
+///
+/// The echo is detected from the regions the VNC server reports as changed, see
+/// [`Session::damage_since`], not by reading the typed text. Any change outside of the `ignore`
+/// regions counts as the echo, so regions which change on their own, like a blinking cursor or a
+/// clock, must be ignored to detect a machine which stopped echoing input.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session to type into and whose screen is watched.
+/// * text: `&str` - The text to type.
+/// * chunk_size: `usize` - Number of characters typed before waiting for the echo.
+/// * timeout: `Duration` - How long to wait for the screen to change after each chunk.
+/// * ignore: `&[Region]` - Regions whose changes do not count as the echo.
+/// * speed: `Option<TypingSpeed>` - The speed at which keys are sent. If `None`, the session's
+///   typing speed is used.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the text has been typed and every chunk was echoed.
+/// * `Err(Error)` - If the chunk size is zero, sending keys fails, the connection is lost, or the
+///   screen stopped changing (`Error::Timeout`).
+pub async fn type_string(
+    session: &Session,
+    text: &str,
+    chunk_size: usize,
+    timeout: Duration,
+    ignore: &[Region],
+    speed: Option<TypingSpeed>,
+) -> Result<(), Error> {
+    if chunk_size == 0 {
+        return Err(Error::InvalidInput(
+            "Chunk size for typing must be greater than zero".to_string(),
+        ));
+    }
+    let layout: KeyboardLayout = session.keyboard_layout();
+    let speed: TypingSpeed = speed.unwrap_or(session.typing_speed());
+    let chars: Vec<char> = text.chars().collect();
+    info!(target: LOG_TARGET, "Typing '{}' in chunks of {} characters...", text, chunk_size);
+
+    let mut updates: watch::Receiver<u64> = session.frame_updates();
+    let mut typed: usize = 0;
+    for chunk in chars.chunks(chunk_size) {
+        let chunk: String = chunk.iter().collect();
+        updates.mark_unchanged();
+        let seen: u64 = session.generation();
+        write_to_console(&session.client(), chunk.clone(), layout, Some(speed)).await?;
+        typed += chunk.chars().count();
+
+        let echoed = async {
+            loop {
+                updates.changed().await?;
+                let damage: Vec<Region> = session.damage_since(seen);
+                if damage.iter().any(|changed| {
+                    !ignore
+                        .iter()
+                        .any(|region| region.intersection(changed) == Some(*changed))
+                }) {
+                    return Ok(());
+                }
+            }
+        };
+        match tokio::time::timeout(timeout, echoed).await {
+            Ok(Ok(())) => {}
+            Ok(Err(watch::error::RecvError { .. })) => {
+                return Err(Error::ConnectionError("Session closed".to_string()))
+            }
+            Err(_) => {
+                error!(target: LOG_TARGET, "Screen did not change after typing '{}'.", chunk);
+                return Err(Error::Timeout(format!(
+                    "Screen did not change within {:?} after typing '{}' ({} of {} characters typed). The remote machine stopped echoing input",
+                    timeout,
+                    chunk.escape_default(),
+                    typed,
+                    chars.len()
+                )));
+            }
+        }
+    }
+    info!(target: LOG_TARGET, "Text '{}' typed.", text);
+    Ok(())
+}
+
+/// Send a key or key combination to the VNC server.
+///
+/// The keys of the combination are pressed in the given order and released in reverse order.
+/// All pressed keys are released, even if sending one of the key events fails, so a failing call
+/// does not leave modifiers stuck on the remote machine.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections.
+/// * spec: `&str` - The openQA key specification, e.g. `"ret"` or `"ctrl-alt-f2"`. See
+///   [`parse_key_spec`].
+/// * layout: `KeyboardLayout` - The keyboard layout of the remote machine.
+/// * speed: `Option<TypingSpeed>` - The speed at which keys are sent. If `None`, the default
+///   speed is used, see [`speed::DEFAULT_CHARS_PER_SEC`].
+///
+/// # Returns
+///
+/// * `Ok(())` - If the key combination has been sent.
+/// * `Err(Error)` - If the specification is invalid or the transaction fails.
+pub async fn send_key(
+    client: &VncClient,
+    spec: &str,
+    layout: KeyboardLayout,
+    speed: Option<TypingSpeed>,
+) -> Result<(), Error> {
+    let speed: TypingSpeed = speed.unwrap_or_default();
+    let keys: Vec<u32> = parse_key_spec(spec, layout)?;
+    info!(target: LOG_TARGET, "Sending key '{}'...", spec);
+
+    let result: Result<(), Error> = press_combination(client, &keys, &speed).await;
+    if let Err(e) = &result {
+        error!(target: LOG_TARGET, "Failed to send key '{}': {}", spec, e);
+    }
+    result
+}
+
+/// Send a key combination until the screen matches a needle with one of the given tags.
+///
+/// The screen is checked before the first key is sent and after every key, see
+/// [`crate::action::view::check_screen`]. Once all keys have been sent, the screen is asserted
+/// one last time. Keys are sent with the session's keyboard layout.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session to send keys to and whose screen is evaluated.
+/// * needles: `&[Needle]` - The needles to consider.
+/// * tags: `&[&str]` - The tags to look for.
+/// * spec: `&str` - The openQA key specification to send, see [`parse_key_spec`].
+/// * counter: `u32` - Maximum number of times the key is sent.
+/// * timeout: `Duration` - How long to wait for a matching screen after each key.
+/// * speed: `Option<TypingSpeed>` - The speed at which keys are sent. If `None`, the session's
+///   typing speed is used.
+///
+/// # Returns
+///
+/// * `Ok(NeedleMatch)` - The matching needle.
+/// * `Err(Error)` - If sending a key fails or no needle matched after the last key. An
+///   `Error::NeedleMismatch` carries the last evaluated frame and the closest candidate needle.
+pub async fn send_key_until_needlematch(
+    session: &Session,
+    needles: &[Needle],
+    tags: &[&str],
+    spec: &str,
+    counter: u32,
+    timeout: Duration,
+    speed: Option<TypingSpeed>,
+) -> Result<NeedleMatch, Error> {
+    let layout: KeyboardLayout = session.keyboard_layout();
+    let speed: TypingSpeed = speed.unwrap_or(session.typing_speed());
+    parse_key_spec(spec, layout)?;
+    info!(target: LOG_TARGET, "Sending key '{}' until tags {:?} match...", spec, tags);
+
+    for _ in 0..counter {
+        if let Some(found) = check_screen(session, needles, tags, timeout).await? {
+            return Ok(found);
+        }
+        send_key(&session.client(), spec, layout, Some(speed)).await?;
+    }
+
+    assert_screen(session, needles, tags, timeout).await
+}
+
+/// Parse an openQA key specification into the keycodes to press.
+///
+/// A specification consists of key names joined by `-`, e.g. `"ctrl-alt-delete"`. The openQA key
+/// names listed below are case-insensitive. Single characters are typed like the characters of
+/// [`write_to_console`], so modifiers required by the keyboard layout are added to the
+/// combination. The `-` key itself is called `minus`. All other names are looked up as
+/// case-sensitive X11 keysym names, see [`crate::keysym::from_name`], which gives access to keys
+/// like `Henkan` or `Hangul` used to control input methods.
+///
+/// Supported key names are the modifiers `shift`, `ctrl`, `alt`, `super` and `meta`, the
+/// function keys `f1` to `f12`, the arrows `up`, `down`, `left` and `right`, `home`, `end`,
+/// `pgup`, `pgdn`, `insert`, `delete`, `backspace`, `tab`, `ret`, `esc`, `spc`, lock and system
+/// keys like `caps` or `sysrq`, the characters `minus`, `equal` and `plus`, and the keypad keys
+/// `kp_0` to `kp_9`, `kp_enter`, `kp_add`, `kp_subtract`, `kp_multiply`, `kp_divide` and
+/// `kp_decimal`.
+///
+/// # Parameters
+///
+/// * spec: `&str` - The key specification.
+/// * layout: `KeyboardLayout` - The keyboard layout of the remote machine.
+///
+/// # Returns
+///
+/// * `Ok(Vec<u32>)` - The keycodes in the order they must be pressed.
+/// * `Err(Error)` - If the specification contains an unknown key name.
+pub fn parse_key_spec(spec: &str, layout: KeyboardLayout) -> Result<Vec<u32>, Error> {
+    // A lone dash is the only key spec whose name contains the separator.
+    let names: Vec<&str> = if spec == "-" {
+        vec!["minus"]
+    } else {
+        spec.split('-').collect()
+    };
+
+    let mut keys: Vec<u32> = Vec::with_capacity(names.len());
+    for name in names {
+        let unknown = || {
+            Error::KeyboardMappingError(format!(
+                "Unknown key '{}' in key specification '{}'",
+                name, spec
+            ))
+        };
+        let lower: String = name.to_ascii_lowercase();
+        let mut chars = name.chars();
+        let ch: Option<char> = match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => key_name_to_char(&lower),
+        };
+
+        match ch {
+            Some(c) => {
+                let stroke: KeyStroke = layout.lookup(c).ok_or_else(unknown)?;
+                for modifier in stroke.modifiers {
+                    if !keys.contains(modifier) {
+                        keys.push(*modifier);
+                    }
+                }
+                keys.push(stroke.keysym);
+            }
+            None => keys.push(
+                key_name_to_keycode(&lower)
+                    .or_else(|| keysym::from_name(name))
+                    .ok_or_else(unknown)?,
+            ),
+        }
+    }
+    Ok(keys)
+}
+
+/// Assign a key name as used by openQA the character it types.
+///
+/// These keys are located differently depending on the keyboard layout.
+fn key_name_to_char(name: &str) -> Option<char> {
+    match name {
+        "minus" => Some('-'),
+        "equal" => Some('='),
+        "plus" => Some('+'),
+        _ => None,
+    }
+}
+
+/// Assign a key name as used by openQA its corresponding keysym.
+///
+/// # Parameters
+///
+/// * name: `&str` - The lowercase key name.
+///
+/// # Returns
+///
+/// * `Some(u32)` - The keysym of the named key.
+/// * `None` - If the key name is unknown.
+fn key_name_to_keycode(name: &str) -> Option<u32> {
+    let keycode = match name {
+        "shift" => keysym::XK_Shift_L,
+        "ctrl" => keysym::XK_Control_L,
+        "alt" => keysym::XK_Alt_L,
+        "super" => keysym::XK_Super_L,
+        "meta" => keysym::XK_Meta_L,
+        "caps" | "capslock" => keysym::XK_Caps_Lock,
+        "numlock" => keysym::XK_Num_Lock,
+        "scrolllock" => keysym::XK_Scroll_Lock,
+        "esc" => keysym::XK_Escape,
+        "ret" | "enter" => keysym::XK_Return,
+        "tab" => keysym::XK_Tab,
+        "spc" | "space" => keysym::XK_space,
+        "backspace" => keysym::XK_BackSpace,
+        "delete" => keysym::XK_Delete,
+        "insert" => keysym::XK_Insert,
+        "home" => keysym::XK_Home,
+        "end" => keysym::XK_End,
+        "pgup" | "pageup" => keysym::XK_Page_Up,
+        "pgdn" | "pagedown" => keysym::XK_Page_Down,
+        "up" => keysym::XK_Up,
+        "down" => keysym::XK_Down,
+        "left" => keysym::XK_Left,
+        "right" => keysym::XK_Right,
+        "sysrq" => keysym::XK_Sys_Req,
+        "print" => keysym::XK_Print,
+        "pause" => keysym::XK_Pause,
+        "menu" => keysym::XK_Menu,
+        "f1" => keysym::XK_F1,
+        "f2" => keysym::XK_F2,
+        "f3" => keysym::XK_F3,
+        "f4" => keysym::XK_F4,
+        "f5" => keysym::XK_F5,
+        "f6" => keysym::XK_F6,
+        "f7" => keysym::XK_F7,
+        "f8" => keysym::XK_F8,
+        "f9" => keysym::XK_F9,
+        "f10" => keysym::XK_F10,
+        "f11" => keysym::XK_F11,
+        "f12" => keysym::XK_F12,
+        "kp_0" => keysym::XK_KP_0,
+        "kp_1" => keysym::XK_KP_1,
+        "kp_2" => keysym::XK_KP_2,
+        "kp_3" => keysym::XK_KP_3,
+        "kp_4" => keysym::XK_KP_4,
+        "kp_5" => keysym::XK_KP_5,
+        "kp_6" => keysym::XK_KP_6,
+        "kp_7" => keysym::XK_KP_7,
+        "kp_8" => keysym::XK_KP_8,
+        "kp_9" => keysym::XK_KP_9,
+        "kp_enter" => keysym::XK_KP_Enter,
+        "kp_add" | "kp_plus" => keysym::XK_KP_Add,
+        "kp_subtract" | "kp_minus" => keysym::XK_KP_Subtract,
+        "kp_multiply" => keysym::XK_KP_Multiply,
+        "kp_divide" => keysym::XK_KP_Divide,
+        "kp_decimal" => keysym::XK_KP_Decimal,
+        _ => return None,
+    };
+    Some(keycode)
+}
+
+/// Press keys in the given order and release them in reverse order.
+///
+/// All pressed keys are released, even if sending one of the key events fails, so a failure does
+/// not leave modifiers like Shift or AltGr held on the remote machine.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - Reference to the client used for communication.
+/// * keys: `&[u32]` - The keysyms to press.
+/// * speed: `&TypingSpeed` - The speed determining the delay after each key event.
+///
+/// # Returns
+///
+/// * `Ok(())` - If all key events have been sent.
+/// * `Err(Error)` - The first error which occured during communication.
+async fn press_combination(
+    client: &VncClient,
+    keys: &[u32],
+    speed: &TypingSpeed,
+) -> Result<(), Error> {
+    let mut pressed: Vec<u32> = Vec::with_capacity(keys.len());
+    let mut result: Result<(), Error> = Ok(());
+    for key in keys {
+        // Remember the key before sending it, as a failed transaction may still have reached the
+        // server. Releasing a key which is not pressed is harmless.
+        pressed.push(*key);
+        if let Err(e) = press_button(client, *key, KeyEventType::Press, speed).await {
+            result = Err(e);
+            break;
+        }
+    }
+    for key in pressed.into_iter().rev() {
+        if let Err(e) = press_button(client, key, KeyEventType::Release, speed).await {
+            warn!(target: LOG_TARGET, "Unable to release key '{:#x}': {}", key, e);
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
+
+/// Encapsulate the `client.input()` function calls to avoid repitition.
+///
+/// Will put the given key into a state according to the [crate::types::KeyEventType] parameter.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - Reference to the client used for communication.
+/// * keycode: `u32` - The keycode of the button to press.
+/// * evtype: `KeyEventType` - Select whether the key is tapped, held or released.
+/// * speed: `&TypingSpeed` - The speed determining the delay after each key event.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the keypress has been sent correctly.
+/// * `Err(Error)` - If an error occured during communication.
+async fn press_button(
+    client: &VncClient,
+    keycode: u32,
+    evtype: KeyEventType,
+    speed: &TypingSpeed,
+) -> Result<(), Error> {
+    match evtype {
+        KeyEventType::Press => {
+            let kevent: ClientKeyEvent = ClientKeyEvent {
+                keycode,
+                down: true,
+            };
+            let xevent: X11Event = X11Event::KeyEvent(kevent);
+
+            client.input(xevent).await?;
+            speed.wait().await;
+        }
+        KeyEventType::Release => {
+            let kevent: ClientKeyEvent = ClientKeyEvent {
+                keycode,
+                down: false,
+            };
+            let xevent: X11Event = X11Event::KeyEvent(kevent);
+
+            client.input(xevent).await?;
+            speed.wait().await;
+        }
+        KeyEventType::Tap => {
+            let mut kevent: ClientKeyEvent = ClientKeyEvent {
+                keycode,
+                down: true,
+            };
+            let mut xevent: X11Event = X11Event::KeyEvent(kevent.clone());
+
+            client.input(xevent).await?;
+            speed.wait().await;
+
+            kevent.down = false;
+            xevent = X11Event::KeyEvent(kevent);
+
+            client.input(xevent).await?;
+            speed.wait().await;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Typing speed module
+//!
+//! Key events have to be paced, as the remote machine drops or reorders keys if they arrive
+//! faster than it can process them. A [`TypingSpeed`] describes how fast characters are typed and
+//! how much the delays between key events vary, which some applications need to not treat the
+//! input as pasted text.
+//!
+//! All delays use `tokio::time`, so waiting never blocks the runtime.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::errors::Error;
+
+/// Characters typed per second by default.
+pub const DEFAULT_CHARS_PER_SEC: f64 = 25.0;
+
+/// Key events sent for a character without modifiers: press and release.
+const EVENTS_PER_CHAR: f64 = 2.0;
+
+/// State of the jitter random number generator, shared by all typing speeds.
+static JITTER_STATE: AtomicU64 = AtomicU64::new(0x9e37_79b9_7f4a_7c15);
+
+/// Speed at which key events are sent to the VNC server.
+///
+/// Every key event is followed by a delay of half the time it takes to type one character, so a
+/// character without modifiers takes `1 / chars_per_sec` seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TypingSpeed {
+    chars_per_sec: f64,
+    jitter: f64,
+}
+
+impl TypingSpeed {
+    /// Create a typing speed without jitter.
+    ///
+    /// # Parameters
+    ///
+    /// * chars_per_sec: `f64` - Number of characters typed per second.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TypingSpeed)` - The typing speed.
+    /// * `Err(Error)` - If the rate is not a positive, finite number, or so small that the delays
+    ///   cannot be represented as a `Duration`.
+    pub fn new(chars_per_sec: f64) -> Result<TypingSpeed, Error> {
+        if !chars_per_sec.is_finite() || chars_per_sec <= 0.0 {
+            return Err(Error::InvalidInput(format!(
+                "Typing speed must be a positive number of characters per second, got {}",
+                chars_per_sec
+            )));
+        }
+        // The longest delay is twice the nominal one, with full jitter.
+        if Duration::try_from_secs_f64(2.0 / (chars_per_sec * EVENTS_PER_CHAR)).is_err() {
+            return Err(Error::InvalidInput(format!(
+                "Typing speed of {} characters per second is too slow",
+                chars_per_sec
+            )));
+        }
+        Ok(TypingSpeed {
+            chars_per_sec,
+            jitter: 0.0,
+        })
+    }
+
+    /// Vary the delays between key events randomly.
+    ///
+    /// # Parameters
+    ///
+    /// * jitter: `f64` - Maximum deviation from the nominal delay as a fraction of it, between
+    ///   `0.0` and `1.0`. A jitter of `0.25` results in delays between 75% and 125% of the nominal
+    ///   delay.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(TypingSpeed)` - The typing speed with jitter.
+    /// * `Err(Error)` - If the jitter is outside of the allowed range.
+    pub fn with_jitter(self, jitter: f64) -> Result<TypingSpeed, Error> {
+        if !(0.0..=1.0).contains(&jitter) {
+            return Err(Error::InvalidInput(format!(
+                "Typing jitter must be between 0.0 and 1.0, got {}",
+                jitter
+            )));
+        }
+        Ok(TypingSpeed { jitter, ..self })
+    }
+
+    /// Number of characters typed per second.
+    pub fn chars_per_sec(&self) -> f64 {
+        self.chars_per_sec
+    }
+
+    /// Maximum deviation of the delays as a fraction of the nominal delay.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// The nominal delay after each key event.
+    pub fn key_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / (self.chars_per_sec * EVENTS_PER_CHAR))
+    }
+
+    /// The delay after the next key event, including jitter.
+    pub fn next_delay(&self) -> Duration {
+        if self.jitter == 0.0 {
+            return self.key_interval();
+        }
+        // Map the random number to a factor in [1 - jitter, 1 + jitter].
+        let unit: f64 = (next_random() >> 11) as f64 / (1u64 << 53) as f64;
+        let factor: f64 = 1.0 + self.jitter * (2.0 * unit - 1.0);
+        self.key_interval().mul_f64(factor)
+    }
+
+    /// Wait for the delay after a key event.
+    pub async fn wait(&self) {
+        tokio::time::sleep(self.next_delay()).await;
+    }
+}
+
+impl Default for TypingSpeed {
+    fn default() -> Self {
+        TypingSpeed {
+            chars_per_sec: DEFAULT_CHARS_PER_SEC,
+            jitter: 0.0,
+        }
+    }
+}
+
+/// Generate the next pseudo-random number for jitter (SplitMix64).
+///
+/// Jitter only has to break up regular timing, so a fast generator without external dependencies
+/// suffices.
+fn next_random() -> u64 {
+    let mut z: u64 = JITTER_STATE
+        .fetch_add(0x9e37_79b9_7f4a_7c15, Ordering::Relaxed)
+        .wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -1,3 +1,5 @@
 //! This module is used to interact with the VNC server in any capacity.
 pub mod keyboard;
+pub mod mouse;
+pub mod text;
 pub mod view;
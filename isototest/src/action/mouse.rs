@@ -0,0 +1,295 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Mouse Module
+//!
+//! This module handles pointer interactions between the VncClient and VncServer.
+//!
+//! It uses [`X11Event::PointerEvent`](https://docs.rs/vnc-rs/0.5.3/vnc/event/struct.ClientMouseEvent.html)
+//! to send the absolute pointer position together with the state of all buttons to the server,
+//! as described in the [RFC](https://www.rfc-editor.org/rfc/rfc6143.html#section-7.5.5).
+//!
+//! All coordinates are absolute and validated against the screen resolution passed by the
+//! caller, e.g. [`crate::session::Session::resolution`].
+use std::time::Duration;
+
+use log::{error, info, warn};
+use vnc::{client::VncClient, ClientMouseEvent, X11Event};
+
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// Time a button is held down during a click.
+const CLICK_HOLD: Duration = Duration::from_millis(50);
+
+/// Time between the two clicks of a double click.
+const DCLICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time between the pointer events of a drag, so the remote machine notices the movement.
+const DRAG_STEP_DELAY: Duration = Duration::from_millis(100);
+
+/// Mouse buttons.
+///
+/// # Members
+///
+/// * `Left` - The primary button.
+/// * `Middle` - The middle button or pressed scroll wheel.
+/// * `Right` - The secondary button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+impl MouseButton {
+    /// Bit of the button in the button mask of a pointer event.
+    fn mask(self) -> u8 {
+        match self {
+            MouseButton::Left => 1,
+            MouseButton::Middle => 1 << 1,
+            MouseButton::Right => 1 << 2,
+        }
+    }
+}
+
+/// Directions of the scroll wheel.
+///
+/// Scrolling is sent as presses of the virtual buttons 4 to 7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl ScrollDirection {
+    /// Bit of the virtual scroll button in the button mask of a pointer event.
+    fn mask(self) -> u8 {
+        match self {
+            ScrollDirection::Up => 1 << 3,
+            ScrollDirection::Down => 1 << 4,
+            ScrollDirection::Left => 1 << 5,
+            ScrollDirection::Right => 1 << 6,
+        }
+    }
+}
+
+/// Move the pointer to an absolute position.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections.
+/// * resolution: `(u32, u32)` - The current screen resolution.
+/// * x: `u32` - Horizontal target position.
+/// * y: `u32` - Vertical target position.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the pointer event has been sent.
+/// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+pub async fn mouse_move(
+    client: &VncClient,
+    resolution: (u32, u32),
+    x: u32,
+    y: u32,
+) -> Result<(), Error> {
+    let pos: (u16, u16) = validate_position(resolution, x, y)?;
+    info!(target: LOG_TARGET, "Moving pointer to ({}, {})...", x, y);
+    pointer_event(client, pos, 0).await
+}
+
+/// Click a mouse button at an absolute position.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections.
+/// * resolution: `(u32, u32)` - The current screen resolution.
+/// * x: `u32` - Horizontal position of the click.
+/// * y: `u32` - Vertical position of the click.
+/// * button: `MouseButton` - The button to click.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the click has been sent.
+/// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+pub async fn mouse_click(
+    client: &VncClient,
+    resolution: (u32, u32),
+    x: u32,
+    y: u32,
+    button: MouseButton,
+) -> Result<(), Error> {
+    let pos: (u16, u16) = validate_position(resolution, x, y)?;
+    info!(target: LOG_TARGET, "Clicking {:?} button at ({}, {})...", button, x, y);
+    click(client, pos, button.mask()).await
+}
+
+/// Double click a mouse button at an absolute position.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections.
+/// * resolution: `(u32, u32)` - The current screen resolution.
+/// * x: `u32` - Horizontal position of the double click.
+/// * y: `u32` - Vertical position of the double click.
+/// * button: `MouseButton` - The button to click.
+///
+/// # Returns
+///
+/// * `Ok(())` - If both clicks have been sent.
+/// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+pub async fn mouse_dclick(
+    client: &VncClient,
+    resolution: (u32, u32),
+    x: u32,
+    y: u32,
+    button: MouseButton,
+) -> Result<(), Error> {
+    let pos: (u16, u16) = validate_position(resolution, x, y)?;
+    info!(target: LOG_TARGET, "Double clicking {:?} button at ({}, {})...", button, x, y);
+    click(client, pos, button.mask()).await?;
+    tokio::time::sleep(DCLICK_INTERVAL).await;
+    click(client, pos, button.mask()).await
+}
+
+/// Drag the pointer from one position to another while holding a button.
+///
+/// Once pressed, the button is released at `to` even if moving the pointer fails, so it is not
+/// left held on the remote machine.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections.
+/// * resolution: `(u32, u32)` - The current screen resolution.
+/// * from: `(u32, u32)` - Position at which the button is pressed.
+/// * to: `(u32, u32)` - Position at which the button is released.
+/// * button: `MouseButton` - The button to hold.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the drag has been sent.
+/// * `Err(Error)` - If a position is outside of the screen or the transaction fails.
+pub async fn mouse_drag(
+    client: &VncClient,
+    resolution: (u32, u32),
+    from: (u32, u32),
+    to: (u32, u32),
+    button: MouseButton,
+) -> Result<(), Error> {
+    let start: (u16, u16) = validate_position(resolution, from.0, from.1)?;
+    let end: (u16, u16) = validate_position(resolution, to.0, to.1)?;
+    info!(target: LOG_TARGET, "Dragging {:?} button from {:?} to {:?}...", button, from, to);
+
+    pointer_event(client, start, 0).await?;
+    tokio::time::sleep(DRAG_STEP_DELAY).await;
+    pointer_event(client, start, button.mask()).await?;
+    tokio::time::sleep(DRAG_STEP_DELAY).await;
+    let moved: Result<(), Error> = pointer_event(client, end, button.mask()).await;
+    if moved.is_ok() {
+        tokio::time::sleep(DRAG_STEP_DELAY).await;
+    }
+    if let Err(e) = pointer_event(client, end, 0).await {
+        warn!(target: LOG_TARGET, "Unable to release {:?} button: {}", button, e);
+        return moved.and(Err(e));
+    }
+    moved
+}
+
+/// Move the pointer into the bottom right corner of the screen.
+///
+/// Most systems do not render the pointer there, so it cannot obstruct screenshots.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections.
+/// * resolution: `(u32, u32)` - The current screen resolution.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the pointer event has been sent.
+/// * `Err(Error)` - If the resolution is empty or the transaction fails.
+pub async fn mouse_hide(client: &VncClient, resolution: (u32, u32)) -> Result<(), Error> {
+    let (x, y): (u32, u32) = (
+        resolution.0.saturating_sub(1),
+        resolution.1.saturating_sub(1),
+    );
+    let pos: (u16, u16) = validate_position(resolution, x, y)?;
+    info!(target: LOG_TARGET, "Hiding pointer...");
+    pointer_event(client, pos, 0).await
+}
+
+/// Turn the scroll wheel at an absolute position.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client to be used for connections.
+/// * resolution: `(u32, u32)` - The current screen resolution.
+/// * x: `u32` - Horizontal position of the pointer.
+/// * y: `u32` - Vertical position of the pointer.
+/// * direction: `ScrollDirection` - The direction to scroll in.
+/// * steps: `u32` - Number of wheel steps.
+///
+/// # Returns
+///
+/// * `Ok(())` - If all steps have been sent.
+/// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+pub async fn mouse_scroll(
+    client: &VncClient,
+    resolution: (u32, u32),
+    x: u32,
+    y: u32,
+    direction: ScrollDirection,
+    steps: u32,
+) -> Result<(), Error> {
+    let pos: (u16, u16) = validate_position(resolution, x, y)?;
+    info!(target: LOG_TARGET, "Scrolling {:?} {} steps at ({}, {})...", direction, steps, x, y);
+    for _ in 0..steps {
+        // Each wheel step is a press and release of the virtual scroll button.
+        pointer_event(client, pos, direction.mask()).await?;
+        pointer_event(client, pos, 0).await?;
+    }
+    Ok(())
+}
+
+/// Press and release the buttons of the given mask.
+async fn click(client: &VncClient, pos: (u16, u16), mask: u8) -> Result<(), Error> {
+    pointer_event(client, pos, 0).await?;
+    pointer_event(client, pos, mask).await?;
+    tokio::time::sleep(CLICK_HOLD).await;
+    pointer_event(client, pos, 0).await
+}
+
+/// Encapsulate the `client.input()` function calls to avoid repetition.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - Reference to the client used for communication.
+/// * pos: `(u16, u16)` - The absolute pointer position.
+/// * buttons: `u8` - Mask of all buttons which are held down.
+async fn pointer_event(client: &VncClient, pos: (u16, u16), buttons: u8) -> Result<(), Error> {
+    let mevent: ClientMouseEvent = ClientMouseEvent {
+        position_x: pos.0,
+        position_y: pos.1,
+        bottons: buttons,
+    };
+    client.input(X11Event::PointerEvent(mevent)).await?;
+    Ok(())
+}
+
+/// Check that a position lies on the screen.
+///
+/// # Returns
+///
+/// * `Ok((u16, u16))` - The position as required by pointer events.
+/// * `Err(Error)` - If the position is outside of the screen.
+fn validate_position(resolution: (u32, u32), x: u32, y: u32) -> Result<(u16, u16), Error> {
+    if x >= resolution.0 || y >= resolution.1 || x > u16::MAX as u32 || y > u16::MAX as u32 {
+        error!(target: LOG_TARGET, "Position ({}, {}) is outside of the screen.", x, y);
+        return Err(Error::InvalidInput(format!(
+            "Position ({}, {}) is outside of the screen resolution {}x{}",
+            x, y, resolution.0, resolution.1
+        )));
+    }
+    Ok((x as u16, y as u16))
+}
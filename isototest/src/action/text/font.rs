@@ -0,0 +1,477 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Font module
+//!
+//! Bitmap fonts used to recognize the characters of a text console. The printable ASCII
+//! characters of the standard VGA 8x16 font are bundled, see [`Font::vga_8x16`]. Linux console
+//! fonts can be loaded from PC Screen Font files (`.psf`, `.psfu`, optionally gzip compressed),
+//! see [`Font::from_psf`].
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use log::{debug, error};
+
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// Magic number of PSF version 1 fonts.
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+
+/// Mode flag of PSF version 1 fonts with 512 glyphs.
+const PSF1_MODE_512: u8 = 0x01;
+
+/// Mode flags of PSF version 1 fonts with a unicode table.
+const PSF1_MODE_HAS_TAB: u8 = 0x02 | 0x04;
+
+/// Magic number of PSF version 2 fonts.
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+
+/// Flag of PSF version 2 fonts with a unicode table.
+const PSF2_HAS_UNICODE_TABLE: u32 = 0x01;
+
+/// Magic number of gzip compressed data.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A bitmap font.
+///
+/// Each glyph is stored row by row, every row padded to full bytes with the leftmost pixel in the
+/// most significant bit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    width: u32,
+    height: u32,
+    glyphs: Vec<(char, Vec<u8>)>,
+}
+
+impl Font {
+    /// Create a font from its glyphs.
+    ///
+    /// # Parameters
+    ///
+    /// * width: `u32` - Width of a glyph in pixels.
+    /// * height: `u32` - Height of a glyph in pixels.
+    /// * glyphs: `Vec<(char, Vec<u8>)>` - The characters and their bitmaps.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Font)` - The font.
+    /// * `Err(Error)` - If the font is empty or a bitmap does not match the glyph size.
+    pub fn new(width: u32, height: u32, glyphs: Vec<(char, Vec<u8>)>) -> Result<Font, Error> {
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidInput(format!(
+                "Font glyphs must not be empty, got {}x{}",
+                width, height
+            )));
+        }
+        let size: usize = width.div_ceil(8) as usize * height as usize;
+        if let Some((ch, _)) = glyphs.iter().find(|(_, bitmap)| bitmap.len() != size) {
+            return Err(Error::InvalidInput(format!(
+                "Bitmap of glyph '{}' does not match the glyph size {}x{}",
+                ch.escape_default(),
+                width,
+                height
+            )));
+        }
+        Ok(Font {
+            width,
+            height,
+            glyphs,
+        })
+    }
+
+    /// The printable ASCII characters of the standard VGA 8x16 font.
+    ///
+    /// This is the font of the VGA text mode and the default font of the Linux console on
+    /// framebuffers of most sizes.
+    pub fn vga_8x16() -> Font {
+        Font {
+            width: 8,
+            height: 16,
+            glyphs: VGA_8X16
+                .iter()
+                .zip(' '..='~')
+                .map(|(bitmap, ch)| (ch, bitmap.to_vec()))
+                .collect(),
+        }
+    }
+
+    /// Load a Linux console font from a file.
+    ///
+    /// # Parameters
+    ///
+    /// * path: `&Path` - The PSF file, optionally gzip compressed.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Font)` - The font.
+    /// * `Err(Error)` - If the file cannot be read or is not a valid PSF font.
+    pub fn load(path: &Path) -> Result<Font, Error> {
+        let data: Vec<u8> = std::fs::read(path).map_err(|e| {
+            error!(target: LOG_TARGET, "Unable to read font '{}': {}", path.display(), e);
+            Error::InvalidInput(format!("Unable to read font '{}': {}", path.display(), e))
+        })?;
+        Font::from_psf(&data)
+    }
+
+    /// Parse a PC Screen Font, the format of Linux console fonts.
+    ///
+    /// Both PSF versions are supported. Glyphs are assigned the characters of the font's unicode
+    /// table. Fonts without a table are assumed to follow the ASCII order, so only their printable
+    /// ASCII glyphs are used.
+    ///
+    /// # Parameters
+    ///
+    /// * data: `&[u8]` - The content of the PSF file, optionally gzip compressed.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Font)` - The font.
+    /// * `Err(Error)` - If the data is not a valid PSF font.
+    pub fn from_psf(data: &[u8]) -> Result<Font, Error> {
+        if data.starts_with(&GZIP_MAGIC) {
+            let mut decompressed: Vec<u8> = Vec::new();
+            GzDecoder::new(data)
+                .read_to_end(&mut decompressed)
+                .map_err(|e| invalid_psf(&format!("Unable to decompress font: {}", e)))?;
+            return Font::from_psf(&decompressed);
+        }
+
+        let (width, height, count, size, offset, has_table): (u32, u32, usize, usize, usize, bool) =
+            if data.starts_with(&PSF1_MAGIC) && data.len() >= 4 {
+                let mode: u8 = data[2];
+                let height: u32 = data[3] as u32;
+                let count: usize = if mode & PSF1_MODE_512 != 0 { 512 } else { 256 };
+                (
+                    8,
+                    height,
+                    count,
+                    height as usize,
+                    4,
+                    mode & PSF1_MODE_HAS_TAB != 0,
+                )
+            } else if data.starts_with(&PSF2_MAGIC) && data.len() >= 32 {
+                let field = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+                (
+                    field(28),
+                    field(24),
+                    field(16) as usize,
+                    field(20) as usize,
+                    field(8) as usize,
+                    field(12) & PSF2_HAS_UNICODE_TABLE != 0,
+                )
+            } else {
+                return Err(invalid_psf("Not a PSF font"));
+            };
+        if width == 0 || height == 0 || size != width.div_ceil(8) as usize * height as usize {
+            return Err(invalid_psf(&format!(
+                "Invalid glyph size {}x{} with {} bytes",
+                width, height, size
+            )));
+        }
+        let end: usize = count
+            .checked_mul(size)
+            .and_then(|len| len.checked_add(offset))
+            .filter(|end| *end <= data.len())
+            .ok_or_else(|| invalid_psf("Font data is truncated"))?;
+        let bitmaps: Vec<&[u8]> = data[offset..end].chunks_exact(size).collect();
+
+        let chars: Vec<Vec<char>> = if has_table {
+            let table: &[u8] = &data[end..];
+            if data.starts_with(&PSF1_MAGIC) {
+                psf1_table(table, count)
+            } else {
+                psf2_table(table, count)
+            }
+        } else {
+            (0..count)
+                .map(|i| match char::from_u32(i as u32) {
+                    Some(ch) if (' '..='~').contains(&ch) => vec![ch],
+                    _ => Vec::new(),
+                })
+                .collect()
+        };
+
+        let mut glyphs: Vec<(char, Vec<u8>)> = Vec::new();
+        for (bitmap, chars) in bitmaps.iter().zip(chars) {
+            for ch in chars {
+                glyphs.push((ch, bitmap.to_vec()));
+            }
+        }
+        debug!(target: LOG_TARGET, "Loaded {}x{} font with {} characters.", width, height, glyphs.len());
+        Font::new(width, height, glyphs)
+    }
+
+    /// Width of a glyph in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of a glyph in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The bitmap of a character, if the font contains it.
+    pub fn glyph(&self, ch: char) -> Option<&[u8]> {
+        self.glyphs
+            .iter()
+            .find(|(c, _)| *c == ch)
+            .map(|(_, bitmap)| bitmap.as_slice())
+    }
+
+    /// All characters and their bitmaps.
+    pub fn glyphs(&self) -> &[(char, Vec<u8>)] {
+        &self.glyphs
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font::vga_8x16()
+    }
+}
+
+/// Characters of the glyphs in the unicode table of a PSF version 1 font.
+///
+/// Each glyph's entry is a list of UCS-2 characters, followed by sequences starting with 0xFFFE,
+/// and terminated by 0xFFFF. Sequences are ignored.
+fn psf1_table(table: &[u8], count: usize) -> Vec<Vec<char>> {
+    let mut values = table
+        .chunks_exact(2)
+        .map(|v| u16::from_le_bytes([v[0], v[1]]));
+    (0..count)
+        .map(|_| {
+            let mut chars: Vec<char> = Vec::new();
+            let mut sequence: bool = false;
+            for value in values.by_ref() {
+                match value {
+                    0xffff => break,
+                    0xfffe => sequence = true,
+                    v if !sequence => chars.extend(char::from_u32(v as u32)),
+                    _ => {}
+                }
+            }
+            chars
+        })
+        .collect()
+}
+
+/// Characters of the glyphs in the unicode table of a PSF version 2 font.
+///
+/// Each glyph's entry is a UTF-8 string, followed by sequences starting with 0xFE, and terminated
+/// by 0xFF. Sequences are ignored.
+fn psf2_table(table: &[u8], count: usize) -> Vec<Vec<char>> {
+    let mut entries = table.split(|b| *b == 0xff);
+    (0..count)
+        .map(|_| {
+            let entry: &[u8] = entries.next().unwrap_or_default();
+            let single: &[u8] = entry.split(|b| *b == 0xfe).next().unwrap_or_default();
+            String::from_utf8_lossy(single)
+                .chars()
+                .filter(|ch| *ch != char::REPLACEMENT_CHARACTER)
+                .collect()
+        })
+        .collect()
+}
+
+/// Log and return the error for an invalid PSF font.
+fn invalid_psf(msg: &str) -> Error {
+    error!(target: LOG_TARGET, "Invalid PSF font: {}", msg);
+    Error::InvalidInput(format!("Invalid PSF font: {}", msg))
+}
+
+/// The printable ASCII characters of the VGA 8x16 font, from `' '` to `'~'`.
+#[rustfmt::skip]
+const VGA_8X16: [[u8; 16]; 95] = [
+    // ' '
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '!'
+    [0x00, 0x00, 0x18, 0x3c, 0x3c, 0x3c, 0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00],
+    // '"'
+    [0x00, 0x66, 0x66, 0x66, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '#'
+    [0x00, 0x00, 0x00, 0x6c, 0x6c, 0xfe, 0x6c, 0x6c, 0x6c, 0xfe, 0x6c, 0x6c, 0x00, 0x00, 0x00, 0x00],
+    // '$'
+    [0x18, 0x18, 0x7c, 0xc6, 0xc2, 0xc0, 0x7c, 0x06, 0x06, 0x86, 0xc6, 0x7c, 0x18, 0x18, 0x00, 0x00],
+    // '%'
+    [0x00, 0x00, 0x00, 0x00, 0xc2, 0xc6, 0x0c, 0x18, 0x30, 0x60, 0xc6, 0x86, 0x00, 0x00, 0x00, 0x00],
+    // '&'
+    [0x00, 0x00, 0x38, 0x6c, 0x6c, 0x38, 0x76, 0xdc, 0xcc, 0xcc, 0xcc, 0x76, 0x00, 0x00, 0x00, 0x00],
+    // "'"
+    [0x00, 0x30, 0x30, 0x30, 0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '('
+    [0x00, 0x00, 0x0c, 0x18, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x18, 0x0c, 0x00, 0x00, 0x00, 0x00],
+    // ')'
+    [0x00, 0x00, 0x30, 0x18, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x18, 0x30, 0x00, 0x00, 0x00, 0x00],
+    // '*'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x3c, 0xff, 0x3c, 0x66, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x7e, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // ','
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x18, 0x30, 0x00, 0x00, 0x00],
+    // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xfe, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '.'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00],
+    // '/'
+    [0x00, 0x00, 0x00, 0x00, 0x02, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xc0, 0x80, 0x00, 0x00, 0x00, 0x00],
+    // '0'
+    [0x00, 0x00, 0x38, 0x6c, 0xc6, 0xc6, 0xd6, 0xd6, 0xc6, 0xc6, 0x6c, 0x38, 0x00, 0x00, 0x00, 0x00],
+    // '1'
+    [0x00, 0x00, 0x18, 0x38, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7e, 0x00, 0x00, 0x00, 0x00],
+    // '2'
+    [0x00, 0x00, 0x7c, 0xc6, 0x06, 0x0c, 0x18, 0x30, 0x60, 0xc0, 0xc6, 0xfe, 0x00, 0x00, 0x00, 0x00],
+    // '3'
+    [0x00, 0x00, 0x7c, 0xc6, 0x06, 0x06, 0x3c, 0x06, 0x06, 0x06, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // '4'
+    [0x00, 0x00, 0x0c, 0x1c, 0x3c, 0x6c, 0xcc, 0xfe, 0x0c, 0x0c, 0x0c, 0x1e, 0x00, 0x00, 0x00, 0x00],
+    // '5'
+    [0x00, 0x00, 0xfe, 0xc0, 0xc0, 0xc0, 0xfc, 0x06, 0x06, 0x06, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // '6'
+    [0x00, 0x00, 0x38, 0x60, 0xc0, 0xc0, 0xfc, 0xc6, 0xc6, 0xc6, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // '7'
+    [0x00, 0x00, 0xfe, 0xc6, 0x06, 0x06, 0x0c, 0x18, 0x30, 0x30, 0x30, 0x30, 0x00, 0x00, 0x00, 0x00],
+    // '8'
+    [0x00, 0x00, 0x7c, 0xc6, 0xc6, 0xc6, 0x7c, 0xc6, 0xc6, 0xc6, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // '9'
+    [0x00, 0x00, 0x7c, 0xc6, 0xc6, 0xc6, 0x7e, 0x06, 0x06, 0x06, 0x0c, 0x78, 0x00, 0x00, 0x00, 0x00],
+    // ':'
+    [0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // ';'
+    [0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x18, 0x18, 0x30, 0x00, 0x00, 0x00, 0x00],
+    // '<'
+    [0x00, 0x00, 0x00, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x30, 0x18, 0x0c, 0x06, 0x00, 0x00, 0x00, 0x00],
+    // '='
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '>'
+    [0x00, 0x00, 0x00, 0x60, 0x30, 0x18, 0x0c, 0x06, 0x0c, 0x18, 0x30, 0x60, 0x00, 0x00, 0x00, 0x00],
+    // '?'
+    [0x00, 0x00, 0x7c, 0xc6, 0xc6, 0x0c, 0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00],
+    // '@'
+    [0x00, 0x00, 0x00, 0x7c, 0xc6, 0xc6, 0xde, 0xde, 0xde, 0xdc, 0xc0, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'A'
+    [0x00, 0x00, 0x10, 0x38, 0x6c, 0xc6, 0xc6, 0xfe, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, 0x00, 0x00],
+    // 'B'
+    [0x00, 0x00, 0xfc, 0x66, 0x66, 0x66, 0x7c, 0x66, 0x66, 0x66, 0x66, 0xfc, 0x00, 0x00, 0x00, 0x00],
+    // 'C'
+    [0x00, 0x00, 0x3c, 0x66, 0xc2, 0xc0, 0xc0, 0xc0, 0xc0, 0xc2, 0x66, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // 'D'
+    [0x00, 0x00, 0xf8, 0x6c, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x6c, 0xf8, 0x00, 0x00, 0x00, 0x00],
+    // 'E'
+    [0x00, 0x00, 0xfe, 0x66, 0x62, 0x68, 0x78, 0x68, 0x60, 0x62, 0x66, 0xfe, 0x00, 0x00, 0x00, 0x00],
+    // 'F'
+    [0x00, 0x00, 0xfe, 0x66, 0x62, 0x68, 0x78, 0x68, 0x60, 0x60, 0x60, 0xf0, 0x00, 0x00, 0x00, 0x00],
+    // 'G'
+    [0x00, 0x00, 0x3c, 0x66, 0xc2, 0xc0, 0xc0, 0xde, 0xc6, 0xc6, 0x66, 0x3a, 0x00, 0x00, 0x00, 0x00],
+    // 'H'
+    [0x00, 0x00, 0xc6, 0xc6, 0xc6, 0xc6, 0xfe, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, 0x00, 0x00],
+    // 'I'
+    [0x00, 0x00, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // 'J'
+    [0x00, 0x00, 0x1e, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0xcc, 0xcc, 0xcc, 0x78, 0x00, 0x00, 0x00, 0x00],
+    // 'K'
+    [0x00, 0x00, 0xe6, 0x66, 0x66, 0x6c, 0x78, 0x78, 0x6c, 0x66, 0x66, 0xe6, 0x00, 0x00, 0x00, 0x00],
+    // 'L'
+    [0x00, 0x00, 0xf0, 0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x62, 0x66, 0xfe, 0x00, 0x00, 0x00, 0x00],
+    // 'M'
+    [0x00, 0x00, 0xc6, 0xee, 0xfe, 0xfe, 0xd6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, 0x00, 0x00],
+    // 'N'
+    [0x00, 0x00, 0xc6, 0xe6, 0xf6, 0xfe, 0xde, 0xce, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, 0x00, 0x00],
+    // 'O'
+    [0x00, 0x00, 0x7c, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'P'
+    [0x00, 0x00, 0xfc, 0x66, 0x66, 0x66, 0x7c, 0x60, 0x60, 0x60, 0x60, 0xf0, 0x00, 0x00, 0x00, 0x00],
+    // 'Q'
+    [0x00, 0x00, 0x7c, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xd6, 0xde, 0x7c, 0x0c, 0x0e, 0x00, 0x00],
+    // 'R'
+    [0x00, 0x00, 0xfc, 0x66, 0x66, 0x66, 0x7c, 0x6c, 0x66, 0x66, 0x66, 0xe6, 0x00, 0x00, 0x00, 0x00],
+    // 'S'
+    [0x00, 0x00, 0x7c, 0xc6, 0xc6, 0x60, 0x38, 0x0c, 0x06, 0xc6, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'T'
+    [0x00, 0x00, 0x7e, 0x7e, 0x5a, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // 'U'
+    [0x00, 0x00, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'V'
+    [0x00, 0x00, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x6c, 0x38, 0x10, 0x00, 0x00, 0x00, 0x00],
+    // 'W'
+    [0x00, 0x00, 0xc6, 0xc6, 0xc6, 0xc6, 0xd6, 0xd6, 0xd6, 0xfe, 0xee, 0x6c, 0x00, 0x00, 0x00, 0x00],
+    // 'X'
+    [0x00, 0x00, 0xc6, 0xc6, 0x6c, 0x7c, 0x38, 0x38, 0x7c, 0x6c, 0xc6, 0xc6, 0x00, 0x00, 0x00, 0x00],
+    // 'Y'
+    [0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x18, 0x18, 0x18, 0x18, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // 'Z'
+    [0x00, 0x00, 0xfe, 0xc6, 0x86, 0x0c, 0x18, 0x30, 0x60, 0xc2, 0xc6, 0xfe, 0x00, 0x00, 0x00, 0x00],
+    // '['
+    [0x00, 0x00, 0x3c, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x30, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // '\\'
+    [0x00, 0x00, 0x00, 0x80, 0xc0, 0xe0, 0x70, 0x38, 0x1c, 0x0e, 0x06, 0x02, 0x00, 0x00, 0x00, 0x00],
+    // ']'
+    [0x00, 0x00, 0x3c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x0c, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // '^'
+    [0x10, 0x38, 0x6c, 0xc6, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // '_'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00],
+    // '`'
+    [0x30, 0x30, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    // 'a'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x78, 0x0c, 0x7c, 0xcc, 0xcc, 0xcc, 0x76, 0x00, 0x00, 0x00, 0x00],
+    // 'b'
+    [0x00, 0x00, 0xe0, 0x60, 0x60, 0x78, 0x6c, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'c'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0xc6, 0xc0, 0xc0, 0xc0, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'd'
+    [0x00, 0x00, 0x1c, 0x0c, 0x0c, 0x3c, 0x6c, 0xcc, 0xcc, 0xcc, 0xcc, 0x76, 0x00, 0x00, 0x00, 0x00],
+    // 'e'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0xc6, 0xfe, 0xc0, 0xc0, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'f'
+    [0x00, 0x00, 0x1c, 0x36, 0x32, 0x30, 0x78, 0x30, 0x30, 0x30, 0x30, 0x78, 0x00, 0x00, 0x00, 0x00],
+    // 'g'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x76, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x0c, 0xcc, 0x78, 0x00],
+    // 'h'
+    [0x00, 0x00, 0xe0, 0x60, 0x60, 0x6c, 0x76, 0x66, 0x66, 0x66, 0x66, 0xe6, 0x00, 0x00, 0x00, 0x00],
+    // 'i'
+    [0x00, 0x00, 0x18, 0x18, 0x00, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // 'j'
+    [0x00, 0x00, 0x06, 0x06, 0x00, 0x0e, 0x06, 0x06, 0x06, 0x06, 0x06, 0x06, 0x66, 0x66, 0x3c, 0x00],
+    // 'k'
+    [0x00, 0x00, 0xe0, 0x60, 0x60, 0x66, 0x6c, 0x78, 0x78, 0x6c, 0x66, 0xe6, 0x00, 0x00, 0x00, 0x00],
+    // 'l'
+    [0x00, 0x00, 0x38, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3c, 0x00, 0x00, 0x00, 0x00],
+    // 'm'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xec, 0xfe, 0xd6, 0xd6, 0xd6, 0xd6, 0xc6, 0x00, 0x00, 0x00, 0x00],
+    // 'n'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xdc, 0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x00, 0x00, 0x00, 0x00],
+    // 'o'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 'p'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xdc, 0x66, 0x66, 0x66, 0x66, 0x66, 0x7c, 0x60, 0x60, 0xf0, 0x00],
+    // 'q'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x76, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x7c, 0x0c, 0x0c, 0x1e, 0x00],
+    // 'r'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xdc, 0x76, 0x66, 0x60, 0x60, 0x60, 0xf0, 0x00, 0x00, 0x00, 0x00],
+    // 's'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x7c, 0xc6, 0x60, 0x38, 0x0c, 0xc6, 0x7c, 0x00, 0x00, 0x00, 0x00],
+    // 't'
+    [0x00, 0x00, 0x10, 0x30, 0x30, 0xfc, 0x30, 0x30, 0x30, 0x30, 0x36, 0x1c, 0x00, 0x00, 0x00, 0x00],
+    // 'u'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0xcc, 0x76, 0x00, 0x00, 0x00, 0x00],
+    // 'v'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3c, 0x18, 0x00, 0x00, 0x00, 0x00],
+    // 'w'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xc6, 0xc6, 0xd6, 0xd6, 0xd6, 0xfe, 0x6c, 0x00, 0x00, 0x00, 0x00],
+    // 'x'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xc6, 0x6c, 0x38, 0x38, 0x38, 0x6c, 0xc6, 0x00, 0x00, 0x00, 0x00],
+    // 'y'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0xc6, 0x7e, 0x06, 0x0c, 0xf8, 0x00],
+    // 'z'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0xfe, 0xcc, 0x18, 0x30, 0x60, 0xc6, 0xfe, 0x00, 0x00, 0x00, 0x00],
+    // '{'
+    [0x00, 0x00, 0x0e, 0x18, 0x18, 0x18, 0x70, 0x18, 0x18, 0x18, 0x18, 0x0e, 0x00, 0x00, 0x00, 0x00],
+    // '|'
+    [0x00, 0x00, 0x18, 0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00, 0x00, 0x00, 0x00],
+    // '}'
+    [0x00, 0x00, 0x70, 0x18, 0x18, 0x18, 0x0e, 0x18, 0x18, 0x18, 0x18, 0x70, 0x00, 0x00, 0x00, 0x00],
+    // '~'
+    [0x00, 0x00, 0x76, 0xdc, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
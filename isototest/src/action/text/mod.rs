@@ -0,0 +1,249 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Text module
+//!
+//! This module reads the characters of text consoles, like the VGA text mode or the Linux
+//! console, from the screen. The screen is split into cells of the font's glyph size, and each
+//! cell is compared against the glyphs of a bitmap [`font::Font`]. Foreground and background
+//! colours do not matter, so coloured and inverted text is recognized as well.
+//!
+//! Checking for short texts like `login:` this way does not require needles.
+use std::fmt;
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use log::{error, info};
+use regex::Regex;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+use crate::session::Session;
+
+pub mod font;
+
+use font::Font;
+
+/// Character of cells which do not match any glyph of the font.
+pub const UNKNOWN_CHAR: char = char::REPLACEMENT_CHARACTER;
+
+/// Minimum difference of the summed colour channels between foreground and background pixels.
+const CONTRAST: u32 = 96;
+
+/// A cell matches a glyph if at most one in this many of its pixels differ.
+const GLYPH_TOLERANCE: usize = 16;
+
+/// The characters of a text console.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextGrid {
+    columns: usize,
+    rows: usize,
+    cells: Vec<char>,
+}
+
+impl TextGrid {
+    /// Number of characters per line.
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Number of lines.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The character at a position, if it is on the screen.
+    ///
+    /// Cells not matching any glyph hold [`UNKNOWN_CHAR`].
+    pub fn char_at(&self, column: usize, row: usize) -> Option<char> {
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+        Some(self.cells[row * self.columns + column])
+    }
+
+    /// The lines of the screen, without trailing spaces.
+    pub fn lines(&self) -> Vec<String> {
+        self.cells
+            .chunks(self.columns.max(1))
+            .take(self.rows)
+            .map(|line| line.iter().collect::<String>().trim_end().to_string())
+            .collect()
+    }
+}
+
+impl fmt::Display for TextGrid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.lines().join("\n"))
+    }
+}
+
+/// Recognize the characters of a text console.
+///
+/// Text starts in the top left corner of the image. Cells are as large as the font's glyphs, or
+/// one pixel wider for 8 pixel wide fonts, as the VGA text mode renders them 9 pixels wide. The
+/// cell width recognizing more characters is used.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screen.
+/// * font: `&Font` - The font of the console.
+///
+/// # Returns
+///
+/// * `TextGrid` - The characters on the screen.
+pub fn read_text(image: &RgbaImage, font: &Font) -> TextGrid {
+    let mut widths: Vec<u32> = vec![font.width()];
+    if font.width() == 8 {
+        widths.push(9);
+    }
+    widths
+        .into_iter()
+        .map(|width| read_cells(image, font, width))
+        // Prefer the narrower cells if both recognize the same number of characters.
+        .min_by_key(|grid| grid.cells.iter().filter(|ch| **ch == UNKNOWN_CHAR).count())
+        .unwrap_or_else(|| read_cells(image, font, font.width()))
+}
+
+/// Get the text on the session's screen.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session whose screen is read. Its text font is used, see
+///   [`Session::set_text_font`].
+///
+/// # Returns
+///
+/// * `TextGrid` - The characters on the screen.
+pub fn screen_text(session: &Session) -> TextGrid {
+    read_text(&session.frame(), &session.text_font())
+}
+
+/// Wait for text matching a regular expression to appear on the screen.
+///
+/// The pattern is matched against the lines of the screen joined by newlines, see
+/// [`TextGrid`]'s `Display` implementation. The screen is always evaluated at least once, even if
+/// `timeout` is zero.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session whose screen is read.
+/// * pattern: `&str` - The regular expression, e.g. `r"login:\s*$"`.
+/// * timeout: `Duration` - How long to wait for the text.
+///
+/// # Returns
+///
+/// * `Ok(String)` - The matching text.
+/// * `Err(Error)` - If the pattern is invalid, the connection is lost or the text did not appear
+///   in time (`Error::Timeout`).
+pub async fn wait_for_text(
+    session: &Session,
+    pattern: &str,
+    timeout: Duration,
+) -> Result<String, Error> {
+    let regex: Regex = Regex::new(pattern).map_err(|e| {
+        Error::InvalidInput(format!("Invalid regular expression '{}': {}", pattern, e))
+    })?;
+    info!(target: LOG_TARGET, "Waiting {:?} for text matching '{}'...", timeout, pattern);
+    let deadline: Instant = Instant::now() + timeout;
+    let mut updates: watch::Receiver<u64> = session.frame_updates();
+
+    loop {
+        updates.mark_unchanged();
+        let text: String = screen_text(session).to_string();
+        if let Some(found) = regex.find(&text) {
+            info!(target: LOG_TARGET, "Text '{}' found.", found.as_str());
+            return Ok(found.as_str().to_string());
+        }
+
+        match tokio::time::timeout_at(deadline, updates.changed()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(_)) => return Err(Error::ConnectionError("Session closed".to_string())),
+            Err(_) => {
+                error!(target: LOG_TARGET, "No text matching '{}' within {:?}.", pattern, timeout);
+                return Err(Error::Timeout(format!(
+                    "No text matching '{}' appeared within {:?}",
+                    pattern, timeout
+                )));
+            }
+        }
+    }
+}
+
+/// Recognize the characters of cells with the given width.
+fn read_cells(image: &RgbaImage, font: &Font, cell_width: u32) -> TextGrid {
+    let columns: usize = (image.width() / cell_width) as usize;
+    let rows: usize = (image.height() / font.height()) as usize;
+    let mut cells: Vec<char> = Vec::with_capacity(columns * rows);
+    for row in 0..rows as u32 {
+        for column in 0..columns as u32 {
+            let origin: (u32, u32) = (column * cell_width, row * font.height());
+            cells.push(read_cell(image, font, origin, cell_width));
+        }
+    }
+    TextGrid {
+        columns,
+        rows,
+        cells,
+    }
+}
+
+/// Recognize the character of the cell at `origin`.
+fn read_cell(image: &RgbaImage, font: &Font, origin: (u32, u32), cell_width: u32) -> char {
+    let background: Rgba<u8> = background(image, origin, (cell_width, font.height()));
+    let stride: usize = font.width().div_ceil(8) as usize;
+    let mut bitmap: Vec<u8> = vec![0; stride * font.height() as usize];
+    let mut empty: bool = true;
+    for y in 0..font.height() {
+        for x in 0..font.width() {
+            let px: &Rgba<u8> = image.get_pixel(origin.0 + x, origin.1 + y);
+            let diff: u32 = px.0[..3]
+                .iter()
+                .zip(&background.0[..3])
+                .map(|(a, b)| a.abs_diff(*b) as u32)
+                .sum();
+            if diff >= CONTRAST {
+                bitmap[y as usize * stride + x as usize / 8] |= 0x80 >> (x % 8);
+                empty = false;
+            }
+        }
+    }
+    if empty {
+        return ' ';
+    }
+
+    let tolerance: usize = (font.width() * font.height()) as usize / GLYPH_TOLERANCE;
+    font.glyphs()
+        .iter()
+        .map(|(ch, glyph)| {
+            let distance: usize = glyph
+                .iter()
+                .zip(&bitmap)
+                .map(|(a, b)| (a ^ b).count_ones() as usize)
+                .sum();
+            (distance, *ch)
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .filter(|(distance, _)| *distance <= tolerance)
+        .map_or(UNKNOWN_CHAR, |(_, ch)| ch)
+}
+
+/// The most frequent colour of a cell, which is taken as its background.
+fn background(image: &RgbaImage, origin: (u32, u32), size: (u32, u32)) -> Rgba<u8> {
+    let mut colours: Vec<[u8; 3]> = Vec::with_capacity((size.0 * size.1) as usize);
+    for y in origin.1..origin.1 + size.1 {
+        for x in origin.0..origin.0 + size.0 {
+            let px: &Rgba<u8> = image.get_pixel(x, y);
+            colours.push([px.0[0], px.0[1], px.0[2]]);
+        }
+    }
+    colours.sort_unstable();
+    let mut best: ([u8; 3], usize) = ([0; 3], 0);
+    for run in colours.chunk_by(|a, b| a == b) {
+        if run.len() > best.1 {
+            best = (run[0], run.len());
+        }
+    }
+    Rgba([best.0[0], best.0[1], best.0[2], 255])
+}
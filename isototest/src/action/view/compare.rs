@@ -0,0 +1,218 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Compare module
+//!
+//! This module compares whole screenshots with each other, e.g. to detect whether the screen is
+//! still changing. Like in openQA, the similarity of two frames is their peak signal-to-noise
+//! ratio in dB. Unlike the similarity of needle matches (see [`super::needle`]), the scale is not
+//! capped, as a single typed character changes too few pixels of the screen to lower the ratio
+//! below the level at which needle areas are considered identical.
+//!
+//! Regions which change regardless of the state of the remote machine, like a blinking cursor or
+//! a clock, can be ignored during the comparison.
+//!
+//! [`diff`] finds the regions in which two frames differ.
+use image::RgbaImage;
+
+use super::damage::merge_regions;
+use super::needle::psnr;
+
+/// Similarity in dB from which two frames are considered equal by default.
+///
+/// A character typed on the text console of a screen of 1024x768 pixels lowers the similarity to
+/// about 47 dB. The larger the screen, the less a single character counts, so pass a higher
+/// similarity to notice single characters on screens beyond 1280x1024 pixels.
+pub const DEFAULT_SIMILARITY: f64 = 50.0;
+
+/// Width and height of the tiles in which [`diff`] groups changed pixels.
+pub const DIFF_TILE_SIZE: u32 = 16;
+
+/// A rectangular region of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// Create a new region.
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Check whether a pixel lies within the region.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && (x - self.x) < self.width && (y - self.y) < self.height
+    }
+
+    /// Check whether the region covers no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Number of pixels covered by the region.
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    /// The pixels covered by both regions, if there are any.
+    pub fn intersection(&self, other: &Region) -> Option<Region> {
+        let x: u32 = self.x.max(other.x);
+        let y: u32 = self.y.max(other.y);
+        let right: u64 = self.right().min(other.right());
+        let bottom: u64 = self.bottom().min(other.bottom());
+        if right <= x as u64 || bottom <= y as u64 {
+            return None;
+        }
+        Some(Region::new(
+            x,
+            y,
+            (right - x as u64) as u32,
+            (bottom - y as u64) as u32,
+        ))
+    }
+
+    /// The smallest region covering both regions.
+    pub fn union(&self, other: &Region) -> Region {
+        let x: u32 = self.x.min(other.x);
+        let y: u32 = self.y.min(other.y);
+        let right: u64 = self.right().max(other.right());
+        let bottom: u64 = self.bottom().max(other.bottom());
+        Region::new(x, y, (right - x as u64) as u32, (bottom - y as u64) as u32)
+    }
+
+    /// Check whether the regions overlap or share an edge.
+    pub fn touches(&self, other: &Region) -> bool {
+        self.x as u64 <= other.right()
+            && other.x as u64 <= self.right()
+            && self.y as u64 <= other.bottom()
+            && other.y as u64 <= self.bottom()
+    }
+
+    /// Horizontal position right of the region.
+    fn right(&self) -> u64 {
+        self.x as u64 + self.width as u64
+    }
+
+    /// Vertical position below the region.
+    fn bottom(&self) -> u64 {
+        self.y as u64 + self.height as u64
+    }
+}
+
+/// The differences between two frames.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameDiff {
+    /// The changed regions, see [`diff`].
+    pub regions: Vec<Region>,
+    /// Number of changed pixels.
+    pub changed_pixels: u64,
+    /// Share of changed pixels in percent.
+    pub percentage: f64,
+}
+
+impl FrameDiff {
+    /// Check whether the frames are identical.
+    pub fn is_empty(&self) -> bool {
+        self.changed_pixels == 0
+    }
+}
+
+/// Find the regions in which two frames differ.
+///
+/// The frames are compared exactly, every pixel with a different colour counts as changed. The
+/// changed pixels are grouped into tiles of [`DIFF_TILE_SIZE`] pixels, and overlapping or adjacent
+/// tiles are merged into their bounding box, so the regions cover all changed pixels, but may
+/// contain unchanged ones as well.
+///
+/// # Parameters
+///
+/// * a: `&RgbaImage` - The first frame.
+/// * b: `&RgbaImage` - The second frame.
+///
+/// # Returns
+///
+/// * `FrameDiff` - The changed regions and the share of changed pixels. Frames of different sizes
+///   differ completely, their regions cover the second frame.
+pub fn diff(a: &RgbaImage, b: &RgbaImage) -> FrameDiff {
+    let (width, height): (u32, u32) = b.dimensions();
+    if a.dimensions() != b.dimensions() {
+        let screen: Region = Region::new(0, 0, width, height);
+        return FrameDiff {
+            regions: merge_regions([screen]),
+            changed_pixels: screen.area(),
+            percentage: 100.0,
+        };
+    }
+
+    let mut changed_pixels: u64 = 0;
+    let mut tiles: Vec<Region> = Vec::new();
+    for tile_y in (0..height).step_by(DIFF_TILE_SIZE as usize) {
+        for tile_x in (0..width).step_by(DIFF_TILE_SIZE as usize) {
+            let mut bounds: Option<Region> = None;
+            for y in tile_y..(tile_y + DIFF_TILE_SIZE).min(height) {
+                for x in tile_x..(tile_x + DIFF_TILE_SIZE).min(width) {
+                    if a.get_pixel(x, y).0[..3] != b.get_pixel(x, y).0[..3] {
+                        changed_pixels += 1;
+                        let pixel: Region = Region::new(x, y, 1, 1);
+                        bounds = Some(bounds.map_or(pixel, |r| r.union(&pixel)));
+                    }
+                }
+            }
+            tiles.extend(bounds);
+        }
+    }
+
+    let total: u64 = width as u64 * height as u64;
+    FrameDiff {
+        regions: merge_regions(tiles),
+        changed_pixels,
+        percentage: match total {
+            0 => 0.0,
+            total => changed_pixels as f64 * 100.0 / total as f64,
+        },
+    }
+}
+
+/// Compare two frames of the screen.
+///
+/// # Parameters
+///
+/// * a: `&RgbaImage` - The first frame.
+/// * b: `&RgbaImage` - The second frame.
+/// * ignore: `&[Region]` - Regions whose pixels are not compared.
+///
+/// # Returns
+///
+/// * `f64` - The similarity of the frames as peak signal-to-noise ratio in dB. Identical frames and
+///   frames without any compared pixels have an infinite similarity, frames of different sizes
+///   one of 0 dB.
+pub fn screen_similarity(a: &RgbaImage, b: &RgbaImage, ignore: &[Region]) -> f64 {
+    if a.dimensions() != b.dimensions() {
+        return 0.0;
+    }
+
+    let width: u32 = a.width();
+    let mut sse: u64 = 0;
+    let mut count: usize = 0;
+    for (i, (pa, pb)) in a.pixels().zip(b.pixels()).enumerate() {
+        let (x, y): (u32, u32) = (i as u32 % width, i as u32 / width);
+        if ignore.iter().any(|r| r.contains(x, y)) {
+            continue;
+        }
+        for c in 0..3 {
+            let diff: i64 = pa[c] as i64 - pb[c] as i64;
+            sse += (diff * diff) as u64;
+        }
+        count += 3;
+    }
+
+    psnr(sse, count)
+}
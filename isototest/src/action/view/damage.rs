@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Damage module
+//!
+//! The VNC server only sends the rectangles of the screen which changed. This module remembers
+//! them together with the framebuffer generation they produced, so callers can ask which parts of
+//! the screen changed since a frame they have seen before, without comparing any pixels.
+use std::collections::VecDeque;
+
+use super::compare::Region;
+
+/// Number of changed regions remembered by default.
+pub const DEFAULT_DAMAGE_HISTORY: usize = 1024;
+
+/// Remembers the regions of the screen changed by each framebuffer generation.
+///
+/// Only the most recent changes are remembered. Queries reaching back further, or past a change
+/// of the resolution, report the whole screen as changed.
+#[derive(Debug, Clone)]
+pub struct DamageTracker {
+    /// Changed regions with the generation they produced, oldest first.
+    history: VecDeque<(u64, Region)>,
+    capacity: usize,
+    /// Oldest generation from which on all changes are remembered.
+    complete_since: u64,
+    /// Size of the screen.
+    screen: Region,
+}
+
+impl DamageTracker {
+    /// Create a tracker for a screen of the given size.
+    ///
+    /// # Parameters
+    ///
+    /// * width: `u32` - Width of the screen.
+    /// * height: `u32` - Height of the screen.
+    /// * capacity: `usize` - Number of changed regions to remember.
+    pub fn new(width: u32, height: u32, capacity: usize) -> DamageTracker {
+        DamageTracker {
+            history: VecDeque::with_capacity(capacity.min(DEFAULT_DAMAGE_HISTORY)),
+            capacity,
+            complete_since: 0,
+            screen: Region::new(0, 0, width, height),
+        }
+    }
+
+    /// Record a change of the screen.
+    ///
+    /// # Parameters
+    ///
+    /// * generation: `u64` - The framebuffer generation produced by the change.
+    /// * region: `Region` - The changed region. Parts outside of the screen are ignored.
+    pub fn add(&mut self, generation: u64, region: Region) {
+        let Some(region) = region.intersection(&self.screen) else {
+            return;
+        };
+        if self.history.len() >= self.capacity {
+            match self.history.pop_front() {
+                Some((oldest, _)) => self.complete_since = oldest,
+                None => {
+                    self.complete_since = generation;
+                    return;
+                }
+            }
+        }
+        self.history.push_back((generation, region));
+    }
+
+    /// Record a change of the resolution, which changes the whole screen.
+    ///
+    /// # Parameters
+    ///
+    /// * generation: `u64` - The framebuffer generation produced by the change.
+    /// * width: `u32` - The new width of the screen.
+    /// * height: `u32` - The new height of the screen.
+    pub fn resize(&mut self, generation: u64, width: u32, height: u32) {
+        self.history.clear();
+        self.complete_since = generation;
+        self.screen = Region::new(0, 0, width, height);
+    }
+
+    /// The regions changed after the given generation.
+    ///
+    /// Overlapping and adjacent regions are merged into their bounding box.
+    ///
+    /// # Parameters
+    ///
+    /// * generation: `u64` - A generation seen before, e.g. from
+    ///   [`super::framebuffer::Framebuffer::generation`].
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Region>` - The changed regions, empty if nothing changed. If the changes are no
+    ///   longer known, the whole screen is returned.
+    pub fn since(&self, generation: u64) -> Vec<Region> {
+        if generation < self.complete_since {
+            return vec![self.screen];
+        }
+        merge_regions(
+            self.history
+                .iter()
+                .filter(|(changed, _)| *changed > generation)
+                .map(|(_, region)| *region),
+        )
+    }
+}
+
+impl Default for DamageTracker {
+    fn default() -> Self {
+        DamageTracker::new(0, 0, DEFAULT_DAMAGE_HISTORY)
+    }
+}
+
+/// Merge overlapping and adjacent regions into their bounding box.
+///
+/// # Parameters
+///
+/// * regions: `impl IntoIterator<Item = Region>` - The regions to merge. Empty regions are
+///   dropped.
+///
+/// # Returns
+///
+/// * `Vec<Region>` - Regions which neither overlap nor touch each other.
+pub fn merge_regions(regions: impl IntoIterator<Item = Region>) -> Vec<Region> {
+    let mut merged: Vec<Region> = Vec::new();
+    for region in regions {
+        if region.is_empty() {
+            continue;
+        }
+        let mut current: Region = region;
+        // Merging may make the region touch others it did not touch before.
+        while let Some(pos) = merged.iter().position(|other| other.touches(&current)) {
+            current = current.union(&merged.swap_remove(pos));
+        }
+        merged.push(current);
+    }
+    merged
+}
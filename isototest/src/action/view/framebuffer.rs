@@ -0,0 +1,352 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Framebuffer module
+//!
+//! This module keeps an in-memory copy of the remote screen, which is updated from the
+//! `VncEvent`s sent by the VNC server.
+//!
+//! Image data is sent in the pixel format negotiated when connecting, see
+//! [`crate::connection::config::VncConfig::with_pixel_format`], and converted to RGBA with
+//! [`to_rgba`].
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use image::{ImageFormat, RgbaImage};
+use log::{debug, info};
+use vnc::{PixelFormat, Rect, VncEvent};
+
+use super::compare::Region;
+use super::damage::{DamageTracker, DEFAULT_DAMAGE_HISTORY};
+use crate::errors::view_errors::ViewError;
+use crate::logging::LOG_TARGET;
+
+/// In-memory copy of the remote screen.
+///
+/// The framebuffer counts every change of its content in its generation, which allows callers to
+/// detect whether the screen has changed between two snapshots.
+///
+/// The screen content is shared copy-on-write with the handles returned by [`Framebuffer::shared`],
+/// so taking a snapshot is cheap and the next update only copies the frame if a handle is still
+/// alive.
+///
+/// The regions changed by each generation are remembered, see [`Framebuffer::damage_since`].
+#[derive(Debug, Clone)]
+pub struct Framebuffer {
+    image: Arc<RgbaImage>,
+    generation: u64,
+    pixel_format: PixelFormat,
+    damage: DamageTracker,
+}
+
+impl Framebuffer {
+    /// Create a new black framebuffer of the given size.
+    ///
+    /// Image data is expected as RGBA, the pixel format requested by default.
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            image: Arc::new(RgbaImage::from_pixel(
+                width,
+                height,
+                image::Rgba([0, 0, 0, 255]),
+            )),
+            generation: 0,
+            pixel_format: PixelFormat::rgba(),
+            damage: DamageTracker::new(width, height, DEFAULT_DAMAGE_HISTORY),
+        }
+    }
+
+    /// The pixel format in which image data is expected.
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.pixel_format
+    }
+
+    /// Set the pixel format in which image data is expected.
+    ///
+    /// # Parameters
+    ///
+    /// * pixel_format: `PixelFormat` - The pixel format negotiated with the server.
+    pub fn set_pixel_format(&mut self, pixel_format: PixelFormat) {
+        self.pixel_format = pixel_format;
+    }
+
+    /// The current resolution as `(width, height)`.
+    pub fn resolution(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    /// Number of changes applied to the framebuffer since its creation.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The regions of the screen changed after the given generation.
+    ///
+    /// See [`DamageTracker::since`].
+    ///
+    /// # Parameters
+    ///
+    /// * generation: `u64` - A generation seen before.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Region>` - The changed regions, empty if nothing changed. If the changes are no
+    ///   longer known, e.g. because the resolution changed, the whole screen is returned.
+    pub fn damage_since(&self, generation: u64) -> Vec<Region> {
+        self.damage.since(generation)
+    }
+
+    /// Borrow the current screen content.
+    pub fn image(&self) -> &RgbaImage {
+        &self.image
+    }
+
+    /// Get a shared handle to the current screen content.
+    ///
+    /// The handle keeps showing the screen at the time of the call, while the framebuffer
+    /// continues to be updated.
+    pub fn shared(&self) -> Arc<RgbaImage> {
+        self.image.clone()
+    }
+
+    /// Apply a `VncEvent` to the framebuffer.
+    ///
+    /// Events which do not affect the screen content are ignored.
+    ///
+    /// # Parameters
+    ///
+    /// * event: `&VncEvent` - The event received from the VNC server.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the event changed the framebuffer.
+    /// * `Ok(false)` - If the event does not affect the framebuffer, e.g. a rectangle outside of
+    ///   the screen.
+    /// * `Err(ViewError)` - If the event carries image data which cannot be decoded.
+    pub fn apply(&mut self, event: &VncEvent) -> Result<bool, ViewError> {
+        let generation: u64 = self.generation;
+        match event {
+            VncEvent::SetResolution(screen) => {
+                info!(target: LOG_TARGET, "Screen resolution: {}x{}", screen.width, screen.height);
+                self.resize(screen.width as u32, screen.height as u32);
+            }
+            VncEvent::RawImage(rect, data) => self.draw_rect(rect, data)?,
+            VncEvent::JpegImage(rect, data) => self.draw_jpeg(rect, data)?,
+            VncEvent::Copy(dst, src) => self.copy_rect(dst, src),
+            VncEvent::SetPixelFormat(pixel_format) => self.set_pixel_format(*pixel_format),
+            _ => {}
+        }
+        Ok(self.generation != generation)
+    }
+
+    /// Resize the framebuffer, discarding its content.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.image = Arc::new(RgbaImage::from_pixel(
+            width,
+            height,
+            image::Rgba([0, 0, 0, 255]),
+        ));
+        self.generation += 1;
+        self.damage.resize(self.generation, width, height);
+    }
+
+    /// Write the pixel data of a rectangle into the framebuffer.
+    ///
+    /// The data is expected in the framebuffer's pixel format, see
+    /// [`Framebuffer::set_pixel_format`]. Parts of the rectangle outside of the framebuffer are
+    /// clipped.
+    ///
+    /// # Parameters
+    ///
+    /// * rect: `&Rect` - Position and size of the rectangle.
+    /// * data: `&[u8]` - Pixel data of the rectangle.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the rectangle has been drawn.
+    /// * `Err(ViewError)` - If the data does not cover the rectangle or the pixel format is not
+    ///   supported.
+    pub fn draw_rect(&mut self, rect: &Rect, data: &[u8]) -> Result<(), ViewError> {
+        let data: Cow<[u8]> = to_rgba(&self.pixel_format, data)?;
+        self.blit(rect, &data)
+    }
+
+    /// Write RGBA pixel data of a rectangle into the framebuffer.
+    ///
+    /// The alpha channel is not used by the VNC server and always set to opaque.
+    fn blit(&mut self, rect: &Rect, data: &[u8]) -> Result<(), ViewError> {
+        let (rw, rh): (usize, usize) = (rect.width as usize, rect.height as usize);
+        if data.len() < rw * rh * 4 {
+            return Err(ViewError::ImageError(format!(
+                "Pixel data does not cover the rectangle {}x{}+{}+{}",
+                rw, rh, rect.x, rect.y
+            )));
+        }
+
+        let img_width: usize = self.image.width() as usize;
+        let x: usize = rect.x as usize;
+        let cols: usize = rw.min(img_width.saturating_sub(x));
+        let rows: usize = rh.min((self.image.height() as usize).saturating_sub(rect.y as usize));
+        if cols == 0 || rows == 0 {
+            // Stale rectangles sent before a resize may lie completely outside of the screen.
+            return Ok(());
+        }
+
+        let buf: &mut [u8] = Arc::make_mut(&mut self.image).as_mut();
+        for row in 0..rows {
+            let src: &[u8] = &data[row * rw * 4..(row * rw + cols) * 4];
+            let start: usize = ((rect.y as usize + row) * img_width + x) * 4;
+            let dst: &mut [u8] = &mut buf[start..start + cols * 4];
+            dst.copy_from_slice(src);
+            dst.chunks_exact_mut(4).for_each(|px| px[3] = 255);
+        }
+        self.generation += 1;
+        self.damage.add(
+            self.generation,
+            Region::new(x as u32, rect.y as u32, cols as u32, rows as u32),
+        );
+        Ok(())
+    }
+
+    /// Decode a JPEG encoded rectangle and write it into the framebuffer.
+    ///
+    /// Servers using the `Tight` encoding may send rectangles as JPEG images.
+    ///
+    /// # Parameters
+    ///
+    /// * rect: `&Rect` - Position and size of the rectangle.
+    /// * data: `&[u8]` - The JPEG image.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the rectangle has been drawn.
+    /// * `Err(ViewError)` - If the image cannot be decoded.
+    pub fn draw_jpeg(&mut self, rect: &Rect, data: &[u8]) -> Result<(), ViewError> {
+        let decoded: RgbaImage = image::load_from_memory_with_format(data, ImageFormat::Jpeg)
+            .map_err(|e| {
+                debug!(target: LOG_TARGET, "Unable to decode JPEG rectangle: {}", e);
+                ViewError::from(e)
+            })?
+            .to_rgba8();
+        let rect = Rect {
+            width: rect.width.min(decoded.width() as u16),
+            height: rect.height.min(decoded.height() as u16),
+            ..*rect
+        };
+        let data: Vec<u8> =
+            image::imageops::crop_imm(&decoded, 0, 0, rect.width as u32, rect.height as u32)
+                .to_image()
+                .into_raw();
+        self.blit(&rect, &data)
+    }
+
+    /// Copy a rectangle of the framebuffer to another position.
+    ///
+    /// # Parameters
+    ///
+    /// * dst: `&Rect` - The destination rectangle.
+    /// * src: `&Rect` - The source rectangle.
+    pub fn copy_rect(&mut self, dst: &Rect, src: &Rect) {
+        let (width, height): (u32, u32) = self.image.dimensions();
+        let cols: usize = (src.width as u32)
+            .min(width.saturating_sub(src.x as u32))
+            .min(width.saturating_sub(dst.x as u32)) as usize;
+        let rows: u32 = (src.height as u32)
+            .min(height.saturating_sub(src.y as u32))
+            .min(height.saturating_sub(dst.y as u32));
+        if cols == 0 || rows == 0 {
+            return;
+        }
+
+        let stride: usize = width as usize * 4;
+        let buf: &mut [u8] = Arc::make_mut(&mut self.image).as_mut();
+        // Copy bottom-up when moving down, so overlapping rows are read before they are
+        // overwritten.
+        let order: Vec<u32> = if dst.y > src.y {
+            (0..rows).rev().collect()
+        } else {
+            (0..rows).collect()
+        };
+        for row in order {
+            let s: usize = (src.y as u32 + row) as usize * stride + src.x as usize * 4;
+            let d: usize = (dst.y as u32 + row) as usize * stride + dst.x as usize * 4;
+            buf.copy_within(s..s + cols * 4, d);
+        }
+        self.generation += 1;
+        self.damage.add(
+            self.generation,
+            Region::new(dst.x as u32, dst.y as u32, cols as u32, rows),
+        );
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Framebuffer::new(0, 0)
+    }
+}
+
+/// Convert pixel data sent by the VNC server to RGBA.
+///
+/// Data already in the RGBA format requested by default is returned as is, its alpha channel is
+/// undefined.
+///
+/// # Parameters
+///
+/// * format: `&PixelFormat` - The pixel format the data is sent in.
+/// * data: `&[u8]` - The pixel data.
+///
+/// # Returns
+///
+/// * `Ok(Cow<[u8]>)` - The RGBA pixel data.
+/// * `Err(ViewError)` - If the format is not a true colour format with 8, 16 or 32 bits per
+///   pixel.
+pub fn to_rgba<'a>(format: &PixelFormat, data: &'a [u8]) -> Result<Cow<'a, [u8]>, ViewError> {
+    if format.true_color_flag == 0 {
+        return Err(ViewError::ImageError(
+            "Colour map pixel formats are not supported".to_string(),
+        ));
+    }
+    let bpp: usize = match format.bits_per_pixel {
+        8 => 1,
+        16 => 2,
+        32 => 4,
+        bits => {
+            return Err(ViewError::ImageError(format!(
+                "Unsupported pixel format with {} bits per pixel",
+                bits
+            )))
+        }
+    };
+
+    let rgba: PixelFormat = PixelFormat::rgba();
+    if bpp == 4
+        && format.big_endian_flag == 0
+        && (format.red_shift, format.green_shift, format.blue_shift)
+            == (rgba.red_shift, rgba.green_shift, rgba.blue_shift)
+        && (format.red_max, format.green_max, format.blue_max) == (255, 255, 255)
+    {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    let channel = |value: u32, shift: u8, max: u16| -> u8 {
+        match max {
+            0 => 0,
+            max => (((value >> shift) & max as u32) * 255 / max as u32) as u8,
+        }
+    };
+    let mut converted: Vec<u8> = Vec::with_capacity(data.len() / bpp * 4);
+    for px in data.chunks_exact(bpp) {
+        let value: u32 = if format.big_endian_flag != 0 {
+            px.iter().fold(0, |acc, b| acc << 8 | *b as u32)
+        } else {
+            px.iter().rev().fold(0, |acc, b| acc << 8 | *b as u32)
+        };
+        converted.extend_from_slice(&[
+            channel(value, format.red_shift, format.red_max),
+            channel(value, format.green_shift, format.green_max),
+            channel(value, format.blue_shift, format.blue_max),
+            255,
+        ]);
+    }
+    Ok(Cow::Owned(converted))
+}
@@ -0,0 +1,257 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Inspect module
+//!
+//! This module examines small parts of the screen, like a progress bar, a status LED or the
+//! corner of a dialog, without the need for a needle.
+//!
+//! All functions check the requested region against the resolution of the screen, which follows
+//! the resolution announced by the VNC server. Regions which are empty or reach beyond the screen
+//! are rejected with a [`ViewError::OutOfBounds`].
+use std::collections::HashMap;
+
+use image::{imageops, Rgba, RgbaImage};
+use log::{debug, error};
+
+use super::compare::Region;
+use crate::errors::view_errors::{ColorMismatch, ViewError};
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// Expected share of pixels of a region showing a colour.
+///
+/// A pixel shows the colour if none of its colour channels deviates by more than the tolerance.
+/// The alpha channel is ignored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorShare {
+    pub color: Rgba<u8>,
+    /// Maximum deviation of each colour channel.
+    pub tolerance: u8,
+    /// Minimum share of matching pixels in percent.
+    pub min: f64,
+    /// Maximum share of matching pixels in percent.
+    pub max: f64,
+}
+
+impl ColorShare {
+    /// Expect a colour to cover any share of the region, which is narrowed with
+    /// [`ColorShare::with_range`].
+    ///
+    /// # Parameters
+    ///
+    /// * color: `Rgba<u8>` - The expected colour.
+    /// * tolerance: `u8` - Maximum deviation of each colour channel.
+    pub fn new(color: Rgba<u8>, tolerance: u8) -> ColorShare {
+        ColorShare {
+            color,
+            tolerance,
+            min: 0.0,
+            max: 100.0,
+        }
+    }
+
+    /// Set the range the share of matching pixels must lie in.
+    ///
+    /// # Parameters
+    ///
+    /// * min: `f64` - Minimum share in percent.
+    /// * max: `f64` - Maximum share in percent. Use `0.0` to expect the colour to be absent.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(ColorShare)` - The expectation.
+    /// * `Err(Error)` - If the range is not within 0% and 100% or `min` exceeds `max`.
+    pub fn with_range(self, min: f64, max: f64) -> Result<ColorShare, Error> {
+        if !(0.0..=100.0).contains(&min) || !(0.0..=100.0).contains(&max) || min > max {
+            return Err(Error::InvalidInput(format!(
+                "Invalid colour share range {}% to {}%",
+                min, max
+            )));
+        }
+        Ok(ColorShare { min, max, ..self })
+    }
+
+    /// Check whether a pixel shows the colour.
+    pub fn matches(&self, pixel: &Rgba<u8>) -> bool {
+        pixel.0[..3]
+            .iter()
+            .zip(&self.color.0[..3])
+            .all(|(a, b)| a.abs_diff(*b) <= self.tolerance)
+    }
+}
+
+/// Copy a region of the screen.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screen, e.g. [`crate::session::Session::frame`].
+/// * region: `Region` - The region to copy.
+///
+/// # Returns
+///
+/// * `Ok(RgbaImage)` - The content of the region.
+/// * `Err(ViewError)` - If the region is empty or not within the screen.
+pub fn capture_region(image: &RgbaImage, region: Region) -> Result<RgbaImage, ViewError> {
+    check_region(image, region)?;
+    Ok(imageops::crop_imm(image, region.x, region.y, region.width, region.height).to_image())
+}
+
+/// Get the colour of a single pixel.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screen.
+/// * x: `u32` - Horizontal position of the pixel.
+/// * y: `u32` - Vertical position of the pixel.
+///
+/// # Returns
+///
+/// * `Ok(Rgba<u8>)` - The colour of the pixel.
+/// * `Err(ViewError)` - If the pixel is not within the screen.
+pub fn pixel_at(image: &RgbaImage, x: u32, y: u32) -> Result<Rgba<u8>, ViewError> {
+    check_region(image, Region::new(x, y, 1, 1))?;
+    Ok(*image.get_pixel(x, y))
+}
+
+/// Get the average colour of a region.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screen.
+/// * region: `Region` - The region to evaluate.
+///
+/// # Returns
+///
+/// * `Ok(Rgba<u8>)` - The rounded mean of each colour channel. The alpha channel is opaque.
+/// * `Err(ViewError)` - If the region is empty or not within the screen.
+pub fn region_mean_color(image: &RgbaImage, region: Region) -> Result<Rgba<u8>, ViewError> {
+    check_region(image, region)?;
+    let mut sums: [u64; 3] = [0; 3];
+    for pixel in pixels(image, region) {
+        for (sum, value) in sums.iter_mut().zip(&pixel.0[..3]) {
+            *sum += *value as u64;
+        }
+    }
+    let count: u64 = region.area();
+    let mean = |sum: u64| ((sum + count / 2) / count) as u8;
+    Ok(Rgba([mean(sums[0]), mean(sums[1]), mean(sums[2]), 255]))
+}
+
+/// Count the colours of a region.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screen.
+/// * region: `Region` - The region to evaluate.
+///
+/// # Returns
+///
+/// * `Ok(Vec<(Rgba<u8>, u64)>)` - Each colour with its number of pixels, most frequent first.
+///   The alpha channel is ignored and always opaque.
+/// * `Err(ViewError)` - If the region is empty or not within the screen.
+pub fn color_histogram(
+    image: &RgbaImage,
+    region: Region,
+) -> Result<Vec<(Rgba<u8>, u64)>, ViewError> {
+    check_region(image, region)?;
+    let mut counts: HashMap<[u8; 3], u64> = HashMap::new();
+    for pixel in pixels(image, region) {
+        *counts
+            .entry([pixel.0[0], pixel.0[1], pixel.0[2]])
+            .or_default() += 1;
+    }
+    let mut histogram: Vec<(Rgba<u8>, u64)> = counts
+        .into_iter()
+        .map(|([r, g, b], count)| (Rgba([r, g, b, 255]), count))
+        .collect();
+    histogram.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0 .0.cmp(&b.0 .0)));
+    Ok(histogram)
+}
+
+/// Get the share of pixels of a region showing a colour.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screen.
+/// * region: `Region` - The region to evaluate.
+/// * color: `Rgba<u8>` - The colour to look for.
+/// * tolerance: `u8` - Maximum deviation of each colour channel.
+///
+/// # Returns
+///
+/// * `Ok(f64)` - The share of matching pixels in percent.
+/// * `Err(ViewError)` - If the region is empty or not within the screen.
+pub fn color_share(
+    image: &RgbaImage,
+    region: Region,
+    color: Rgba<u8>,
+    tolerance: u8,
+) -> Result<f64, ViewError> {
+    check_region(image, region)?;
+    Ok(share(image, region, &ColorShare::new(color, tolerance)))
+}
+
+/// Assert that the colours of a region meet all expectations.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screen.
+/// * region: `Region` - The region to evaluate.
+/// * expected: `&[ColorShare]` - The expected shares of colours, e.g. at least 90% green for a
+///   status LED which is on.
+///
+/// # Returns
+///
+/// * `Ok(())` - If all expectations are met.
+/// * `Err(ViewError)` - If the region is empty or not within the screen, or a
+///   `ViewError::ColorMismatch` for the first expectation which is not met.
+pub fn assert_colors(
+    image: &RgbaImage,
+    region: Region,
+    expected: &[ColorShare],
+) -> Result<(), ViewError> {
+    check_region(image, region)?;
+    for expectation in expected {
+        let share: f64 = share(image, region, expectation);
+        debug!(target: LOG_TARGET, "{:.2}% of region {:?} match {:?}.", share, region, expectation);
+        if share < expectation.min || share > expectation.max {
+            let mismatch: ColorMismatch = ColorMismatch {
+                region,
+                expected: *expectation,
+                share,
+            };
+            let err: ViewError = ViewError::ColorMismatch(Box::new(mismatch));
+            error!(target: LOG_TARGET, "{}", err);
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Share of pixels of a region matching an expectation in percent.
+///
+/// The region must have been checked before.
+fn share(image: &RgbaImage, region: Region, expected: &ColorShare) -> f64 {
+    let matching: usize = pixels(image, region)
+        .filter(|pixel| expected.matches(pixel))
+        .count();
+    matching as f64 * 100.0 / region.area() as f64
+}
+
+/// The pixels of a region, row by row.
+///
+/// The region must have been checked before.
+fn pixels(image: &RgbaImage, region: Region) -> impl Iterator<Item = &Rgba<u8>> {
+    (region.y..region.y + region.height)
+        .flat_map(move |y| (region.x..region.x + region.width).map(move |x| image.get_pixel(x, y)))
+}
+
+/// Check that a region covers at least one pixel and lies within the screen.
+fn check_region(image: &RgbaImage, region: Region) -> Result<(), ViewError> {
+    let screen: Region = Region::new(0, 0, image.width(), image.height());
+    if region.is_empty() || region.intersection(&screen) != Some(region) {
+        error!(target: LOG_TARGET, "Region {:?} is not within the screen of {}x{}.", region, screen.width, screen.height);
+        return Err(ViewError::OutOfBounds(region, image.dimensions()));
+    }
+    Ok(())
+}
@@ -0,0 +1,474 @@
+//! # View module
+//!
+//! This module handles everything related to requesting visual data from the VNC server.
+//!
+//! The in-memory copy of the screen kept by a [`crate::session::Session`] is implemented in
+//! [`framebuffer`], comparing the screen against reference images ("needles") is handled by
+//! [`needle`]. Frames are compared with each other by [`compare`], which is used to wait for the
+//! screen to change or to become still. The regions changed by the VNC server are tracked by
+//! [`damage`]. Small parts of the screen, down to single pixels, are examined by [`inspect`].
+//! Videos of the screen are recorded by [`recorder`].
+pub mod compare;
+pub mod damage;
+pub mod framebuffer;
+pub mod inspect;
+pub mod needle;
+pub mod recorder;
+
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use std::future::Future;
+use std::sync::Arc;
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
+use vnc::{VncClient, VncError, VncEvent, X11Event};
+
+use log::{debug, error, info, warn};
+
+use crate::errors::needle_errors::ScreenMismatch;
+use crate::errors::view_errors::ViewError;
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+use crate::session::Session;
+use compare::{screen_similarity, Region};
+use framebuffer::Framebuffer;
+use needle::{search_needles, Needle, NeedleMatch};
+use tokio::sync::watch;
+
+/// Interval in which the screen is re-evaluated while waiting for a needle.
+const NEEDLE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Receive the changes of the remote machine's screen.
+///
+/// An incremental update is requested, and the rectangles sent by the VNC server are written into
+/// `framebuffer` until the server has been idle for `timeout`. Raw rectangles are copied row by
+/// row and `CopyRect` updates move pixels within the framebuffer, so only the changed parts of the
+/// screen are touched and the framebuffer always holds the whole screen. Parts of rectangles
+/// reaching beyond the screen, e.g. ones sent before a resize, are dropped.
+///
+/// # Parameters
+///
+/// * client: `&VncClient` - The client instance used for connection.
+/// * framebuffer: `&mut Framebuffer` - Keeps the screen between calls and is updated in place.
+///   Pass an empty framebuffer (`Framebuffer::default()`) on the first call.
+/// * timeout: `Duration` - The `Duration` this function should wait for a `VncEvent` before it
+///   continues.
+///
+/// **NOTE**: The same framebuffer must be passed to all calls of `read_screen`. If it is empty,
+/// the function will attempt to detect the resolution from the VNC server. This only works for
+/// the first time though. The client cannot retrieve the resolution a second time by itself as
+/// long as it has not changed.
+///
+/// The frame is only kept in memory. Use [`save_screenshot`] to write it to the file system. It
+/// shares its pixels with the framebuffer, so drop it before the next call to spare a copy of the
+/// screen.
+///
+/// Image data is expected in the framebuffer's pixel format, RGBA by default, unless the server
+/// announces its native pixel format because the client was connected without requesting one.
+/// Clients connected with another pixel format should use a [`Session`] instead.
+///
+/// # Returns
+///
+/// * `Ok(Arc<RgbaImage>)` - The screen of the VNC machine we connect to. Whether it changed can be
+///   told by [`Framebuffer::generation`].
+/// * `Err(ViewError)` - If the connection fails, image data cannot be decoded or no screen data
+///   arrives within `timeout` on the first call.
+pub async fn read_screen(
+    client: &VncClient,
+    framebuffer: &mut Framebuffer,
+    timeout: Duration,
+) -> Result<Arc<RgbaImage>, ViewError> {
+    info!(target: LOG_TARGET, "Requesting screenshot...");
+    // Request screen update.
+    client.input(X11Event::Refresh).await?;
+
+    // Try to detect screen resolution of the remote machine if the framebuffer is empty.
+    // **This will cause issues, if you try to use this functionality a second time.**
+    let detect: bool = framebuffer.resolution() == (0, 0);
+    if detect {
+        receive_resolution(client, framebuffer, timeout).await?;
+    }
+
+    let changed: bool = receive_updates(client, framebuffer, timeout).await?;
+    if detect && !changed {
+        error!(target: LOG_TARGET, "No screen data received within {:?}.", timeout);
+        return Err(ViewError::Timeout(timeout));
+    }
+    Ok(framebuffer.shared())
+}
+
+/// File formats screenshots can be saved in.
+///
+/// # Members
+///
+/// * `Png` - Lossless PNG.
+/// * `WebP` - Lossless WebP.
+/// * `Ppm` - Uncompressed binary PPM. The alpha channel is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenshotFormat {
+    Png,
+    WebP,
+    Ppm,
+}
+
+impl ScreenshotFormat {
+    /// Determine the format from the extension of a file path.
+    ///
+    /// # Returns
+    ///
+    /// * `Some(ScreenshotFormat)` - If the extension is `png`, `webp` or `ppm`.
+    /// * `None` - If the extension is missing or unsupported.
+    pub fn from_path(path: &Path) -> Option<ScreenshotFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "png" => Some(ScreenshotFormat::Png),
+            "webp" => Some(ScreenshotFormat::WebP),
+            "ppm" => Some(ScreenshotFormat::Ppm),
+            _ => None,
+        }
+    }
+}
+
+/// Save a screenshot to the file system.
+///
+/// # Parameters
+///
+/// * image: `&RgbaImage` - The screenshot, e.g. returned by [`read_screen`] or
+///   [`Session::snapshot`].
+/// * path: `&Path` - The file to write. Existing files are overwritten.
+/// * format: `Option<ScreenshotFormat>` - The file format. If `None`, it is determined from the
+///   file extension.
+///
+/// # Returns
+///
+/// * `Ok(())` - If the screenshot has been saved.
+/// * `Err(ViewError)` - If the format cannot be determined or the file cannot be written.
+pub fn save_screenshot(
+    image: &RgbaImage,
+    path: &Path,
+    format: Option<ScreenshotFormat>,
+) -> Result<(), ViewError> {
+    let format: ScreenshotFormat = format
+        .or_else(|| ScreenshotFormat::from_path(path))
+        .ok_or_else(|| {
+            ViewError::ImageError(format!(
+                "Unable to determine screenshot format of '{}'",
+                path.display()
+            ))
+        })?;
+
+    let result = match format {
+        ScreenshotFormat::Png => image.save_with_format(path, ImageFormat::Png),
+        ScreenshotFormat::WebP => image.save_with_format(path, ImageFormat::WebP),
+        ScreenshotFormat::Ppm => DynamicImage::ImageRgba8(image.clone())
+            .to_rgb8()
+            .save_with_format(path, ImageFormat::Pnm),
+    };
+    result.map_err(|e| {
+        error!(target: LOG_TARGET, "Unable to save screenshot to '{}': {}", path.display(), e);
+        ViewError::from(e)
+    })?;
+
+    info!(target: LOG_TARGET, "Screenshot saved to '{}'", path.display());
+    Ok(())
+}
+
+/// Wait for the screen to match a needle with one of the given tags.
+///
+/// The session's framebuffer is compared against all `needles` carrying at least one of the
+/// `tags` whenever it changes, until a needle matches or `timeout` has passed. The screen is
+/// always evaluated at least once, even if `timeout` is zero.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session whose screen is evaluated.
+/// * needles: `&[Needle]` - The needles to consider.
+/// * tags: `&[&str]` - The tags to look for.
+/// * timeout: `Duration` - How long to wait for a matching screen.
+///
+/// # Returns
+///
+/// * `Ok(NeedleMatch)` - The matching needle.
+/// * `Err(Error)` - If the connection is lost or no needle matched in time. An
+///   `Error::NeedleMismatch` carries the last evaluated frame and the closest candidate needle.
+pub async fn assert_screen(
+    session: &Session,
+    needles: &[Needle],
+    tags: &[&str],
+    timeout: Duration,
+) -> Result<NeedleMatch, Error> {
+    info!(target: LOG_TARGET, "Asserting screen for tags {:?}...", tags);
+    match poll_needles(session, needles, tags, timeout).await? {
+        Ok(found) => Ok(found),
+        Err(mismatch) => {
+            error!(target: LOG_TARGET, "No needle matched tags {:?} within {:?}.", tags, timeout);
+            Err(Error::NeedleMismatch(Box::new(mismatch)))
+        }
+    }
+}
+
+/// Check whether the screen matches a needle with one of the given tags.
+///
+/// Works like [`assert_screen`], but a screen which does not match in time is not treated as an
+/// error.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session whose screen is evaluated.
+/// * needles: `&[Needle]` - The needles to consider.
+/// * tags: `&[&str]` - The tags to look for.
+/// * timeout: `Duration` - How long to wait for a matching screen.
+///
+/// # Returns
+///
+/// * `Ok(Some(NeedleMatch))` - The matching needle.
+/// * `Ok(None)` - If no needle matched in time.
+/// * `Err(Error)` - If the connection is lost.
+pub async fn check_screen(
+    session: &Session,
+    needles: &[Needle],
+    tags: &[&str],
+    timeout: Duration,
+) -> Result<Option<NeedleMatch>, Error> {
+    info!(target: LOG_TARGET, "Checking screen for tags {:?}...", tags);
+    Ok(poll_needles(session, needles, tags, timeout).await?.ok())
+}
+
+/// Wait until the screen has not changed for `stilltime`.
+///
+/// Frames are compared against the frame at the start of the current still period, so slow but
+/// steady changes are detected as well.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session whose screen is watched.
+/// * stilltime: `Duration` - How long the screen must not change.
+/// * timeout: `Duration` - How long to wait for the screen to become still.
+/// * similarity: `f64` - Similarity in dB from which two frames are considered equal, see
+///   [`compare::DEFAULT_SIMILARITY`].
+/// * ignore: `&[Region]` - Regions which are ignored, e.g. a blinking cursor or a clock.
+///
+/// # Returns
+///
+/// * `Ok(true)` - If the screen was still for `stilltime`.
+/// * `Ok(false)` - If the screen kept changing until `timeout`.
+/// * `Err(Error)` - If the connection is lost.
+pub async fn wait_still_screen(
+    session: &Session,
+    stilltime: Duration,
+    timeout: Duration,
+    similarity: f64,
+    ignore: &[Region],
+) -> Result<bool, Error> {
+    info!(target: LOG_TARGET, "Waiting {:?} for the screen to be still for {:?}...", timeout, stilltime);
+    let deadline: tokio::time::Instant = tokio::time::Instant::now() + timeout;
+    let mut updates: watch::Receiver<u64> = session.frame_updates();
+    updates.mark_unchanged();
+    let mut reference: Arc<RgbaImage> = session.frame();
+    let mut still_since: tokio::time::Instant = tokio::time::Instant::now();
+
+    loop {
+        let now: tokio::time::Instant = tokio::time::Instant::now();
+        let still_until: tokio::time::Instant = still_since + stilltime;
+        if now >= still_until {
+            info!(target: LOG_TARGET, "Screen is still.");
+            return Ok(true);
+        }
+        if now >= deadline {
+            warn!(target: LOG_TARGET, "Screen did not become still within {:?}.", timeout);
+            return Ok(false);
+        }
+
+        match tokio::time::timeout_at(still_until.min(deadline), updates.changed()).await {
+            Ok(Ok(())) => {
+                let frame: Arc<RgbaImage> = session.frame();
+                if screen_similarity(&reference, &frame, ignore) < similarity {
+                    debug!(target: LOG_TARGET, "Screen changed; restarting still period.");
+                    reference = frame;
+                    still_since = tokio::time::Instant::now();
+                }
+            }
+            Ok(Err(_)) => return Err(session_closed()),
+            Err(_) => {}
+        }
+    }
+}
+
+/// Run an action and wait until the screen changes.
+///
+/// The reference frame is taken before `action` is started, so changes caused by the action are
+/// never missed. Pass `async { Ok(()) }` to wait for any change without an action.
+///
+/// # Parameters
+///
+/// * session: `&Session` - The session whose screen is watched.
+/// * action: `impl Future<Output = Result<(), Error>>` - The action expected to change the
+///   screen, e.g. sending a key.
+/// * timeout: `Duration` - How long to wait for the screen to change after the action.
+/// * similarity: `f64` - Similarity in dB below which the screen is considered changed, see
+///   [`compare::DEFAULT_SIMILARITY`].
+/// * ignore: `&[Region]` - Regions which are ignored, e.g. a blinking cursor or a clock.
+///
+/// # Returns
+///
+/// * `Ok(true)` - If the screen changed.
+/// * `Ok(false)` - If the screen did not change until `timeout`.
+/// * `Err(Error)` - If the action fails or the connection is lost.
+pub async fn wait_screen_change(
+    session: &Session,
+    action: impl Future<Output = Result<(), Error>>,
+    timeout: Duration,
+    similarity: f64,
+    ignore: &[Region],
+) -> Result<bool, Error> {
+    let mut updates: watch::Receiver<u64> = session.frame_updates();
+    updates.mark_unchanged();
+    let reference: Arc<RgbaImage> = session.frame();
+    action.await?;
+
+    info!(target: LOG_TARGET, "Waiting {:?} for the screen to change...", timeout);
+    let deadline: tokio::time::Instant = tokio::time::Instant::now() + timeout;
+    loop {
+        match tokio::time::timeout_at(deadline, updates.changed()).await {
+            Ok(Ok(())) => {
+                let frame: Arc<RgbaImage> = session.frame();
+                if screen_similarity(&reference, &frame, ignore) < similarity {
+                    info!(target: LOG_TARGET, "Screen changed.");
+                    return Ok(true);
+                }
+            }
+            Ok(Err(_)) => return Err(session_closed()),
+            Err(_) => {
+                warn!(target: LOG_TARGET, "Screen did not change within {:?}.", timeout);
+                return Ok(false);
+            }
+        }
+    }
+}
+
+/// Evaluate the screen until a needle matches or the deadline passes.
+///
+/// # Returns
+///
+/// * `Ok(Ok(NeedleMatch))` - The matching needle.
+/// * `Ok(Err(ScreenMismatch))` - The last frame and closest candidate if no needle matched.
+/// * `Err(Error)` - If the connection is lost.
+async fn poll_needles(
+    session: &Session,
+    needles: &[Needle],
+    tags: &[&str],
+    timeout: Duration,
+) -> Result<Result<NeedleMatch, ScreenMismatch>, Error> {
+    let deadline: Instant = Instant::now() + timeout;
+    let mut updates: watch::Receiver<u64> = session.frame_updates();
+
+    loop {
+        updates.mark_unchanged();
+        let frame: Arc<RgbaImage> = session.frame();
+
+        let mut candidates: Vec<NeedleMatch> = search_needles(&frame, needles, tags);
+        if candidates.first().is_some_and(NeedleMatch::is_match) {
+            let found: NeedleMatch = candidates.swap_remove(0);
+            info!(target: LOG_TARGET, "Needle '{}' matched ({:.2}%).", found.needle, found.similarity);
+            return Ok(Ok(found));
+        }
+
+        let now: Instant = Instant::now();
+        if now >= deadline {
+            return Ok(Err(ScreenMismatch {
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                last_frame: Arc::unwrap_or_clone(frame),
+                closest: candidates.into_iter().next(),
+            }));
+        }
+
+        // Re-evaluate once the screen has changed, but not more often than the poll interval.
+        tokio::time::sleep(NEEDLE_POLL_INTERVAL.min(deadline - now)).await;
+        let remaining: Duration = deadline.saturating_duration_since(Instant::now());
+        if let Ok(Err(_)) = tokio::time::timeout(remaining, updates.changed()).await {
+            return Err(session_closed());
+        }
+    }
+}
+
+/// The error returned when the session's framebuffer updates stop.
+fn session_closed() -> Error {
+    Error::ConnectionError("Session closed".to_string())
+}
+
+/// Wait for the VNC server to announce the screen resolution and resize the framebuffer.
+///
+/// The server only sends image data for the new resolution once it has been requested again.
+async fn receive_resolution(
+    client: &VncClient,
+    framebuffer: &mut Framebuffer,
+    timeout: Duration,
+) -> Result<(), ViewError> {
+    match tokio::time::timeout(timeout, client.recv_event()).await {
+        Ok(Ok(event @ VncEvent::SetResolution(_))) => {
+            framebuffer.apply(&event)?;
+            client.input(X11Event::Refresh).await?;
+            Ok(())
+        }
+        Ok(Ok(_)) => {
+            error!(target: LOG_TARGET, "Failed to retrieve screen resolution. Aborting...");
+            Err(ViewError::ProtocolError(VncError::General(
+                "[error] No resolution found!".to_string(),
+            )))
+        }
+        Ok(Err(e)) => Err(e.into()),
+        Err(_) => {
+            error!(target: LOG_TARGET, "No screen resolution received within {:?}.", timeout);
+            Err(ViewError::Timeout(timeout))
+        }
+    }
+}
+
+/// Apply the events sent by the VNC server to the framebuffer until the server has been idle for
+/// `timeout`.
+///
+/// Changes of the resolution are followed by requesting the screen again.
+///
+/// # Returns
+///
+/// * `Ok(bool)` - Whether image data has been applied.
+/// * `Err(ViewError)` - If the connection fails or image data cannot be decoded.
+async fn receive_updates(
+    client: &VncClient,
+    framebuffer: &mut Framebuffer,
+    timeout: Duration,
+) -> Result<bool, ViewError> {
+    let mut changed: bool = false;
+    let mut idle_timer: Instant = Instant::now();
+    loop {
+        match client.poll_event().await? {
+            Some(event) => {
+                idle_timer = Instant::now();
+                match event {
+                    VncEvent::SetResolution(_) => {
+                        framebuffer.apply(&event)?;
+                        client.input(X11Event::Refresh).await?;
+                    }
+                    VncEvent::Error(e) => {
+                        error!(target: LOG_TARGET, "Error event received: {}", e);
+                        return Err(ViewError::ProtocolError(VncError::General(e)));
+                    }
+                    x => {
+                        if framebuffer.apply(&x)? {
+                            changed = true;
+                        } else {
+                            debug!(target: LOG_TARGET, "Ignored event '{:?}'.", x);
+                        }
+                    }
+                }
+            }
+            None => {
+                if idle_timer.elapsed() >= timeout {
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+    }
+    Ok(changed)
+}
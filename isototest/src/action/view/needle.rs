@@ -0,0 +1,514 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Needle module
+//!
+//! This module loads [openQA compatible needles](https://open.qa/docs/#_needles) and compares
+//! them against screenshots of the remote machine.
+//!
+//! A needle consists of two files sharing the same base name: A JSON file describing the needle
+//! and a PNG file containing the reference screenshot. The JSON file lists the tags of the needle
+//! and the areas of the screenshot which are relevant for the comparison:
+//!
+//! ```json
+//! {
+//!     "tags": ["grub-menu"],
+//!     "area": [
+//!         { "xpos": 10, "ypos": 20, "width": 200, "height": 40, "type": "match", "match": 95 },
+//!         { "xpos": 50, "ypos": 25, "width": 20, "height": 10, "type": "exclude" }
+//!     ],
+//!     "properties": []
+//! }
+//! ```
+//!
+//! Every `match` area is searched for in the vicinity of its original position (see
+//! [`NeedleArea::margin`]). Pixels covered by an `exclude` area are ignored during the comparison.
+//! `ocr` areas are parsed, but not evaluated.
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use image::{imageops, GrayImage, RgbaImage};
+use log::{debug, info};
+use serde::Deserialize;
+
+use crate::errors::needle_errors::NeedleError;
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// Match level in percent used for `match` areas which do not define one.
+pub const DEFAULT_MATCH_LEVEL: f64 = 96.0;
+
+/// Distance in pixels a `match` area may be displaced from its original position, if the area
+/// does not define one.
+pub const DEFAULT_MARGIN: u32 = 50;
+
+/// Peak signal-to-noise ratio (in dB) from which two areas are considered identical.
+const PSNR_IDENTICAL: f64 = 40.0;
+
+/// Type of a needle area.
+///
+/// # Members
+///
+/// * `Match` - The area must be found on the screen.
+/// * `Exclude` - The area is ignored when comparing the other areas.
+/// * `Ocr` - The area is meant for text recognition and is not compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AreaType {
+    #[default]
+    Match,
+    Exclude,
+    Ocr,
+}
+
+/// A rectangular area of a needle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeedleArea {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub kind: AreaType,
+    /// Required similarity in percent for the area to match.
+    pub match_level: f64,
+    /// Distance in pixels the area may be displaced on the screen and still match.
+    pub margin: u32,
+}
+
+/// A needle consisting of its definition and reference image.
+#[derive(Debug, Clone)]
+pub struct Needle {
+    /// Name of the needle, which is the base name of its files.
+    pub name: String,
+    pub tags: Vec<String>,
+    pub properties: Vec<String>,
+    pub areas: Vec<NeedleArea>,
+    pub image: RgbaImage,
+}
+
+/// Result of searching a single `match` area on the screen.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AreaMatch {
+    /// Horizontal position on the screen where the area was found.
+    pub x: u32,
+    /// Vertical position on the screen where the area was found.
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Similarity of the best candidate position in percent.
+    pub similarity: f64,
+    /// Required similarity in percent.
+    pub match_level: f64,
+}
+
+impl AreaMatch {
+    /// Check whether the area reached its required match level.
+    pub fn is_match(&self) -> bool {
+        self.similarity >= self.match_level
+    }
+}
+
+/// Result of comparing a needle against a screenshot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NeedleMatch {
+    /// Name of the compared needle.
+    pub needle: String,
+    pub tags: Vec<String>,
+    /// Similarity of the worst matching area in percent.
+    pub similarity: f64,
+    /// Results of all `match` areas, in the order they are defined in the needle.
+    pub areas: Vec<AreaMatch>,
+}
+
+impl NeedleMatch {
+    /// Check whether every `match` area of the needle reached its match level.
+    pub fn is_match(&self) -> bool {
+        self.areas.iter().all(AreaMatch::is_match)
+    }
+}
+
+/// Needle definition as stored in the JSON file.
+#[derive(Deserialize)]
+struct RawNeedle {
+    tags: Vec<String>,
+    area: Vec<RawArea>,
+    #[serde(default)]
+    properties: Vec<RawProperty>,
+}
+
+#[derive(Deserialize)]
+struct RawArea {
+    xpos: u32,
+    ypos: u32,
+    width: u32,
+    height: u32,
+    #[serde(rename = "type", default)]
+    kind: AreaType,
+    #[serde(rename = "match")]
+    match_level: Option<f64>,
+    margin: Option<u32>,
+}
+
+/// Properties are either plain names or objects carrying a name and a value.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawProperty {
+    Name(String),
+    Object { name: String },
+}
+
+impl Needle {
+    /// Load a needle from its JSON file.
+    ///
+    /// The reference image is expected next to the JSON file, with the same base name and a
+    /// `.png` extension.
+    ///
+    /// # Parameters
+    ///
+    /// * json_path: `&Path` - Path to the needle's JSON file.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Needle)` - The loaded needle.
+    /// * `Err(Error)` - An `Error::NeedleError` if either file cannot be read or the needle is
+    ///   invalid.
+    pub fn load(json_path: &Path) -> Result<Needle, Error> {
+        let name: String = json_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .ok_or_else(|| {
+                NeedleError::IoError(format!("'{}' is not a file", json_path.display()))
+            })?;
+
+        let json: String = fs::read_to_string(json_path)
+            .map_err(|e| NeedleError::IoError(format!("{}: {}", json_path.display(), e)))?;
+
+        let png_path: PathBuf = json_path.with_extension("png");
+        let image: RgbaImage = image::open(&png_path)
+            .map_err(|e| NeedleError::ImageError(format!("{}: {}", png_path.display(), e)))?
+            .to_rgba8();
+
+        Needle::from_parts(name, &json, image)
+    }
+
+    /// Create a needle from an already loaded JSON definition and reference image.
+    ///
+    /// # Parameters
+    ///
+    /// * name: `String` - The name of the needle.
+    /// * json: `&str` - The needle definition in the openQA JSON format.
+    /// * image: `RgbaImage` - The reference image.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Needle)` - The needle.
+    /// * `Err(Error)` - An `Error::NeedleError` if the definition is malformed or an area exceeds
+    ///   the image.
+    pub fn from_parts(name: String, json: &str, image: RgbaImage) -> Result<Needle, Error> {
+        let raw: RawNeedle = serde_json::from_str(json)
+            .map_err(|e| NeedleError::ParseError(format!("{}: {}", name, e)))?;
+
+        let mut areas: Vec<NeedleArea> = Vec::with_capacity(raw.area.len());
+        for area in raw.area {
+            let right: Option<u32> = area.xpos.checked_add(area.width);
+            let bottom: Option<u32> = area.ypos.checked_add(area.height);
+            if area.width == 0
+                || area.height == 0
+                || right.is_none_or(|right| right > image.width())
+                || bottom.is_none_or(|bottom| bottom > image.height())
+            {
+                return Err(NeedleError::InvalidArea(format!(
+                    "{}: {}x{}+{}+{} exceeds image of {}x{}",
+                    name,
+                    area.width,
+                    area.height,
+                    area.xpos,
+                    area.ypos,
+                    image.width(),
+                    image.height()
+                ))
+                .into());
+            }
+            areas.push(NeedleArea {
+                x: area.xpos,
+                y: area.ypos,
+                width: area.width,
+                height: area.height,
+                kind: area.kind,
+                match_level: area.match_level.unwrap_or(DEFAULT_MATCH_LEVEL),
+                margin: area.margin.unwrap_or(DEFAULT_MARGIN),
+            });
+        }
+
+        if !areas.iter().any(|a| a.kind == AreaType::Match) {
+            return Err(
+                NeedleError::ParseError(format!("{}: needle has no match area", name)).into(),
+            );
+        }
+
+        let properties: Vec<String> = raw
+            .properties
+            .into_iter()
+            .map(|p| match p {
+                RawProperty::Name(name) | RawProperty::Object { name } => name,
+            })
+            .collect();
+
+        Ok(Needle {
+            name,
+            tags: raw.tags,
+            properties,
+            areas,
+            image,
+        })
+    }
+
+    /// Check whether the needle carries at least one of the given tags.
+    pub fn has_any_tag(&self, tags: &[&str]) -> bool {
+        self.tags.iter().any(|t| tags.contains(&t.as_str()))
+    }
+}
+
+/// Load all needles from a directory and its subdirectories.
+///
+/// Every `.json` file is treated as a needle definition. Needles are returned in the order of
+/// their paths.
+///
+/// # Parameters
+///
+/// * dir: `&Path` - The directory containing the needles.
+///
+/// # Returns
+///
+/// * `Ok(Vec<Needle>)` - All needles found in the directory.
+/// * `Err(Error)` - An `Error::NeedleError` if the directory cannot be read or any needle is
+///   invalid.
+pub fn load_needles(dir: &Path) -> Result<Vec<Needle>, Error> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    collect_json_files(dir, &mut paths)?;
+    paths.sort();
+
+    let needles: Vec<Needle> = paths
+        .iter()
+        .map(|p| Needle::load(p))
+        .collect::<Result<_, _>>()?;
+    info!(target: LOG_TARGET, "Loaded {} needles from '{}'.", needles.len(), dir.display());
+    Ok(needles)
+}
+
+/// Recursively collect all JSON files in a directory.
+fn collect_json_files(dir: &Path, paths: &mut Vec<PathBuf>) -> Result<(), NeedleError> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| NeedleError::IoError(format!("{}: {}", dir.display(), e)))?;
+    for entry in entries {
+        let path: PathBuf = entry
+            .map_err(|e| NeedleError::IoError(format!("{}: {}", dir.display(), e)))?
+            .path();
+        if path.is_dir() {
+            collect_json_files(&path, paths)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Compare a needle against a screenshot.
+///
+/// Each `match` area is searched within its margin around the original position. The best
+/// candidate position and its similarity are reported, regardless of whether the area matched.
+///
+/// # Parameters
+///
+/// * screen: `&RgbaImage` - The screenshot to search in.
+/// * needle: `&Needle` - The needle to look for.
+///
+/// # Returns
+///
+/// * `NeedleMatch` - The comparison result. Use [`NeedleMatch::is_match`] to check whether the
+///   needle matched.
+pub fn match_needle(screen: &RgbaImage, needle: &Needle) -> NeedleMatch {
+    let screen: GrayImage = imageops::grayscale(screen);
+    match_gray(&screen, needle)
+}
+
+/// Compare all needles carrying at least one of the given tags against a screenshot.
+///
+/// # Parameters
+///
+/// * screen: `&RgbaImage` - The screenshot to search in.
+/// * needles: `&[Needle]` - The needles to consider.
+/// * tags: `&[&str]` - The tags to look for.
+///
+/// # Returns
+///
+/// * `Vec<NeedleMatch>` - Results of all candidate needles. Matching needles come first, each
+///   group is ordered by descending similarity.
+pub fn search_needles(screen: &RgbaImage, needles: &[Needle], tags: &[&str]) -> Vec<NeedleMatch> {
+    let screen: GrayImage = imageops::grayscale(screen);
+    let mut results: Vec<NeedleMatch> = needles
+        .iter()
+        .filter(|n| n.has_any_tag(tags))
+        .map(|n| match_gray(&screen, n))
+        .collect();
+
+    results.sort_by(|a, b| {
+        b.is_match()
+            .cmp(&a.is_match())
+            .then(b.similarity.total_cmp(&a.similarity))
+    });
+    results
+}
+
+/// Find the best matching needle carrying at least one of the given tags.
+///
+/// # Returns
+///
+/// * `Some(NeedleMatch)` - The best matching needle.
+/// * `None` - If no needle matched.
+pub fn find_needle(screen: &RgbaImage, needles: &[Needle], tags: &[&str]) -> Option<NeedleMatch> {
+    search_needles(screen, needles, tags)
+        .into_iter()
+        .next()
+        .filter(NeedleMatch::is_match)
+}
+
+/// Compare a needle against a grayscale screenshot.
+fn match_gray(screen: &GrayImage, needle: &Needle) -> NeedleMatch {
+    let reference: GrayImage = imageops::grayscale(&needle.image);
+    let excludes: Vec<&NeedleArea> = needle
+        .areas
+        .iter()
+        .filter(|a| a.kind == AreaType::Exclude)
+        .collect();
+
+    let areas: Vec<AreaMatch> = needle
+        .areas
+        .iter()
+        .filter(|a| a.kind == AreaType::Match)
+        .map(|a| search_area(screen, &reference, a, &excludes))
+        .collect();
+
+    let similarity: f64 = areas.iter().map(|a| a.similarity).fold(100.0, f64::min);
+
+    debug!(target: LOG_TARGET, "Needle '{}' similarity: {:.2}%", needle.name, similarity);
+    NeedleMatch {
+        needle: needle.name.clone(),
+        tags: needle.tags.clone(),
+        similarity,
+        areas,
+    }
+}
+
+/// Search a single area of the reference image on the screen.
+///
+/// Candidate positions are compared by their sum of squared errors. A comparison is aborted as
+/// soon as it exceeds the best error found so far.
+fn search_area(
+    screen: &GrayImage,
+    reference: &GrayImage,
+    area: &NeedleArea,
+    excludes: &[&NeedleArea],
+) -> AreaMatch {
+    // Collect the pixels of the area that take part in the comparison as (dx, dy, value).
+    let mut pixels: Vec<(u32, u32, u8)> = Vec::with_capacity((area.width * area.height) as usize);
+    for dy in 0..area.height {
+        for dx in 0..area.width {
+            let (x, y) = (area.x + dx, area.y + dy);
+            let excluded = excludes
+                .iter()
+                .any(|e| x >= e.x && x < e.x + e.width && y >= e.y && y < e.y + e.height);
+            if !excluded {
+                pixels.push((dx, dy, reference.get_pixel(x, y)[0]));
+            }
+        }
+    }
+
+    let mut result = AreaMatch {
+        x: area.x,
+        y: area.y,
+        width: area.width,
+        height: area.height,
+        similarity: 0.0,
+        match_level: area.match_level,
+    };
+
+    if pixels.is_empty() {
+        result.similarity = 100.0;
+        return result;
+    }
+    if area.width > screen.width() || area.height > screen.height() {
+        return result;
+    }
+
+    let max_x: u32 = screen.width() - area.width;
+    let max_y: u32 = screen.height() - area.height;
+    let x_range = area.x.saturating_sub(area.margin).min(max_x)..=(area.x + area.margin).min(max_x);
+    let y_range = area.y.saturating_sub(area.margin).min(max_y)..=(area.y + area.margin).min(max_y);
+
+    // Start at the original position, so an unmoved area wins ties against displaced candidates.
+    let origin: (u32, u32) = (area.x.min(max_x), area.y.min(max_y));
+    let mut best: (u32, u32) = origin;
+    let mut best_sse: u64 = squared_error(screen, &pixels, origin, u64::MAX);
+
+    'search: for y in y_range {
+        for x in x_range.clone() {
+            if best_sse == 0 {
+                break 'search;
+            }
+            if (x, y) == origin {
+                continue;
+            }
+            let sse: u64 = squared_error(screen, &pixels, (x, y), best_sse);
+            if sse < best_sse {
+                best_sse = sse;
+                best = (x, y);
+            }
+        }
+    }
+
+    result.x = best.0;
+    result.y = best.1;
+    result.similarity = similarity(best_sse, pixels.len());
+    result
+}
+
+/// Sum of squared errors between the area pixels and the screen at the given position.
+///
+/// Stops early and returns a value of at least `limit` once the error reaches `limit`.
+fn squared_error(
+    screen: &GrayImage,
+    pixels: &[(u32, u32, u8)],
+    pos: (u32, u32),
+    limit: u64,
+) -> u64 {
+    let mut sse: u64 = 0;
+    for &(dx, dy, value) in pixels {
+        let diff: i64 = screen.get_pixel(pos.0 + dx, pos.1 + dy)[0] as i64 - value as i64;
+        sse += (diff * diff) as u64;
+        if sse >= limit {
+            return sse;
+        }
+    }
+    sse
+}
+
+/// Convert a sum of squared errors into a similarity in percent.
+///
+/// The similarity is the peak signal-to-noise ratio of the area, scaled so that
+/// [`PSNR_IDENTICAL`] and above map to 100%.
+fn similarity(sse: u64, count: usize) -> f64 {
+    (psnr(sse, count) / PSNR_IDENTICAL).clamp(0.0, 1.0) * 100.0
+}
+
+/// Convert a sum of squared errors of 8 bit values into the peak signal-to-noise ratio in dB.
+///
+/// Identical values have an infinite ratio.
+pub(crate) fn psnr(sse: u64, count: usize) -> f64 {
+    if sse == 0 {
+        return f64::INFINITY;
+    }
+    let mse: f64 = sse as f64 / count as f64;
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
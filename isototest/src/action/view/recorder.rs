@@ -0,0 +1,554 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Recorder module
+//!
+//! This module records the screen of a [`Session`] into a video, which helps to understand why a
+//! test failed. Frames are taken from the session's framebuffer at a fixed rate, encoded as JPEG
+//! and written into an AVI file (Motion JPEG), which common players and browsers can show without
+//! further conversion.
+//!
+//! Frames in which the screen did not change are not encoded again. They refer to the data of the
+//! previous frame in the index of the file instead, so long phases of a still screen take almost
+//! no space.
+//!
+//! Next to the video, a JSON index is written which maps the test steps marked with
+//! [`Recorder::mark_step`] to their position in the video:
+//!
+//! ```json
+//! {
+//!     "video": "test.avi",
+//!     "fps": 10,
+//!     "frames": 245,
+//!     "unique_frames": 31,
+//!     "started": "2024-07-30T12:00:00Z",
+//!     "duration": 24.5,
+//!     "steps": [
+//!         { "name": "boot", "timestamp": "2024-07-30T12:00:01.200Z", "offset": 1.2, "frame": 12 }
+//!     ]
+//! }
+//! ```
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use chrono::{DateTime, Utc};
+use image::codecs::jpeg::JpegEncoder;
+use image::{imageops, DynamicImage, RgbImage, RgbaImage};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use super::framebuffer::Framebuffer;
+use crate::errors::view_errors::ViewError;
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+use crate::session::Session;
+
+/// Frame rate of recordings by default.
+pub const DEFAULT_FPS: u32 = 10;
+
+/// JPEG quality of recorded frames by default.
+pub const DEFAULT_QUALITY: u8 = 75;
+
+/// Length of the AVI headers up to the start of the `movi` list.
+const HEADER_LEN: u64 = 212;
+
+/// Position of the `movi` identifier, to which the offsets in the index are relative.
+const MOVI_OFFSET: u64 = HEADER_LEN + 8;
+
+/// AVI flag marking a file with an index.
+const AVIF_HASINDEX: u32 = 0x10;
+
+/// AVI flag marking a frame which can be decoded on its own.
+const AVIIF_KEYFRAME: u32 = 0x10;
+
+/// Settings of a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordingConfig {
+    fps: u32,
+    quality: u8,
+}
+
+impl RecordingConfig {
+    /// Create the default settings.
+    ///
+    /// Recordings run at [`DEFAULT_FPS`] frames per second with a JPEG quality of
+    /// [`DEFAULT_QUALITY`].
+    pub fn new() -> RecordingConfig {
+        RecordingConfig::default()
+    }
+
+    /// Set the frame rate.
+    ///
+    /// # Parameters
+    ///
+    /// * fps: `u32` - Frames per second, between 1 and 60.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RecordingConfig)` - The settings.
+    /// * `Err(Error)` - If the frame rate is out of range.
+    pub fn with_fps(self, fps: u32) -> Result<RecordingConfig, Error> {
+        if !(1..=60).contains(&fps) {
+            return Err(Error::InvalidInput(format!(
+                "Frame rate must be between 1 and 60, got {}",
+                fps
+            )));
+        }
+        Ok(RecordingConfig { fps, ..self })
+    }
+
+    /// Set the JPEG quality of the frames.
+    ///
+    /// # Parameters
+    ///
+    /// * quality: `u8` - Quality between 1 (smallest) and 100 (best).
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RecordingConfig)` - The settings.
+    /// * `Err(Error)` - If the quality is out of range.
+    pub fn with_quality(self, quality: u8) -> Result<RecordingConfig, Error> {
+        if !(1..=100).contains(&quality) {
+            return Err(Error::InvalidInput(format!(
+                "JPEG quality must be between 1 and 100, got {}",
+                quality
+            )));
+        }
+        Ok(RecordingConfig { quality, ..self })
+    }
+
+    /// Frames per second.
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    /// JPEG quality of the frames.
+    pub fn quality(&self) -> u8 {
+        self.quality
+    }
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        RecordingConfig {
+            fps: DEFAULT_FPS,
+            quality: DEFAULT_QUALITY,
+        }
+    }
+}
+
+/// A test step marked during a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub name: String,
+    /// Wall clock time at which the step was marked.
+    pub timestamp: DateTime<Utc>,
+    /// Position in the video in seconds.
+    pub offset: f64,
+    /// Number of the frame shown at the time of the step, starting at 0.
+    pub frame: u64,
+}
+
+/// The index written next to a video.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordingIndex {
+    /// File name of the video.
+    pub video: String,
+    pub fps: u32,
+    /// Number of frames in the video.
+    pub frames: u64,
+    /// Number of frames which have been encoded, the others repeat their predecessor.
+    pub unique_frames: u64,
+    /// Wall clock time of the first frame.
+    pub started: DateTime<Utc>,
+    /// Length of the video in seconds.
+    pub duration: f64,
+    pub steps: Vec<Step>,
+}
+
+impl RecordingIndex {
+    /// The path of the index belonging to a video, which is the video's path with `.json`
+    /// appended.
+    pub fn path_for(video: &Path) -> PathBuf {
+        let mut path = video.as_os_str().to_os_string();
+        path.push(".json");
+        PathBuf::from(path)
+    }
+}
+
+/// A running recording of a session's screen.
+///
+/// The recording runs in the background until it is stopped with [`Recorder::stop`] or the
+/// session is closed. Dropping the recorder finishes the video, but does not write the index.
+pub struct Recorder {
+    path: PathBuf,
+    fps: u32,
+    started: Instant,
+    started_at: DateTime<Utc>,
+    steps: Vec<Step>,
+    stop: CancellationToken,
+    task: JoinHandle<Result<(u64, u64), Error>>,
+}
+
+impl Recorder {
+    /// Start recording the screen of a session.
+    ///
+    /// The video has the resolution of the screen at the start of the recording. Frames of
+    /// another resolution, e.g. after the remote machine switched its video mode, are scaled to
+    /// it.
+    ///
+    /// # Parameters
+    ///
+    /// * session: `&Session` - The session to record.
+    /// * path: `&Path` - The video file to write, usually ending in `.avi`. Existing files are
+    ///   overwritten.
+    /// * config: `&RecordingConfig` - Frame rate and quality of the video.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Recorder)` - The running recording.
+    /// * `Err(Error)` - If the file cannot be created.
+    pub fn start(
+        session: &Session,
+        path: &Path,
+        config: &RecordingConfig,
+    ) -> Result<Recorder, Error> {
+        let (width, height): (u32, u32) = session.resolution();
+        let writer: AviWriter = AviWriter::create(path, width, height, config)?;
+        info!(target: LOG_TARGET, "Recording {}x{} at {} fps to '{}'...", width, height, config.fps(), path.display());
+
+        let stop: CancellationToken = CancellationToken::new();
+        let task: JoinHandle<Result<(u64, u64), Error>> = tokio::spawn(record(
+            session.shared_framebuffer(),
+            session.frame_updates(),
+            writer,
+            config.fps(),
+            stop.clone(),
+        ));
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            fps: config.fps(),
+            started: Instant::now(),
+            started_at: Utc::now(),
+            steps: Vec::new(),
+            stop,
+            task,
+        })
+    }
+
+    /// Mark the start of a test step at the current position of the video.
+    ///
+    /// # Parameters
+    ///
+    /// * name: `&str` - Name of the step, e.g. the name of the test module.
+    pub fn mark_step(&mut self, name: &str) {
+        let offset: f64 = self.started.elapsed().as_secs_f64();
+        let step: Step = Step {
+            name: name.to_string(),
+            timestamp: Utc::now(),
+            offset,
+            frame: (offset * self.fps as f64) as u64,
+        };
+        debug!(target: LOG_TARGET, "Step '{}' at {:.2}s of the recording.", name, offset);
+        self.steps.push(step);
+    }
+
+    /// The steps marked so far.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Stop the recording, finish the video and write its index.
+    ///
+    /// The index is written to [`RecordingIndex::path_for`] the video.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RecordingIndex)` - The index of the video.
+    /// * `Err(Error)` - If writing the video or the index failed.
+    pub async fn stop(mut self) -> Result<RecordingIndex, Error> {
+        self.stop.cancel();
+        let (frames, unique_frames): (u64, u64) = (&mut self.task)
+            .await
+            .map_err(|e| Error::from(ViewError::IoError(format!("Recording failed: {}", e))))??;
+
+        let index: RecordingIndex = RecordingIndex {
+            video: self
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            fps: self.fps,
+            frames,
+            unique_frames,
+            started: self.started_at,
+            duration: frames as f64 / self.fps as f64,
+            steps: std::mem::take(&mut self.steps),
+        };
+        let index_path: PathBuf = RecordingIndex::path_for(&self.path);
+        let json: String = serde_json::to_string_pretty(&index)
+            .map_err(|e| ViewError::IoError(format!("Unable to serialize index: {}", e)))?;
+        fs::write(&index_path, json).map_err(|e| {
+            error!(target: LOG_TARGET, "Unable to write recording index to '{}': {}", index_path.display(), e);
+            ViewError::from(e)
+        })?;
+
+        info!(
+            target: LOG_TARGET,
+            "Recorded {} frames ({} unique) to '{}'.",
+            frames,
+            unique_frames,
+            self.path.display()
+        );
+        Ok(index)
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+/// Write a frame of the framebuffer to the video at every tick until the recording is stopped or
+/// the session is closed.
+///
+/// Returns the number of frames and the number of encoded frames.
+async fn record(
+    framebuffer: Arc<RwLock<Framebuffer>>,
+    updates: watch::Receiver<u64>,
+    mut writer: AviWriter,
+    fps: u32,
+    stop: CancellationToken,
+) -> Result<(u64, u64), Error> {
+    let mut ticks: tokio::time::Interval =
+        tokio::time::interval(std::time::Duration::from_secs(1) / fps);
+    let mut last_generation: Option<u64> = None;
+    loop {
+        tokio::select! {
+            _ = stop.cancelled() => break,
+            _ = ticks.tick() => {}
+        }
+        if updates.has_changed().is_err() {
+            debug!(target: LOG_TARGET, "Session closed, stopping recording.");
+            break;
+        }
+
+        let (generation, frame): (u64, Arc<RgbaImage>) = {
+            let fb = framebuffer.read().unwrap_or_else(|e| e.into_inner());
+            (fb.generation(), fb.shared())
+        };
+        if last_generation == Some(generation) && writer.repeat_frame() {
+            continue;
+        }
+        last_generation = Some(generation);
+        writer = tokio::task::spawn_blocking(move || -> Result<AviWriter, ViewError> {
+            writer.write_frame(&frame)?;
+            Ok(writer)
+        })
+        .await
+        .map_err(|e| ViewError::IoError(format!("Encoding frame failed: {}", e)))??;
+    }
+
+    let counts: (u64, u64) = (writer.frames.len() as u64, writer.unique_frames);
+    tokio::task::spawn_blocking(move || writer.finish())
+        .await
+        .map_err(|e| ViewError::IoError(format!("Finishing video failed: {}", e)))??;
+    Ok(counts)
+}
+
+/// Writer of Motion JPEG videos in an AVI container.
+struct AviWriter {
+    file: BufWriter<File>,
+    width: u32,
+    height: u32,
+    fps: u32,
+    quality: u8,
+    /// Offset relative to the `movi` list and size of the data shown in each frame.
+    frames: Vec<(u32, u32)>,
+    unique_frames: u64,
+    /// Current end of the file.
+    position: u64,
+    /// Size of the largest frame.
+    largest: u32,
+}
+
+impl AviWriter {
+    /// Create the video file and write preliminary headers.
+    fn create(
+        path: &Path,
+        width: u32,
+        height: u32,
+        config: &RecordingConfig,
+    ) -> Result<AviWriter, ViewError> {
+        if width == 0 || height == 0 || width > u16::MAX as u32 || height > u16::MAX as u32 {
+            return Err(ViewError::ImageError(format!(
+                "Unable to record a screen of {}x{} pixels",
+                width, height
+            )));
+        }
+        let file: File = File::create(path).map_err(|e| {
+            error!(target: LOG_TARGET, "Unable to create video '{}': {}", path.display(), e);
+            ViewError::from(e)
+        })?;
+        let mut writer: AviWriter = AviWriter {
+            file: BufWriter::new(file),
+            width,
+            height,
+            fps: config.fps(),
+            quality: config.quality(),
+            frames: Vec::new(),
+            unique_frames: 0,
+            position: MOVI_OFFSET + 4,
+            largest: 0,
+        };
+        writer.write_headers(0, 0)?;
+        Ok(writer)
+    }
+
+    /// Encode a frame and append it to the video.
+    fn write_frame(&mut self, frame: &RgbaImage) -> Result<(), ViewError> {
+        let rgb: RgbImage = if frame.dimensions() == (self.width, self.height) {
+            DynamicImage::ImageRgba8(frame.clone()).to_rgb8()
+        } else {
+            let scaled: RgbaImage = imageops::resize(
+                frame,
+                self.width,
+                self.height,
+                imageops::FilterType::Triangle,
+            );
+            DynamicImage::ImageRgba8(scaled).to_rgb8()
+        };
+        let mut data: Vec<u8> = Vec::new();
+        JpegEncoder::new_with_quality(&mut data, self.quality).encode_image(&rgb)?;
+
+        let padding: u64 = data.len() as u64 % 2;
+        let end: u64 = self.position + 8 + data.len() as u64 + padding;
+        // The index written by `finish` must fit into the file as well.
+        if end + 8 + 16 * (self.frames.len() as u64 + 1) > u32::MAX as u64 {
+            return Err(ViewError::IoError(
+                "Video exceeds the maximum size of an AVI file".to_string(),
+            ));
+        }
+
+        let size: u32 = data.len() as u32;
+        self.file.write_all(b"00dc")?;
+        self.file.write_all(&size.to_le_bytes())?;
+        self.file.write_all(&data)?;
+        if padding != 0 {
+            self.file.write_all(&[0])?;
+        }
+        self.frames
+            .push(((self.position - MOVI_OFFSET) as u32, size));
+        self.unique_frames += 1;
+        self.largest = self.largest.max(size);
+        self.position = end;
+        Ok(())
+    }
+
+    /// Show the previous frame once more.
+    ///
+    /// Returns `false` if there is no previous frame.
+    fn repeat_frame(&mut self) -> bool {
+        match self.frames.last().copied() {
+            Some(frame) => {
+                self.frames.push(frame);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Write the index and the final headers.
+    fn finish(mut self) -> Result<(), ViewError> {
+        let movi_size: u32 = (self.position - MOVI_OFFSET) as u32;
+        self.file.write_all(b"idx1")?;
+        self.file
+            .write_all(&(16 * self.frames.len() as u32).to_le_bytes())?;
+        for (offset, size) in &self.frames {
+            self.file.write_all(b"00dc")?;
+            for value in [AVIIF_KEYFRAME, *offset, *size] {
+                self.file.write_all(&value.to_le_bytes())?;
+            }
+        }
+        let riff_size: u32 = (self.position + 8 + 16 * self.frames.len() as u64 - 8) as u32;
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_headers(riff_size, movi_size)?;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    /// Write the headers of the file, up to the `movi` identifier.
+    fn write_headers(&mut self, riff_size: u32, movi_size: u32) -> Result<(), ViewError> {
+        let frames: u32 = self.frames.len() as u32;
+        let mut header: Vec<u8> = Vec::with_capacity(MOVI_OFFSET as usize + 4);
+        let mut put = |fourcc: &[u8; 4], values: &[u32]| {
+            header.extend_from_slice(fourcc);
+            for value in values {
+                header.extend_from_slice(&value.to_le_bytes());
+            }
+        };
+
+        put(b"RIFF", &[riff_size]);
+        put(b"AVI ", &[]);
+        put(b"LIST", &[192]);
+        put(b"hdrl", &[]);
+        // Main header
+        put(
+            b"avih",
+            &[
+                56,
+                1_000_000 / self.fps,
+                self.largest * self.fps,
+                0,
+                AVIF_HASINDEX,
+                frames,
+                0,
+                1,
+                self.largest,
+                self.width,
+                self.height,
+                0,
+                0,
+                0,
+                0,
+            ],
+        );
+        put(b"LIST", &[116]);
+        put(b"strl", &[]);
+        // Stream header, followed by its frame rectangle.
+        put(b"strh", &[56]);
+        put(b"vids", &[]);
+        put(
+            b"MJPG",
+            &[
+                0,
+                0,
+                0,
+                1,
+                self.fps,
+                0,
+                frames,
+                self.largest,
+                u32::MAX,
+                0,
+                0,
+                self.width | (self.height << 16),
+            ],
+        );
+        // Stream format, a bitmap info header.
+        put(b"strf", &[40, 40, self.width, self.height, 1 | (24 << 16)]);
+        put(b"MJPG", &[self.width * self.height * 3, 0, 0, 0, 0]);
+        put(b"LIST", &[movi_size]);
+        put(b"movi", &[]);
+
+        self.file.write_all(&header)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,300 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Connection configuration module
+//!
+//! A [`VncConfig`] describes how a client connects to the VNC server: the encodings it asks for,
+//! whether other clients may stay connected, the pixel format image data is sent in, how long to
+//! wait for the server, how to cancel connecting and whether to secure the connection with TLS.
+//! Pass it to [`super::connect`] or [`crate::session::Session::connect`].
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::error;
+use tokio_util::sync::CancellationToken;
+use vnc::{PixelFormat, VncEncoding};
+
+use super::tls::TlsConfig;
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// Encodings requested by default, in order of preference.
+pub const DEFAULT_ENCODINGS: [VncEncoding; 7] = [
+    VncEncoding::Tight,
+    VncEncoding::Zrle,
+    VncEncoding::CopyRect,
+    VncEncoding::Raw,
+    VncEncoding::Trle,
+    VncEncoding::CursorPseudo,
+    VncEncoding::DesktopSizePseudo,
+];
+
+/// Time to wait for the TCP connection by default.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time to wait for the protocol handshake by default.
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time to wait for the authentication by default.
+pub const DEFAULT_AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where the password for VNC authentication is taken from.
+///
+/// The password is only read if the server requires authentication.
+///
+/// # Members
+///
+/// * `Empty` - An empty password.
+/// * `Plain` - The given password.
+/// * `Env` - The value of the given environment variable.
+/// * `File` - The first line of the given file.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PasswordSource {
+    #[default]
+    Empty,
+    Plain(String),
+    Env(String),
+    File(PathBuf),
+}
+
+impl PasswordSource {
+    /// Read the password.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The password.
+    /// * `Err(Error)` - An `Error::AuthenticationError` if the environment variable is not set or
+    ///   the file cannot be read.
+    pub fn resolve(&self) -> Result<String, Error> {
+        let result: Result<String, String> = match self {
+            PasswordSource::Empty => Ok(String::new()),
+            PasswordSource::Plain(password) => Ok(password.clone()),
+            PasswordSource::Env(var) => std::env::var(var)
+                .map_err(|e| format!("Unable to read password from '${}': {}", var, e)),
+            PasswordSource::File(path) => std::fs::read_to_string(path)
+                .map(|content| content.lines().next().unwrap_or_default().to_string())
+                .map_err(|e| format!("Unable to read password from '{}': {}", path.display(), e)),
+        };
+        result.map_err(|msg| {
+            error!(target: LOG_TARGET, "{}", msg);
+            Error::AuthenticationError(msg)
+        })
+    }
+}
+
+/// Configuration of a connection to a VNC server.
+///
+/// The default configuration requests [`DEFAULT_ENCODINGS`], shares the server with other
+/// clients, receives image data as RGBA and waits for the server according to
+/// [`DEFAULT_CONNECT_TIMEOUT`], [`DEFAULT_HANDSHAKE_TIMEOUT`] and [`DEFAULT_AUTH_TIMEOUT`].
+///
+/// # Example
+///
+/// ``` no_run
+/// use std::time::Duration;
+/// use isototest::connection::{config::{PasswordSource, VncConfig}, connect};
+///
+/// # async fn example() -> Result<(), isototest::Error> {
+/// let config = VncConfig::new()
+///     .with_shared(false)
+///     .with_connect_timeout(Duration::from_secs(10))
+///     .with_password(PasswordSource::Env("VNC_PASSWORD".to_string()));
+/// let client = connect("127.0.0.1:5900", &config).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct VncConfig {
+    encodings: Vec<VncEncoding>,
+    shared: bool,
+    pixel_format: Option<PixelFormat>,
+    connect_timeout: Duration,
+    handshake_timeout: Duration,
+    auth_timeout: Duration,
+    password: PasswordSource,
+    cancellation: CancellationToken,
+    tls: Option<TlsConfig>,
+}
+
+impl VncConfig {
+    /// Create the default configuration.
+    pub fn new() -> VncConfig {
+        VncConfig::default()
+    }
+
+    /// Set the encodings to request, in order of preference.
+    ///
+    /// # Parameters
+    ///
+    /// * encodings: `Vec<VncEncoding>` - The encodings. Pseudo encodings like
+    ///   `DesktopSizePseudo` must be included to receive resolution changes.
+    pub fn with_encodings(self, encodings: Vec<VncEncoding>) -> VncConfig {
+        VncConfig { encodings, ..self }
+    }
+
+    /// Set whether other clients may stay connected to the server.
+    ///
+    /// # Parameters
+    ///
+    /// * shared: `bool` - `true` to share the server, `false` to disconnect all other clients.
+    pub fn with_shared(self, shared: bool) -> VncConfig {
+        VncConfig { shared, ..self }
+    }
+
+    /// Set the pixel format in which the server sends image data.
+    ///
+    /// The view module converts image data according to this format, so any true colour format
+    /// works.
+    ///
+    /// # Parameters
+    ///
+    /// * pixel_format: `Option<PixelFormat>` - The pixel format. If `None`, the server's native
+    ///   format is used.
+    pub fn with_pixel_format(self, pixel_format: Option<PixelFormat>) -> VncConfig {
+        VncConfig {
+            pixel_format,
+            ..self
+        }
+    }
+
+    /// Set how long to wait for the TCP connection to the server.
+    ///
+    /// # Parameters
+    ///
+    /// * timeout: `Duration` - The maximum time to wait.
+    pub fn with_connect_timeout(self, timeout: Duration) -> VncConfig {
+        VncConfig {
+            connect_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// Set how long to wait for the protocol handshake after the TCP connection is established.
+    ///
+    /// The handshake negotiates the protocol version and the security type. If the server does not
+    /// require authentication, it lasts until the connection is complete.
+    ///
+    /// # Parameters
+    ///
+    /// * timeout: `Duration` - The maximum time to wait.
+    pub fn with_handshake_timeout(self, timeout: Duration) -> VncConfig {
+        VncConfig {
+            handshake_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// Set how long to wait for the authentication after the handshake.
+    ///
+    /// This includes exchanging the initialisation messages, which complete the connection.
+    ///
+    /// # Parameters
+    ///
+    /// * timeout: `Duration` - The maximum time to wait.
+    pub fn with_auth_timeout(self, timeout: Duration) -> VncConfig {
+        VncConfig {
+            auth_timeout: timeout,
+            ..self
+        }
+    }
+
+    /// Set a token to cancel connecting.
+    ///
+    /// Cancelling the token aborts connection attempts in progress and stops retrying, see
+    /// [`super::reconnect`].
+    ///
+    /// # Parameters
+    ///
+    /// * token: `CancellationToken` - The token.
+    pub fn with_cancellation_token(self, token: CancellationToken) -> VncConfig {
+        VncConfig {
+            cancellation: token,
+            ..self
+        }
+    }
+
+    /// Secure the connection with TLS, using the VeNCrypt security type.
+    ///
+    /// Servers not offering VeNCrypt are rejected, so the connection is never established in
+    /// plaintext. See [`super::tls`].
+    ///
+    /// # Parameters
+    ///
+    /// * tls: `TlsConfig` - The TLS configuration.
+    pub fn with_tls(self, tls: TlsConfig) -> VncConfig {
+        VncConfig {
+            tls: Some(tls),
+            ..self
+        }
+    }
+
+    /// Set where the password for VNC authentication is taken from.
+    ///
+    /// # Parameters
+    ///
+    /// * password: `PasswordSource` - The source of the password.
+    pub fn with_password(self, password: PasswordSource) -> VncConfig {
+        VncConfig { password, ..self }
+    }
+
+    /// The requested encodings, in order of preference.
+    pub fn encodings(&self) -> &[VncEncoding] {
+        &self.encodings
+    }
+
+    /// Whether other clients may stay connected to the server.
+    pub fn shared(&self) -> bool {
+        self.shared
+    }
+
+    /// The requested pixel format, or `None` for the server's native format.
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        self.pixel_format
+    }
+
+    /// How long to wait for the TCP connection.
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    /// How long to wait for the protocol handshake.
+    pub fn handshake_timeout(&self) -> Duration {
+        self.handshake_timeout
+    }
+
+    /// How long to wait for the authentication.
+    pub fn auth_timeout(&self) -> Duration {
+        self.auth_timeout
+    }
+
+    /// The TLS configuration, if the connection is secured with TLS.
+    pub fn tls(&self) -> Option<&TlsConfig> {
+        self.tls.as_ref()
+    }
+
+    /// The token cancelling connection attempts.
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Where the password is taken from.
+    pub fn password(&self) -> &PasswordSource {
+        &self.password
+    }
+}
+
+impl Default for VncConfig {
+    fn default() -> Self {
+        VncConfig {
+            encodings: DEFAULT_ENCODINGS.to_vec(),
+            // Allow for multiple other VNC sessions to be connected at once.
+            shared: true,
+            pixel_format: Some(PixelFormat::rgba()),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            auth_timeout: DEFAULT_AUTH_TIMEOUT,
+            password: PasswordSource::Empty,
+            cancellation: CancellationToken::new(),
+            tls: None,
+        }
+    }
+}
@@ -0,0 +1,585 @@
+// SPDX-FileCopyrightTest: Christopher Hock <christopher.hock@suse.com>
+// SPDX-License-Identifier: GPL-2.0-or-later
+
+//! This module handles the VncClient and its connection to the VncServer.
+//!
+//! How the client connects is configured with a [`config::VncConfig`]. Clients connect over TCP,
+//! Unix domain sockets or already established streams, see [`transport`], optionally secured with
+//! TLS, see [`tls`]. Events sent by the server can be distributed to multiple consumers with an
+//! [`EventDispatcher`], which can also re-establish lost connections, see [`reconnect`].
+pub mod config;
+pub mod reconnect;
+pub mod tls;
+pub mod transport;
+
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use log::{debug, error, info, warn};
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio::{
+    self,
+    net::TcpStream,
+    sync::{broadcast, watch},
+    task::JoinHandle,
+    time::Instant,
+};
+use vnc::{PixelFormat, VncClient, VncConnector, VncError, VncEvent, X11Event};
+
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+use config::{PasswordSource, VncConfig};
+use reconnect::{Reconnect, ReconnectEvent, RetryPolicy};
+use transport::{dial, Target, VncStream};
+
+/// Interval in which incremental framebuffer updates are requested from the server.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time to wait before polling the client again if no event is pending.
+const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Number of framebuffer events buffered per subscriber.
+const FRAMEBUFFER_CHANNEL_SIZE: usize = 1024;
+
+/// Number of other events buffered per subscriber.
+const EVENT_CHANNEL_SIZE: usize = 64;
+
+/// Create a new VNC client.
+///
+/// During the connection process the connection to the VNC server is
+/// tested. The client uses the default [`VncConfig`], see [`connect`] to configure it.
+///
+/// # Parameters
+///
+/// * target_ip: `String` - The IP and port of the VNC target server (e.g `172.0.0.1:5900`), or
+///   the path of its Unix domain socket prefixed with `unix:`.
+/// * psw: `String` - The password used for authenticating with the server. (If the server
+///   does not use authentication, this is irrelevant.)
+///
+/// # Returns
+///
+/// * vnc: `Ok(VncClient)` - A new instance of a `vnc-rs` `VncClient`.
+/// * `Err(Error)` - An `Error::ConnectionError` if the server cannot be reached, an
+///   `Error::AuthenticationError` if the password is rejected, or an `Error::ProtocolError` if the
+///   handshake fails otherwise.
+pub async fn create_vnc_client(target_ip: String, psw: Option<String>) -> Result<VncClient, Error> {
+    let password: PasswordSource = match psw {
+        Some(psw) => PasswordSource::Plain(psw),
+        None => {
+            debug!("No password provided; using empty password.");
+            PasswordSource::Empty
+        }
+    };
+    connect(target_ip, &VncConfig::new().with_password(password)).await
+}
+
+/// Create a new VNC client with the given configuration.
+///
+/// Connecting is split into three phases, each limited by its own timeout of the `config`:
+/// establishing the connection, the protocol handshake and the authentication. Without
+/// authentication the handshake lasts until the client is connected. Cancelling the
+/// cancellation token of the `config` aborts connecting in any phase.
+///
+/// # Parameters
+///
+/// * target: `impl Into<Target>` - The VNC target server, e.g. `172.0.0.1:5900` or
+///   `unix:/run/qemu/vnc.sock`, see [`Target::parse`].
+/// * config: `&VncConfig` - The configuration of the connection.
+///
+/// # Returns
+///
+/// * `Ok(VncClient)` - The connected client.
+/// * `Err(Error)` - An `Error::ConnectionError` if the server cannot be reached, an
+///   `Error::ConnectTimeout`, `Error::HandshakeTimeout` or `Error::AuthenticationTimeout` if a
+///   phase does not complete in time, an `Error::AuthenticationError` if the password is rejected,
+///   an `Error::Cancelled` if connecting is cancelled, or an `Error::ProtocolError` if the
+///   handshake fails otherwise.
+pub async fn connect(target: impl Into<Target>, config: &VncConfig) -> Result<VncClient, Error> {
+    let target: Target = target.into();
+    info!(target: LOG_TARGET, "Creating VNC client for target: '{}'", target);
+
+    let timeout: Duration = config.connect_timeout();
+    let cancelled = config.cancellation_token().cancelled();
+    match &target {
+        Target::Tcp(addr) => tokio::select! {
+            _ = cancelled => Err(cancelled_error()),
+            stream = dial(timeout, TcpStream::connect(addr)) => start(stream?, config, host(addr)).await,
+        },
+        #[cfg(unix)]
+        Target::Unix(path) => tokio::select! {
+            _ = cancelled => Err(cancelled_error()),
+            stream = dial(timeout, UnixStream::connect(path)) => start(stream?, config, None).await,
+        },
+    }
+}
+
+/// Create a new VNC client on an established stream.
+///
+/// Use this for transports which cannot be dialed by [`connect`], e.g. an SSH tunnel or an
+/// in-memory pipe. The handshake and authentication are limited by the timeouts of the `config`
+/// and can be cancelled with its cancellation token. Clients connected this way cannot reconnect.
+///
+/// # Parameters
+///
+/// * stream: `impl VncStream` - The stream connected to the VNC server.
+/// * config: `&VncConfig` - The configuration of the connection. If it uses TLS, the server
+///   certificate is verified against the server name of its [`tls::TlsConfig`].
+///
+/// # Returns
+///
+/// * `Ok(VncClient)` - The connected client.
+/// * `Err(Error)` - See [`connect`].
+pub async fn connect_stream(
+    stream: impl VncStream,
+    config: &VncConfig,
+) -> Result<VncClient, Error> {
+    start(stream, config, None).await
+}
+
+/// Perform the handshake on a connected stream, securing it with TLS if configured.
+///
+/// The TLS negotiation counts towards the handshake timeout.
+async fn start(
+    stream: impl VncStream,
+    config: &VncConfig,
+    host: Option<&str>,
+) -> Result<VncClient, Error> {
+    let deadline: Instant = Instant::now() + config.handshake_timeout();
+    let Some(tls) = config.tls() else {
+        return handshake(stream, config, deadline).await;
+    };
+    let stream = tokio::select! {
+        stream = tls::negotiate(stream, tls, config.password(), host) => stream?,
+        _ = config.cancellation_token().cancelled() => return Err(cancelled_error()),
+        _ = tokio::time::sleep_until(deadline) => {
+            return Err(handshake_timeout_error(config.handshake_timeout()));
+        }
+    };
+    handshake(stream, config, deadline).await
+}
+
+/// Perform the VNC handshake and authentication on a connected stream.
+///
+/// The handshake must be completed before `deadline`.
+async fn handshake(
+    stream: impl VncStream,
+    config: &VncConfig,
+    deadline: Instant,
+) -> Result<VncClient, Error> {
+    // The authentication callback is only awaited once the handshake has completed and the
+    // server requested a password, which marks the start of the authentication phase.
+    let (auth_tx, auth_rx) = watch::channel(false);
+    let password: PasswordSource = config.password().clone();
+    let mut connector = VncConnector::new(stream)
+        .set_auth_method(async move {
+            auth_tx.send_replace(true);
+            // The detailed cause has already been logged.
+            password.resolve().map_err(|_| VncError::NoPassword)
+        })
+        .allow_shared(config.shared());
+    for encoding in config.encodings() {
+        connector = connector.add_encoding(*encoding);
+    }
+    if let Some(pixel_format) = config.pixel_format() {
+        connector = connector.set_pixel_format(pixel_format);
+    }
+
+    let mut start = match connector.build() {
+        Ok(vnc) => vnc,
+        Err(e) => {
+            error!(target: LOG_TARGET, "Failed to build VNC client: {}", e);
+            return Err(e.into());
+        }
+    }
+    .try_start();
+
+    let result = tokio::select! {
+        result = &mut start => result,
+        _ = config.cancellation_token().cancelled() => return Err(cancelled_error()),
+        _ = tokio::time::sleep_until(deadline) => {
+            return Err(handshake_timeout_error(config.handshake_timeout()));
+        }
+        _ = authentication_started(auth_rx) => {
+            debug!(target: LOG_TARGET, "Handshake completed; authenticating...");
+            let auth_timeout: Duration = config.auth_timeout();
+            tokio::select! {
+                result = &mut start => result,
+                _ = config.cancellation_token().cancelled() => return Err(cancelled_error()),
+                _ = tokio::time::sleep(auth_timeout) => {
+                    error!(target: LOG_TARGET, "Authentication not completed within {:?}.", auth_timeout);
+                    return Err(Error::AuthenticationTimeout(auth_timeout));
+                }
+            }
+        }
+    };
+    let vnc: VncClient = result?.finish()?;
+
+    info!("VNC Client successfully built and started.");
+
+    Ok(vnc)
+}
+
+/// Wait until the authentication callback has been called.
+///
+/// If the handshake completed without authentication, the callback is dropped and this never
+/// returns.
+async fn authentication_started(mut started: watch::Receiver<bool>) {
+    if started.wait_for(|started| *started).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// The host of an address, used as the TLS server name.
+fn host(addr: &str) -> Option<&str> {
+    let host: &str = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    Some(host.trim_start_matches('[').trim_end_matches(']'))
+}
+
+/// Log and return the error for a handshake not completed in time.
+fn handshake_timeout_error(timeout: Duration) -> Error {
+    error!(target: LOG_TARGET, "Handshake not completed within {:?}.", timeout);
+    Error::HandshakeTimeout(timeout)
+}
+
+/// Log and return the error for a cancelled connection attempt.
+fn cancelled_error() -> Error {
+    info!(target: LOG_TARGET, "Connecting cancelled.");
+    Error::Cancelled
+}
+
+/// Stop VNC engine, release all resources.
+///
+/// # Parameters
+///
+/// * client: `VncClient` - The client to kill.
+///
+/// # Returns
+///
+/// * `Ok(())` - In case the client terminates correctly.
+/// * `Err(Error)` - Escalates the `VncError` upwards, if the `.close()` function of `vnc-rs`
+///   returns an error.
+pub async fn kill_client(client: VncClient) -> Result<(), Error> {
+    info!(target: LOG_TARGET, "Closing connection...");
+    match client.close().await {
+        Ok(_) => {
+            info!(target: LOG_TARGET, "Connection closed.");
+        }
+        Err(e) => {
+            error!(target: LOG_TARGET, "Unable to close connection: {}", e);
+            return Err(e.into());
+        }
+    };
+    drop(client);
+    info!(target: LOG_TARGET, "Client dropped.");
+    Ok(())
+}
+
+/// Channels on which the events of a client are published.
+#[derive(Clone)]
+struct EventChannels {
+    framebuffer: broadcast::Sender<Arc<VncEvent>>,
+    resolution: broadcast::Sender<(u32, u32)>,
+    current_resolution: watch::Sender<(u32, u32)>,
+    current_pixel_format: watch::Sender<Option<PixelFormat>>,
+    bell: broadcast::Sender<()>,
+    cut_text: broadcast::Sender<String>,
+    errors: broadcast::Sender<String>,
+    reconnect: broadcast::Sender<ReconnectEvent>,
+}
+
+/// Background event pump distributing the events of a `VncClient` to any number of subscribers.
+///
+/// The dispatcher continuously polls the client in a tokio task, requests incremental
+/// framebuffer updates and publishes every received event on a broadcast channel matching its
+/// kind. This allows screenshots, needle matching and input actions to run concurrently on the
+/// same connection.
+///
+/// Once the connection is lost, all channels are closed, unless the dispatcher has been started
+/// with [`EventDispatcher::spawn_reconnecting`].
+pub struct EventDispatcher {
+    client: Arc<RwLock<VncClient>>,
+    channels: Arc<Mutex<Option<EventChannels>>>,
+    current_resolution: watch::Receiver<(u32, u32)>,
+    current_pixel_format: watch::Receiver<Option<PixelFormat>>,
+    task: JoinHandle<()>,
+}
+
+impl EventDispatcher {
+    /// Start dispatching the events of a client.
+    ///
+    /// No other function may consume events of the client afterwards, as events are taken out of
+    /// the client's queue.
+    ///
+    /// # Parameters
+    ///
+    /// * client: `VncClient` - The connected client.
+    ///
+    /// # Returns
+    ///
+    /// * `EventDispatcher` - The running dispatcher.
+    pub fn spawn(client: VncClient) -> EventDispatcher {
+        EventDispatcher::start(client, None)
+    }
+
+    /// Start dispatching the events of a client, reconnecting whenever the connection is lost.
+    ///
+    /// After reconnecting, the client is replaced, a full framebuffer update is requested and a
+    /// [`ReconnectEvent`] is published. The channels are only closed once reconnecting failed.
+    ///
+    /// # Parameters
+    ///
+    /// * client: `VncClient` - The connected client.
+    /// * target: `impl Into<Target>` - The target the client is connected to.
+    /// * config: `&VncConfig` - The configuration the client has been connected with.
+    /// * policy: `RetryPolicy` - How often and how fast to attempt reconnecting.
+    ///
+    /// # Returns
+    ///
+    /// * `EventDispatcher` - The running dispatcher.
+    pub fn spawn_reconnecting(
+        client: VncClient,
+        target: impl Into<Target>,
+        config: &VncConfig,
+        policy: RetryPolicy,
+    ) -> EventDispatcher {
+        let reconnect: Reconnect = Reconnect {
+            target: target.into(),
+            config: config.clone(),
+            policy,
+        };
+        EventDispatcher::start(client, Some(reconnect))
+    }
+
+    fn start(client: VncClient, reconnect: Option<Reconnect>) -> EventDispatcher {
+        info!(target: LOG_TARGET, "Starting event dispatcher...");
+        let (current_tx, current_resolution) = watch::channel((0, 0));
+        let (pixel_format_tx, current_pixel_format) = watch::channel(None);
+        let channels = EventChannels {
+            framebuffer: broadcast::channel(FRAMEBUFFER_CHANNEL_SIZE).0,
+            resolution: broadcast::channel(EVENT_CHANNEL_SIZE).0,
+            current_resolution: current_tx,
+            current_pixel_format: pixel_format_tx,
+            bell: broadcast::channel(EVENT_CHANNEL_SIZE).0,
+            cut_text: broadcast::channel(EVENT_CHANNEL_SIZE).0,
+            errors: broadcast::channel(EVENT_CHANNEL_SIZE).0,
+            reconnect: broadcast::channel(EVENT_CHANNEL_SIZE).0,
+        };
+        let shared: Arc<Mutex<Option<EventChannels>>> =
+            Arc::new(Mutex::new(Some(channels.clone())));
+        let client: Arc<RwLock<VncClient>> = Arc::new(RwLock::new(client));
+        let task: JoinHandle<()> = tokio::spawn(dispatch_events(
+            client.clone(),
+            channels,
+            shared.clone(),
+            reconnect,
+        ));
+
+        EventDispatcher {
+            client,
+            channels: shared,
+            current_resolution,
+            current_pixel_format,
+            task,
+        }
+    }
+
+    /// The client whose events are dispatched.
+    ///
+    /// The client is replaced when reconnecting, so the returned handle should not be kept.
+    pub fn client(&self) -> VncClient {
+        self.client
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Shared handle to the client, which always holds the current client.
+    pub(crate) fn shared_client(&self) -> Arc<RwLock<VncClient>> {
+        self.client.clone()
+    }
+
+    /// The most recent screen resolution announced by the server as `(width, height)`.
+    ///
+    /// `(0, 0)` until the server announced a resolution.
+    pub fn resolution(&self) -> (u32, u32) {
+        *self.current_resolution.borrow()
+    }
+
+    /// The pixel format announced by the server.
+    ///
+    /// The server only announces its native pixel format if the client did not request one, see
+    /// [`VncConfig::with_pixel_format`]. `None` until the server announced a pixel format.
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        *self.current_pixel_format.borrow()
+    }
+
+    /// Check whether the connection is still being dispatched.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Subscribe to framebuffer updates.
+    ///
+    /// Carries `RawImage`, `JpegImage`, `Copy`, `SetCursor` and `SetPixelFormat` events. Changes
+    /// of the resolution are published here as well, so they are received in order with the
+    /// image data.
+    pub fn subscribe_framebuffer(&self) -> broadcast::Receiver<Arc<VncEvent>> {
+        self.subscribe(|c| &c.framebuffer)
+    }
+
+    /// Subscribe to changes of the screen resolution as `(width, height)`.
+    pub fn subscribe_resolution(&self) -> broadcast::Receiver<(u32, u32)> {
+        self.subscribe(|c| &c.resolution)
+    }
+
+    /// Subscribe to bell events of the server.
+    pub fn subscribe_bell(&self) -> broadcast::Receiver<()> {
+        self.subscribe(|c| &c.bell)
+    }
+
+    /// Subscribe to clipboard text sent by the server.
+    pub fn subscribe_cut_text(&self) -> broadcast::Receiver<String> {
+        self.subscribe(|c| &c.cut_text)
+    }
+
+    /// Subscribe to error events of the client.
+    pub fn subscribe_errors(&self) -> broadcast::Receiver<String> {
+        self.subscribe(|c| &c.errors)
+    }
+
+    /// Subscribe to notifications about re-established connections.
+    pub fn subscribe_reconnect(&self) -> broadcast::Receiver<ReconnectEvent> {
+        self.subscribe(|c| &c.reconnect)
+    }
+
+    /// Stop dispatching events.
+    ///
+    /// All channels are closed. The client itself stays connected.
+    pub fn stop(&self) {
+        info!(target: LOG_TARGET, "Stopping event dispatcher...");
+        self.task.abort();
+        self.channels
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+
+    /// Subscribe to one of the channels, or return a closed receiver if dispatching has stopped.
+    fn subscribe<T: Clone>(
+        &self,
+        channel: impl Fn(&EventChannels) -> &broadcast::Sender<T>,
+    ) -> broadcast::Receiver<T> {
+        match &*self.channels.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(channels) => channel(channels).subscribe(),
+            None => broadcast::channel(1).1,
+        }
+    }
+}
+
+impl Drop for EventDispatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Poll events of the client and publish them until the connection closes.
+///
+/// `VncClient::recv_event` holds the client's lock while waiting, which would block all input to
+/// the server. Events are therefore polled.
+async fn dispatch_events(
+    current: Arc<RwLock<VncClient>>,
+    channels: EventChannels,
+    shared: Arc<Mutex<Option<EventChannels>>>,
+    reconnect: Option<Reconnect>,
+) {
+    let mut client: VncClient = current.read().unwrap_or_else(|e| e.into_inner()).clone();
+    let mut last_refresh: Instant = Instant::now();
+
+    loop {
+        if last_refresh.elapsed() >= REFRESH_INTERVAL {
+            if let Err(e) = client.input(X11Event::Refresh).await {
+                warn!(target: LOG_TARGET, "Unable to request screen update: {}", e);
+            }
+            last_refresh = Instant::now();
+        }
+
+        match client.poll_event().await {
+            Ok(Some(event)) => publish_event(&channels, event),
+            Ok(None) => tokio::time::sleep(EVENT_POLL_INTERVAL).await,
+            Err(e) => {
+                let Some(reconnect) = &reconnect else {
+                    info!(target: LOG_TARGET, "Event dispatcher stopped: {}", e);
+                    break;
+                };
+                warn!(target: LOG_TARGET, "Connection lost: {}", e);
+                let lost: Instant = Instant::now();
+                match reconnect.run().await {
+                    Ok((new_client, attempts)) => {
+                        client = new_client;
+                        *current.write().unwrap_or_else(|e| e.into_inner()) = client.clone();
+                        if let Err(e) = client.input(X11Event::FullRefresh).await {
+                            warn!(target: LOG_TARGET, "Unable to request full screen update: {}", e);
+                        }
+                        let event: ReconnectEvent = ReconnectEvent {
+                            attempts,
+                            downtime: lost.elapsed(),
+                        };
+                        info!(target: LOG_TARGET, "Reconnected after {} attempts.", attempts);
+                        let _ = channels.reconnect.send(event);
+                    }
+                    Err(e) => {
+                        error!(target: LOG_TARGET, "Event dispatcher stopped, unable to reconnect: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    // Drop all senders, so subscribers notice the closed connection.
+    shared.lock().unwrap_or_else(|e| e.into_inner()).take();
+}
+
+/// Publish an event on the channel matching its kind.
+///
+/// Sending fails only if nobody is subscribed, which is not an error.
+fn publish_event(channels: &EventChannels, event: VncEvent) {
+    match event {
+        VncEvent::SetResolution(ref screen) => {
+            let resolution: (u32, u32) = (screen.width as u32, screen.height as u32);
+            info!(target: LOG_TARGET, "Screen resolution: {}x{}", resolution.0, resolution.1);
+            channels.current_resolution.send_replace(resolution);
+            let _ = channels.resolution.send(resolution);
+            let _ = channels.framebuffer.send(Arc::new(event));
+        }
+        VncEvent::SetPixelFormat(pixel_format) => {
+            debug!(target: LOG_TARGET, "Pixel format: {:?}", pixel_format);
+            channels
+                .current_pixel_format
+                .send_replace(Some(pixel_format));
+            let _ = channels.framebuffer.send(Arc::new(event));
+        }
+        VncEvent::RawImage(..)
+        | VncEvent::JpegImage(..)
+        | VncEvent::Copy(..)
+        | VncEvent::SetCursor(..) => {
+            let _ = channels.framebuffer.send(Arc::new(event));
+        }
+        VncEvent::Bell => {
+            debug!(target: LOG_TARGET, "Bell received.");
+            let _ = channels.bell.send(());
+        }
+        VncEvent::Text(text) => {
+            debug!(target: LOG_TARGET, "Server cut text received: '{}'", text);
+            let _ = channels.cut_text.send(text);
+        }
+        VncEvent::Error(e) => {
+            error!(target: LOG_TARGET, "Error event received: {}", e);
+            let _ = channels.errors.send(e);
+        }
+        x => {
+            debug!(target: LOG_TARGET, "Event dispatcher ignored event '{:?}'.", x);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Reconnect module
+//!
+//! The VNC server goes away whenever the remote machine reboots or QEMU restarts its VNC server.
+//! A [`RetryPolicy`] describes how often and how fast a lost connection is re-established, see
+//! [`crate::session::Session::connect_with_retry`].
+use std::time::Duration;
+
+use log::{error, info, warn};
+use vnc::VncClient;
+
+use super::config::VncConfig;
+use super::connect;
+use super::transport::Target;
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// Number of connection attempts made by default.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 30;
+
+/// Delay before the first retry by default.
+pub const DEFAULT_INITIAL_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest delay between two attempts by default.
+pub const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// How often and how fast a connection is attempted.
+///
+/// The delay between two attempts starts at the initial delay and is multiplied by the backoff
+/// factor after every failed attempt, up to the maximum delay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+    backoff: f64,
+}
+
+impl RetryPolicy {
+    /// Create the default policy.
+    ///
+    /// It makes up to [`DEFAULT_MAX_ATTEMPTS`] attempts, starting with a delay of
+    /// [`DEFAULT_INITIAL_DELAY`] which doubles up to [`DEFAULT_MAX_DELAY`].
+    pub fn new() -> RetryPolicy {
+        RetryPolicy::default()
+    }
+
+    /// Set the number of connection attempts.
+    ///
+    /// # Parameters
+    ///
+    /// * max_attempts: `u32` - The maximum number of attempts, including the first one.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RetryPolicy)` - The policy.
+    /// * `Err(Error)` - If `max_attempts` is zero.
+    pub fn with_max_attempts(self, max_attempts: u32) -> Result<RetryPolicy, Error> {
+        if max_attempts == 0 {
+            return Err(Error::InvalidInput(
+                "At least one connection attempt is required".to_string(),
+            ));
+        }
+        Ok(RetryPolicy {
+            max_attempts,
+            ..self
+        })
+    }
+
+    /// Set the delays between attempts.
+    ///
+    /// # Parameters
+    ///
+    /// * initial_delay: `Duration` - The delay before the first retry.
+    /// * max_delay: `Duration` - The longest delay between two attempts.
+    /// * backoff: `f64` - The factor the delay is multiplied with after each failed attempt. A
+    ///   factor of `1.0` retries at a constant rate.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RetryPolicy)` - The policy.
+    /// * `Err(Error)` - If the backoff factor is smaller than `1.0` or the initial delay exceeds
+    ///   the maximum delay.
+    pub fn with_delays(
+        self,
+        initial_delay: Duration,
+        max_delay: Duration,
+        backoff: f64,
+    ) -> Result<RetryPolicy, Error> {
+        if !backoff.is_finite() || backoff < 1.0 {
+            return Err(Error::InvalidInput(format!(
+                "Backoff factor must be at least 1.0, got {}",
+                backoff
+            )));
+        }
+        if initial_delay > max_delay {
+            return Err(Error::InvalidInput(format!(
+                "Initial delay {:?} exceeds the maximum delay {:?}",
+                initial_delay, max_delay
+            )));
+        }
+        Ok(RetryPolicy {
+            initial_delay,
+            max_delay,
+            backoff,
+            ..self
+        })
+    }
+
+    /// The maximum number of attempts, including the first one.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// The delay after the given number of failed attempts.
+    ///
+    /// # Parameters
+    ///
+    /// * failed: `u32` - Number of failed attempts so far, at least 1.
+    pub fn delay(&self, failed: u32) -> Duration {
+        let exponent: i32 = failed.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let secs: f64 = self.initial_delay.as_secs_f64() * self.backoff.powi(exponent);
+        if secs < self.max_delay.as_secs_f64() {
+            Duration::from_secs_f64(secs)
+        } else {
+            self.max_delay
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            backoff: 2.0,
+        }
+    }
+}
+
+/// Notification that a lost connection has been re-established.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectEvent {
+    /// Number of connection attempts it took to reconnect.
+    pub attempts: u32,
+    /// Time between noticing the lost connection and reconnecting.
+    pub downtime: Duration,
+}
+
+/// Connect to a VNC server, retrying according to the policy.
+///
+/// Only failures to reach the server and timeouts before the authentication are retried. A rejected
+/// password or an incompatible server will not change by retrying, so these errors are returned
+/// immediately. Cancelling the cancellation token of the `config` stops retrying with an
+/// `Error::Cancelled`.
+///
+/// # Parameters
+///
+/// * target: `impl Into<Target>` - The VNC target server, see [`super::connect`].
+/// * config: `&VncConfig` - The configuration of the connection.
+/// * policy: `&RetryPolicy` - How often and how fast to attempt the connection.
+///
+/// # Returns
+///
+/// * `Ok((VncClient, u32))` - The connected client and the number of attempts it took.
+/// * `Err(Error)` - The error of the last attempt.
+pub async fn connect_with_retry(
+    target: impl Into<Target>,
+    config: &VncConfig,
+    policy: &RetryPolicy,
+) -> Result<(VncClient, u32), Error> {
+    let target: Target = target.into();
+    let mut attempt: u32 = 1;
+    loop {
+        match connect(&target, config).await {
+            Ok(client) => return Ok((client, attempt)),
+            Err(
+                e @ (Error::ConnectionError(_)
+                | Error::ConnectTimeout(_)
+                | Error::HandshakeTimeout(_)
+                | Error::Timeout(_)),
+            ) if attempt < policy.max_attempts => {
+                let delay: Duration = policy.delay(attempt);
+                warn!(target: LOG_TARGET, "Connection attempt {} of {} failed: {}; retrying in {:?}...", attempt, policy.max_attempts, e, delay);
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = config.cancellation_token().cancelled() => {
+                        info!(target: LOG_TARGET, "Connecting to '{}' cancelled.", target);
+                        return Err(Error::Cancelled);
+                    }
+                }
+                attempt += 1;
+            }
+            Err(e) => {
+                error!(target: LOG_TARGET, "Giving up connecting to '{}' after {} attempts.", target, attempt);
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Everything needed to re-establish a lost connection.
+#[derive(Debug, Clone)]
+pub(crate) struct Reconnect {
+    pub(crate) target: Target,
+    pub(crate) config: VncConfig,
+    pub(crate) policy: RetryPolicy,
+}
+
+impl Reconnect {
+    /// Re-establish the connection.
+    pub(crate) async fn run(&self) -> Result<(VncClient, u32), Error> {
+        info!(target: LOG_TARGET, "Connection to '{}' lost; reconnecting...", self.target);
+        connect_with_retry(&self.target, &self.config, &self.policy).await
+    }
+}
@@ -0,0 +1,712 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # TLS module
+//!
+//! VNC servers of BMCs and hypervisors often only accept connections secured with the VeNCrypt
+//! security type, which wraps the connection in TLS, either authenticated with X509 certificates
+//! or anonymously. Configure it with [`TlsConfig`] and
+//! [`crate::connection::config::VncConfig::with_tls`].
+//!
+//! The TLS handshake itself is performed by a [`TlsConnector`], which verifies the server
+//! certificate according to the requested [`CertificateVerification`]. With the `tls` feature,
+//! enabled by default, the crate ships the [`RustlsConnector`] based on `rustls`. Other TLS
+//! libraries can be used by implementing [`TlsConnector`].
+//!
+//! The security negotiation is done here, after which the remaining protocol is spoken over the
+//! TLS stream. The subtypes `X509Vnc`, `X509Plain`, `X509None` and, if allowed, their anonymous
+//! `TLS*` counterparts are supported.
+use std::fmt;
+use std::future::Future;
+use std::io;
+#[cfg(feature = "tls")]
+use std::net::{IpAddr, Ipv4Addr};
+#[cfg(feature = "tls")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+#[cfg(feature = "tls")]
+use log::warn;
+use log::{debug, error, info};
+#[cfg(feature = "tls")]
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+#[cfg(feature = "tls")]
+use rustls::crypto::CryptoProvider;
+#[cfg(feature = "tls")]
+use rustls::pki_types::pem::PemObject;
+#[cfg(feature = "tls")]
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+#[cfg(feature = "tls")]
+use rustls::{DigitallySignedStruct, RootCertStore, SignatureScheme};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use vnc::VncError;
+
+use super::config::PasswordSource;
+use super::transport::VncStream;
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// The only protocol version spoken with VeNCrypt.
+const RFB_VERSION: &[u8; 12] = b"RFB 003.008\n";
+
+/// Security type of VeNCrypt.
+const SECURITY_VENCRYPT: u8 = 19;
+
+/// Security type without authentication.
+const SECURITY_NONE: u8 = 1;
+
+/// Security type of the VNC password authentication.
+const SECURITY_VNC_AUTH: u8 = 2;
+
+/// Longest failure reason accepted from the server, which is read before authentication.
+const MAX_REASON_LEN: u32 = 64 * 1024;
+
+/// The VeNCrypt version, 0.2.
+const VENCRYPT_VERSION: [u8; 2] = [0, 2];
+
+/// A VeNCrypt subtype, deciding how the connection is secured and authenticated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subtype {
+    /// Anonymous TLS without authentication.
+    TlsNone = 257,
+    /// Anonymous TLS with VNC password authentication.
+    TlsVnc = 258,
+    /// Anonymous TLS with username and password authentication.
+    TlsPlain = 259,
+    /// X509 TLS without authentication.
+    X509None = 260,
+    /// X509 TLS with VNC password authentication.
+    X509Vnc = 261,
+    /// X509 TLS with username and password authentication.
+    X509Plain = 262,
+}
+
+impl Subtype {
+    /// Subtypes in order of preference: authenticated servers first, then authenticated users.
+    const PREFERENCE: [Subtype; 6] = [
+        Subtype::X509Vnc,
+        Subtype::X509Plain,
+        Subtype::X509None,
+        Subtype::TlsVnc,
+        Subtype::TlsPlain,
+        Subtype::TlsNone,
+    ];
+
+    /// Whether the subtype uses anonymous TLS, which does not authenticate the server.
+    pub fn is_anonymous(self) -> bool {
+        matches!(self, Subtype::TlsNone | Subtype::TlsVnc | Subtype::TlsPlain)
+    }
+
+    /// Whether the subtype authenticates with a username and password.
+    fn is_plain(self) -> bool {
+        matches!(self, Subtype::TlsPlain | Subtype::X509Plain)
+    }
+
+    /// Whether the subtype authenticates with a VNC password.
+    fn is_vnc_auth(self) -> bool {
+        matches!(self, Subtype::TlsVnc | Subtype::X509Vnc)
+    }
+}
+
+/// How the certificate of the server is verified.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CertificateVerification {
+    /// Verify the certificate against the root certificates of the system.
+    #[default]
+    SystemRoots,
+    /// Verify the certificate against the CA certificates in the given PEM file.
+    CaFile(PathBuf),
+    /// Accept any certificate. Only use this in trusted networks.
+    Insecure,
+}
+
+/// Parameters of a TLS handshake, passed to the [`TlsConnector`].
+#[derive(Debug, Clone, Copy)]
+pub struct TlsHandshake<'a> {
+    /// The name the server certificate is verified against.
+    pub server_name: Option<&'a str>,
+    /// Whether anonymous TLS is used, which means there is no certificate to verify.
+    pub anonymous: bool,
+    /// How the server certificate is verified.
+    pub verification: &'a CertificateVerification,
+}
+
+/// Performs the TLS handshake with the VNC server.
+///
+/// Implement this for the TLS library of your choice. The connector must verify the server
+/// certificate as requested by the [`TlsHandshake`], and fail the handshake otherwise.
+pub trait TlsConnector: Send + Sync {
+    /// Whether the connector supports anonymous TLS. Anonymous subtypes are only chosen if it does.
+    fn supports_anonymous(&self) -> bool {
+        true
+    }
+
+    /// Perform the TLS handshake on the stream.
+    ///
+    /// # Parameters
+    ///
+    /// * handshake: `TlsHandshake` - The parameters of the handshake.
+    /// * stream: `Box<dyn VncStream>` - The stream connected to the VNC server.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Box<dyn VncStream>)` - The encrypted stream.
+    /// * `Err(io::Error)` - If the handshake or the certificate verification fails.
+    #[allow(clippy::type_complexity)]
+    fn connect<'a>(
+        &'a self,
+        handshake: TlsHandshake<'a>,
+        stream: Box<dyn VncStream>,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Box<dyn VncStream>>> + Send + 'a>>;
+}
+
+/// Configuration of TLS secured connections.
+///
+/// # Example
+///
+/// ```no_run
+/// # use isototest::connection::config::VncConfig;
+/// # use isototest::connection::tls::{CertificateVerification, TlsConfig};
+/// let config = VncConfig::new().with_tls(
+///     TlsConfig::default()
+///         .with_server_name("bmc.example.com")
+///         .with_verification(CertificateVerification::CaFile("/etc/pki/bmc-ca.pem".into())),
+/// );
+/// ```
+#[derive(Clone)]
+pub struct TlsConfig {
+    connector: Arc<dyn TlsConnector>,
+    server_name: Option<String>,
+    verification: CertificateVerification,
+    username: Option<String>,
+    anonymous: bool,
+}
+
+impl TlsConfig {
+    /// Create a configuration verifying the server certificate against the system roots.
+    ///
+    /// Anonymous TLS is not allowed and username authentication is not used.
+    ///
+    /// # Parameters
+    ///
+    /// * connector: `Arc<dyn TlsConnector>` - Performs the TLS handshake.
+    pub fn new(connector: Arc<dyn TlsConnector>) -> TlsConfig {
+        TlsConfig {
+            connector,
+            server_name: None,
+            verification: CertificateVerification::default(),
+            username: None,
+            anonymous: false,
+        }
+    }
+
+    /// Set the name the server certificate is verified against.
+    ///
+    /// By default the host of the target is used.
+    ///
+    /// # Parameters
+    ///
+    /// * name: `&str` - The DNS name or IP address of the server.
+    pub fn with_server_name(self, name: &str) -> TlsConfig {
+        TlsConfig {
+            server_name: Some(name.to_string()),
+            ..self
+        }
+    }
+
+    /// Set how the server certificate is verified.
+    ///
+    /// # Parameters
+    ///
+    /// * verification: `CertificateVerification` - The verification of the certificate.
+    pub fn with_verification(self, verification: CertificateVerification) -> TlsConfig {
+        TlsConfig {
+            verification,
+            ..self
+        }
+    }
+
+    /// Set the username for servers authenticating with username and password.
+    ///
+    /// The password is taken from [`crate::connection::config::VncConfig::with_password`].
+    ///
+    /// # Parameters
+    ///
+    /// * username: `&str` - The username.
+    pub fn with_username(self, username: &str) -> TlsConfig {
+        TlsConfig {
+            username: Some(username.to_string()),
+            ..self
+        }
+    }
+
+    /// Allow anonymous TLS, which encrypts the connection without authenticating the server.
+    ///
+    /// Anonymous TLS is only used if the connector supports it, which the [`RustlsConnector`]
+    /// does not.
+    ///
+    /// # Parameters
+    ///
+    /// * anonymous: `bool` - Whether anonymous TLS may be used.
+    pub fn with_anonymous(self, anonymous: bool) -> TlsConfig {
+        TlsConfig { anonymous, ..self }
+    }
+
+    /// The name the server certificate is verified against, if set.
+    pub fn server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// How the server certificate is verified.
+    pub fn verification(&self) -> &CertificateVerification {
+        &self.verification
+    }
+
+    /// The username for username and password authentication, if set.
+    pub fn username(&self) -> Option<&str> {
+        self.username.as_deref()
+    }
+
+    /// Whether anonymous TLS may be used.
+    pub fn anonymous(&self) -> bool {
+        self.anonymous
+    }
+
+    /// Choose the preferred subtype among the ones offered by the server.
+    fn choose(&self, offered: &[u32]) -> Option<Subtype> {
+        Subtype::PREFERENCE.into_iter().find(|subtype| {
+            offered.contains(&(*subtype as u32))
+                && ((self.anonymous && self.connector.supports_anonymous())
+                    || !subtype.is_anonymous())
+                && (self.username.is_some() || !subtype.is_plain())
+        })
+    }
+}
+
+#[cfg(feature = "tls")]
+impl Default for TlsConfig {
+    /// Create a configuration using the [`RustlsConnector`], see [`TlsConfig::new`].
+    fn default() -> TlsConfig {
+        TlsConfig::new(Arc::new(RustlsConnector))
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig")
+            .field("server_name", &self.server_name)
+            .field("verification", &self.verification)
+            .field("username", &self.username)
+            .field("anonymous", &self.anonymous)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`TlsConnector`] based on `rustls`.
+///
+/// The server certificate is verified against the root certificates of the system or the CA
+/// certificates of a PEM file. Verification is only skipped for
+/// [`CertificateVerification::Insecure`]. `rustls` does not implement the cipher suites of
+/// anonymous TLS, so servers only offering anonymous subtypes cannot be connected to.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RustlsConnector;
+
+#[cfg(feature = "tls")]
+impl RustlsConnector {
+    /// Build the client configuration verifying certificates as requested.
+    fn client_config(
+        &self,
+        verification: &CertificateVerification,
+    ) -> io::Result<rustls::ClientConfig> {
+        let provider: Arc<CryptoProvider> = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = rustls::ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?;
+        let config: rustls::ClientConfig = match verification {
+            CertificateVerification::SystemRoots => builder
+                .with_root_certificates(system_roots()?)
+                .with_no_client_auth(),
+            CertificateVerification::CaFile(path) => builder
+                .with_root_certificates(ca_file_roots(path)?)
+                .with_no_client_auth(),
+            CertificateVerification::Insecure => {
+                warn!(target: LOG_TARGET, "The certificate of the server is not verified.");
+                builder
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+                    .with_no_client_auth()
+            }
+        };
+        Ok(config)
+    }
+}
+
+#[cfg(feature = "tls")]
+impl TlsConnector for RustlsConnector {
+    fn supports_anonymous(&self) -> bool {
+        false
+    }
+
+    fn connect<'a>(
+        &'a self,
+        handshake: TlsHandshake<'a>,
+        stream: Box<dyn VncStream>,
+    ) -> Pin<Box<dyn Future<Output = io::Result<Box<dyn VncStream>>> + Send + 'a>> {
+        Box::pin(async move {
+            if handshake.anonymous {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "Anonymous TLS is not supported by rustls",
+                ));
+            }
+            let config: rustls::ClientConfig = self.client_config(handshake.verification)?;
+            let server_name: ServerName<'static> = match handshake.server_name {
+                Some(name) => ServerName::try_from(name.to_string()).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid server name '{}': {}", name, e),
+                    )
+                })?,
+                // Targets like Unix sockets have no host. An IP address also keeps the name from
+                // being sent to the server.
+                None if *handshake.verification == CertificateVerification::Insecure => {
+                    ServerName::from(IpAddr::from(Ipv4Addr::UNSPECIFIED))
+                }
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "No server name to verify the certificate against",
+                    ))
+                }
+            };
+            let stream = tokio_rustls::TlsConnector::from(Arc::new(config))
+                .connect(server_name, stream)
+                .await?;
+            Ok(Box::new(stream) as Box<dyn VncStream>)
+        })
+    }
+}
+
+/// Load the root certificates of the system.
+#[cfg(feature = "tls")]
+fn system_roots() -> io::Result<RootCertStore> {
+    let certs = rustls_native_certs::load_native_certs();
+    for e in certs.errors {
+        warn!(target: LOG_TARGET, "Could not load system root certificates: {}", e);
+    }
+    let mut roots: RootCertStore = RootCertStore::empty();
+    let (added, ignored) = roots.add_parsable_certificates(certs.certs);
+    debug!(target: LOG_TARGET, "Loaded {} system root certificates, ignored {}.", added, ignored);
+    if roots.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "No system root certificates found",
+        ));
+    }
+    Ok(roots)
+}
+
+/// Load the CA certificates of a PEM file.
+#[cfg(feature = "tls")]
+fn ca_file_roots(path: &Path) -> io::Result<RootCertStore> {
+    let invalid = |e: &dyn fmt::Display| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid CA file '{}': {}", path.display(), e),
+        )
+    };
+    let mut roots: RootCertStore = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(path).map_err(|e| invalid(&e))? {
+        roots
+            .add(cert.map_err(|e| invalid(&e))?)
+            .map_err(|e| invalid(&e))?;
+    }
+    if roots.is_empty() {
+        return Err(invalid(&"no certificates found"));
+    }
+    Ok(roots)
+}
+
+/// Certificate verifier accepting any certificate, used for [`CertificateVerification::Insecure`].
+///
+/// The signatures of the handshake are still checked, so the server must own the key of the
+/// certificate it presents.
+#[cfg(feature = "tls")]
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+#[cfg(feature = "tls")]
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Negotiate VeNCrypt and secure the stream with TLS.
+///
+/// # Parameters
+///
+/// * stream: `impl VncStream` - The stream connected to the VNC server.
+/// * config: `&TlsConfig` - The TLS configuration.
+/// * password: `&PasswordSource` - The password for username and password authentication.
+/// * host: `Option<&str>` - The host of the target, used if no server name is configured.
+///
+/// # Returns
+///
+/// * `Ok(Prelude<Box<dyn VncStream>>)` - The TLS stream, prepared for the client to continue the
+///   handshake on.
+/// * `Err(Error)` - An `Error::ProtocolError` if the server does not offer a usable subtype, an
+///   `Error::TlsError` if the TLS handshake fails, or an `Error::AuthenticationError` if the
+///   credentials are rejected.
+pub(crate) async fn negotiate(
+    mut stream: impl VncStream,
+    config: &TlsConfig,
+    password: &PasswordSource,
+    host: Option<&str>,
+) -> Result<Prelude<Box<dyn VncStream>>, Error> {
+    let mut version = [0; 12];
+    stream.read_exact(&mut version).await.map_err(io_error)?;
+    if version.as_slice() < RFB_VERSION.as_slice() {
+        return Err(Error::ProtocolError(format!(
+            "VeNCrypt requires RFB 3.8, server speaks '{}'",
+            String::from_utf8_lossy(&version).trim_end()
+        )));
+    }
+    stream.write_all(RFB_VERSION).await.map_err(io_error)?;
+
+    let count: u8 = stream.read_u8().await.map_err(io_error)?;
+    if count == 0 {
+        let reason: String = read_reason(&mut stream).await?;
+        return Err(Error::ConnectionError(reason));
+    }
+    let mut types: Vec<u8> = vec![0; count as usize];
+    stream.read_exact(&mut types).await.map_err(io_error)?;
+    if !types.contains(&SECURITY_VENCRYPT) {
+        error!(target: LOG_TARGET, "Server offers security types {:?}, but not VeNCrypt.", types);
+        return Err(Error::ProtocolError(format!(
+            "Server does not support VeNCrypt, offered security types: {:?}",
+            types
+        )));
+    }
+    stream.write_u8(SECURITY_VENCRYPT).await.map_err(io_error)?;
+
+    let mut server_version = [0; 2];
+    stream
+        .read_exact(&mut server_version)
+        .await
+        .map_err(io_error)?;
+    if server_version < VENCRYPT_VERSION {
+        return Err(Error::ProtocolError(format!(
+            "Unsupported VeNCrypt version {}.{}",
+            server_version[0], server_version[1]
+        )));
+    }
+    stream
+        .write_all(&VENCRYPT_VERSION)
+        .await
+        .map_err(io_error)?;
+    if stream.read_u8().await.map_err(io_error)? != 0 {
+        return Err(Error::ProtocolError(
+            "Server rejected VeNCrypt version 0.2".to_string(),
+        ));
+    }
+
+    let count: u8 = stream.read_u8().await.map_err(io_error)?;
+    let mut offered: Vec<u32> = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        offered.push(stream.read_u32().await.map_err(io_error)?);
+    }
+    let Some(subtype) = config.choose(&offered) else {
+        error!(target: LOG_TARGET, "No usable VeNCrypt subtype among {:?}.", offered);
+        return Err(Error::ProtocolError(format!(
+            "No supported VeNCrypt subtype offered: {:?}",
+            offered
+        )));
+    };
+    debug!(target: LOG_TARGET, "Using VeNCrypt subtype {:?}.", subtype);
+    stream.write_u32(subtype as u32).await.map_err(io_error)?;
+    if stream.read_u8().await.map_err(io_error)? != 1 {
+        return Err(Error::ProtocolError(format!(
+            "Server rejected VeNCrypt subtype {:?}",
+            subtype
+        )));
+    }
+
+    let handshake: TlsHandshake = TlsHandshake {
+        server_name: config.server_name().or(host),
+        anonymous: subtype.is_anonymous(),
+        verification: config.verification(),
+    };
+    let mut stream: Box<dyn VncStream> = config
+        .connector
+        .connect(handshake, Box::new(stream))
+        .await
+        .map_err(|e| {
+            error!(target: LOG_TARGET, "TLS handshake failed: {}", e);
+            Error::TlsError(e.to_string())
+        })?;
+    info!(target: LOG_TARGET, "TLS established with VeNCrypt subtype {:?}.", subtype);
+
+    // The client continues the handshake as if the server offered only the security type of the
+    // subtype. VNC authentication is left to the client, the others are completed here.
+    if subtype.is_vnc_auth() {
+        return Ok(Prelude::new(stream, &[1, SECURITY_VNC_AUTH]));
+    }
+    if subtype.is_plain() {
+        let username: &str = config.username().unwrap_or_default();
+        let password: String = password.resolve()?;
+        stream
+            .write_u32(username.len() as u32)
+            .await
+            .map_err(io_error)?;
+        stream
+            .write_u32(password.len() as u32)
+            .await
+            .map_err(io_error)?;
+        stream
+            .write_all(username.as_bytes())
+            .await
+            .map_err(io_error)?;
+        stream
+            .write_all(password.as_bytes())
+            .await
+            .map_err(io_error)?;
+    }
+    if stream.read_u32().await.map_err(io_error)? != 0 {
+        let reason: String = read_reason(&mut stream).await?;
+        error!(target: LOG_TARGET, "Authentication failed: {}", reason);
+        return Err(Error::AuthenticationError(reason));
+    }
+    Ok(Prelude::new(stream, &[1, SECURITY_NONE, 0, 0, 0, 0]))
+}
+
+/// Read the reason of a failure sent by the server.
+///
+/// Reasons longer than [`MAX_REASON_LEN`] are rejected with an `Error::ProtocolError`.
+async fn read_reason(stream: &mut (impl AsyncRead + Unpin)) -> Result<String, Error> {
+    let len: u32 = stream.read_u32().await.map_err(io_error)?;
+    if len > MAX_REASON_LEN {
+        error!(target: LOG_TARGET, "Server sent a failure reason of {} bytes.", len);
+        return Err(Error::ProtocolError(format!(
+            "Failure reason of {} bytes exceeds the limit of {} bytes",
+            len, MAX_REASON_LEN
+        )));
+    }
+    let mut reason: Vec<u8> = vec![0; len as usize];
+    stream.read_exact(&mut reason).await.map_err(io_error)?;
+    Ok(String::from_utf8_lossy(&reason).into_owned())
+}
+
+/// Convert an I/O error during the negotiation.
+fn io_error(e: io::Error) -> Error {
+    Error::from(VncError::IoError(e))
+}
+
+/// A stream replaying the start of the handshake to the client.
+///
+/// The negotiation has already been done on the stream, so the client is shown the protocol
+/// version and the security type to continue with, and its answers to them are dropped.
+pub(crate) struct Prelude<S> {
+    inner: S,
+    replay: Vec<u8>,
+    replayed: usize,
+    swallow: usize,
+}
+
+impl<S> Prelude<S> {
+    /// Replay the protocol version followed by `security`.
+    fn new(inner: S, security: &[u8]) -> Prelude<S> {
+        let mut replay: Vec<u8> = RFB_VERSION.to_vec();
+        replay.extend_from_slice(security);
+        Prelude {
+            inner,
+            replay,
+            replayed: 0,
+            // The protocol version and the chosen security type.
+            swallow: RFB_VERSION.len() + 1,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Prelude<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this: &mut Prelude<S> = &mut self;
+        if this.replayed < this.replay.len() {
+            let n: usize = buf.remaining().min(this.replay.len() - this.replayed);
+            buf.put_slice(&this.replay[this.replayed..this.replayed + n]);
+            this.replayed += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut this.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Prelude<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this: &mut Prelude<S> = &mut self;
+        if this.swallow > 0 {
+            let n: usize = buf.len().min(this.swallow);
+            this.swallow -= n;
+            return Poll::Ready(Ok(n));
+        }
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -0,0 +1,125 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+//! # Transport module
+//!
+//! The VNC protocol can be spoken over any reliable byte stream. A [`Target`] names a server which
+//! can be dialed, either over TCP or over a Unix domain socket, like the one QEMU opens with
+//! `-vnc unix:/path/to/socket`. Streams which have already been established elsewhere, e.g. an SSH
+//! tunnel or a pipe of a test harness, are passed to [`super::connect_stream`] instead.
+use std::fmt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use log::error;
+use tokio::io::{AsyncRead, AsyncWrite};
+use vnc::VncError;
+
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// Prefix marking a target as the path of a Unix domain socket.
+pub const UNIX_PREFIX: &str = "unix:";
+
+/// A byte stream the VNC protocol can be spoken over.
+///
+/// Implemented for every type fulfilling the bounds, e.g. `TcpStream`, `UnixStream` or
+/// `tokio::io::DuplexStream`.
+pub trait VncStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static {}
+
+impl<S> VncStream for S where S: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static {}
+
+/// A VNC server which can be connected to.
+///
+/// Targets are usually created from a string, which is parsed with [`Target::parse`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// The IP and port of the server. (e.g `172.0.0.1:5900`)
+    Tcp(String),
+    /// The path of a Unix domain socket the server listens on.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+impl Target {
+    /// Parse a target.
+    ///
+    /// Strings starting with `unix:` name a Unix domain socket, everything else is an IP and port.
+    ///
+    /// # Parameters
+    ///
+    /// * target: `&str` - The target, e.g. `172.0.0.1:5900` or `unix:/run/qemu/vnc.sock`.
+    ///
+    /// # Returns
+    ///
+    /// * `Target` - The parsed target.
+    pub fn parse(target: &str) -> Target {
+        #[cfg(unix)]
+        if let Some(path) = target.strip_prefix(UNIX_PREFIX) {
+            return Target::Unix(PathBuf::from(path));
+        }
+        Target::Tcp(target.to_string())
+    }
+}
+
+/// Wait for a connection to be established.
+///
+/// # Parameters
+///
+/// * timeout: `Duration` - How long to wait for the connection.
+/// * connect: `impl Future` - Establishes the connection.
+///
+/// # Returns
+///
+/// * `Ok(S)` - The connected stream.
+/// * `Err(Error)` - An `Error::ConnectionError` if the target cannot be reached, or an
+///   `Error::ConnectTimeout` if it does not answer in time.
+pub(crate) async fn dial<S>(
+    timeout: Duration,
+    connect: impl std::future::Future<Output = std::io::Result<S>>,
+) -> Result<S, Error> {
+    match tokio::time::timeout(timeout, connect).await {
+        Ok(Ok(stream)) => Ok(stream),
+        Ok(Err(e)) => {
+            error!(target: LOG_TARGET, "Failed to connect: {}", e);
+            Err(Error::from(VncError::IoError(e)))
+        }
+        Err(_) => {
+            error!(target: LOG_TARGET, "Failed to connect within {:?}.", timeout);
+            Err(Error::ConnectTimeout(timeout))
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Tcp(addr) => write!(f, "{}", addr),
+            #[cfg(unix)]
+            Target::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+impl From<&str> for Target {
+    fn from(target: &str) -> Self {
+        Target::parse(target)
+    }
+}
+
+impl From<String> for Target {
+    fn from(target: String) -> Self {
+        Target::parse(&target)
+    }
+}
+
+impl From<&String> for Target {
+    fn from(target: &String) -> Self {
+        Target::parse(target)
+    }
+}
+
+impl From<&Target> for Target {
+    fn from(target: &Target) -> Self {
+        target.clone()
+    }
+}
@@ -1,3 +1,170 @@
 //! This module defines custom error types to be returned by `isototest`.
 //! These types are thematically split into submodules.
+//!
+//! Public functions return the crate-level [`Error`], which tells apart the causes of a failure.
+//! Errors of the `vnc-rs` client and of the submodules convert into it, so they can be propagated
+//! with `?`.
+pub mod needle_errors;
 pub mod util_errors;
+pub mod view_errors;
+
+use std::fmt;
+use std::time::Duration;
+
+use vnc::VncError;
+
+use needle_errors::{NeedleError, ScreenMismatch};
+#[cfg(feature = "default-logging")]
+use util_errors::LoggingError;
+use view_errors::{ColorMismatch, ViewError};
+
+#[derive(Debug)]
+pub enum Error {
+    /// The connection to the VNC server could not be established or has been lost.
+    ConnectionError(String),
+    /// The TCP connection to the VNC server was not established in time, e.g. because the host
+    /// is down.
+    ConnectTimeout(Duration),
+    /// The VNC server accepted the connection, but did not complete the protocol handshake in
+    /// time.
+    HandshakeTimeout(Duration),
+    /// The TLS handshake with the VNC server failed, e.g. because its certificate is not trusted.
+    TlsError(String),
+    /// The VNC server rejected the credentials or requires a password which was not given.
+    AuthenticationError(String),
+    /// The VNC server did not complete the authentication in time.
+    AuthenticationTimeout(Duration),
+    /// The operation was cancelled through its cancellation token.
+    Cancelled,
+    /// The VNC server sent unexpected or malformed data.
+    ProtocolError(String),
+    /// A character or key specification cannot be typed on the keyboard layout.
+    KeyboardMappingError(String),
+    /// The screen could not be captured or stored.
+    ScreenCaptureError(ViewError),
+    /// The remote machine did not react within the given time.
+    Timeout(String),
+    /// A needle could not be loaded.
+    NeedleError(NeedleError),
+    /// No needle matched the screen before the deadline.
+    NeedleMismatch(Box<ScreenMismatch>),
+    /// The colours of a region did not meet the expectation of a colour assertion.
+    ColorMismatch(Box<ColorMismatch>),
+    /// A parameter is out of its valid range, e.g. a position outside of the screen.
+    InvalidInput(String),
+    /// The default logging configuration could not be set up.
+    #[cfg(feature = "default-logging")]
+    LoggingError(LoggingError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ConnectionError(msg) => {
+                write!(f, "[error] Connection to the VNC server failed: '{}'", msg)
+            }
+            Error::ConnectTimeout(timeout) => {
+                write!(f, "[error] VNC server not reachable within {:?}", timeout)
+            }
+            Error::HandshakeTimeout(timeout) => {
+                write!(
+                    f,
+                    "[error] VNC handshake not completed within {:?}",
+                    timeout
+                )
+            }
+            Error::TlsError(msg) => {
+                write!(f, "[error] TLS handshake failed: '{}'", msg)
+            }
+            Error::AuthenticationError(msg) => {
+                write!(f, "[error] Authentication failed: '{}'", msg)
+            }
+            Error::AuthenticationTimeout(timeout) => {
+                write!(
+                    f,
+                    "[error] Authentication not completed within {:?}",
+                    timeout
+                )
+            }
+            Error::Cancelled => write!(f, "[error] Operation cancelled"),
+            Error::ProtocolError(msg) => {
+                write!(f, "[error] VNC protocol error: '{}'", msg)
+            }
+            Error::KeyboardMappingError(msg) => {
+                write!(f, "[error] Unable to map keys: '{}'", msg)
+            }
+            // The view errors describe the failed capture themselves.
+            Error::ScreenCaptureError(e) => e.fmt(f),
+            Error::Timeout(msg) => {
+                write!(f, "[error] Timeout: '{}'", msg)
+            }
+            Error::NeedleError(e) => e.fmt(f),
+            Error::NeedleMismatch(mismatch) => match &mismatch.closest {
+                Some(closest) => write!(
+                    f,
+                    "[error] No needle matched tags {:?}. Closest candidate: '{}' ({:.2}%)",
+                    mismatch.tags, closest.needle, closest.similarity
+                ),
+                None => write!(f, "[error] No needle found for tags {:?}", mismatch.tags),
+            },
+            Error::ColorMismatch(mismatch) => mismatch.fmt(f),
+            Error::InvalidInput(msg) => {
+                write!(f, "[error] Invalid input: '{}'", msg)
+            }
+            #[cfg(feature = "default-logging")]
+            Error::LoggingError(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ScreenCaptureError(e) => Some(e),
+            Error::NeedleError(e) => Some(e),
+            #[cfg(feature = "default-logging")]
+            Error::LoggingError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<VncError> for Error {
+    fn from(e: VncError) -> Self {
+        match e {
+            VncError::NoPassword | VncError::WrongPassword => {
+                Error::AuthenticationError(e.to_string())
+            }
+            VncError::IoError(_) | VncError::ConnectError | VncError::ClientNotRunning => {
+                Error::ConnectionError(e.to_string())
+            }
+            VncError::InvalidImageData => Error::ScreenCaptureError(ViewError::from(e)),
+            VncError::General(msg) => Error::ProtocolError(msg),
+            e => Error::ProtocolError(e.to_string()),
+        }
+    }
+}
+
+impl From<ViewError> for Error {
+    fn from(e: ViewError) -> Self {
+        match e {
+            ViewError::ProtocolError(e) => Error::from(e),
+            // A failed assertion is not a failure to capture the screen.
+            ViewError::ColorMismatch(mismatch) => Error::ColorMismatch(mismatch),
+            e => Error::ScreenCaptureError(e),
+        }
+    }
+}
+
+impl From<NeedleError> for Error {
+    fn from(e: NeedleError) -> Self {
+        Error::NeedleError(e)
+    }
+}
+
+#[cfg(feature = "default-logging")]
+impl From<LoggingError> for Error {
+    fn from(e: LoggingError) -> Self {
+        Error::LoggingError(e)
+    }
+}
@@ -0,0 +1,54 @@
+//! This module defines and implements error types which refer to loading and evaluating needles.
+//!
+//! A needle is a reference screenshot together with a JSON description of the screen areas that
+//! must match, as used by [openQA](https://open.qa/docs/#_needles).
+use std::fmt;
+
+use image::RgbaImage;
+
+use crate::action::view::needle::NeedleMatch;
+
+#[derive(Debug)]
+pub enum NeedleError {
+    /// The needle JSON or image could not be read from disk.
+    IoError(String),
+    /// The needle JSON is malformed or does not follow the openQA needle format.
+    ParseError(String),
+    /// The needle image could not be decoded.
+    ImageError(String),
+    /// An area of the needle lies outside of the needle image.
+    InvalidArea(String),
+}
+
+impl fmt::Display for NeedleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NeedleError::IoError(msg) => {
+                write!(f, "[error] Unable to read needle: '{}'", msg)
+            }
+            NeedleError::ParseError(msg) => {
+                write!(f, "[error] Invalid needle definition: '{}'", msg)
+            }
+            NeedleError::ImageError(msg) => {
+                write!(f, "[error] Unable to decode needle image: '{}'", msg)
+            }
+            NeedleError::InvalidArea(msg) => {
+                write!(f, "[error] Invalid needle area: '{}'", msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NeedleError {}
+
+/// Details of a failed screen assertion.
+#[derive(Debug)]
+pub struct ScreenMismatch {
+    /// The tags which were looked for.
+    pub tags: Vec<String>,
+    /// The last screenshot taken before the deadline passed.
+    pub last_frame: RgbaImage,
+    /// The needle which came closest to matching the last screenshot, if any needle carried
+    /// one of the tags.
+    pub closest: Option<NeedleMatch>,
+}
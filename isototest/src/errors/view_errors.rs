@@ -0,0 +1,110 @@
+//! This module defines and implements error types which refer to capturing and storing the screen
+//! of the remote machine.
+use std::fmt;
+use std::time::Duration;
+
+use vnc::VncError;
+
+use crate::action::view::compare::Region;
+use crate::action::view::inspect::ColorShare;
+
+#[derive(Debug)]
+pub enum ViewError {
+    /// A screenshot could not be written to or read from disk.
+    IoError(String),
+    /// Image data could not be decoded or encoded.
+    ImageError(String),
+    /// The VNC client failed or the server sent data which does not fit the screen.
+    ProtocolError(VncError),
+    /// The server did not send the expected data in time.
+    Timeout(Duration),
+    /// A region is empty or does not lie within the screen resolution `(width, height)`.
+    OutOfBounds(Region, (u32, u32)),
+    /// The colours of a region do not meet the expectation.
+    ColorMismatch(Box<ColorMismatch>),
+}
+
+/// Details of a failed colour assertion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorMismatch {
+    /// The region whose colours were evaluated.
+    pub region: Region,
+    /// The expectation which was not met.
+    pub expected: ColorShare,
+    /// Share of the region's pixels matching the expected colour in percent.
+    pub share: f64,
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewError::IoError(msg) => {
+                write!(f, "[error] Unable to access screenshot: '{}'", msg)
+            }
+            ViewError::ImageError(msg) => {
+                write!(f, "[error] Invalid image data: '{}'", msg)
+            }
+            ViewError::ProtocolError(e) => {
+                write!(f, "[error] VNC protocol error: '{}'", e)
+            }
+            ViewError::Timeout(timeout) => {
+                write!(f, "[error] No screen data received within {:?}", timeout)
+            }
+            ViewError::OutOfBounds(region, (width, height)) => {
+                write!(
+                    f,
+                    "[error] Region {}x{}+{}+{} is not within the screen resolution {}x{}",
+                    region.width, region.height, region.x, region.y, width, height
+                )
+            }
+            ViewError::ColorMismatch(mismatch) => mismatch.fmt(f),
+        }
+    }
+}
+
+impl fmt::Display for ColorMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [r, g, b, _] = self.expected.color.0;
+        write!(
+            f,
+            "[error] {:.2}% of region {}x{}+{}+{} match colour #{:02x}{:02x}{:02x} (±{}), expected {}% to {}%",
+            self.share,
+            self.region.width,
+            self.region.height,
+            self.region.x,
+            self.region.y,
+            r,
+            g,
+            b,
+            self.expected.tolerance,
+            self.expected.min,
+            self.expected.max
+        )
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+impl From<VncError> for ViewError {
+    fn from(e: VncError) -> Self {
+        match e {
+            VncError::InvalidImageData => ViewError::ImageError(e.to_string()),
+            e => ViewError::ProtocolError(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for ViewError {
+    fn from(e: std::io::Error) -> Self {
+        ViewError::IoError(e.to_string())
+    }
+}
+
+impl From<image::ImageError> for ViewError {
+    fn from(e: image::ImageError) -> Self {
+        match e {
+            image::ImageError::IoError(e) => ViewError::IoError(e.to_string()),
+            e => ViewError::ImageError(e.to_string()),
+        }
+    }
+}
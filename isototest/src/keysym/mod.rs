@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+
+//! # Keysym module
+//!
+//! X11 keysyms are the key identifiers sent to the VNC server in key events, as described in the
+//! [RFC](https://www.rfc-editor.org/rfc/rfc6143.html#section-7.5.4).
+//!
+//! This module contains all keysyms defined in X11's `keysymdef.h` under their X11 names, e.g.
+//! [`XK_Return`] or [`XK_adiaeresis`]. The definitions are generated by
+//! `scripts/gen_keysyms.py` (`make keysyms`).
+//!
+//! Characters without a keysym of their own are sent as Unicode keysyms, which are the code
+//! point plus `0x01000000`.
+mod defs;
+
+pub use defs::*;
+
+/// Offset of Unicode keysyms.
+pub const UNICODE_OFFSET: u32 = 0x0100_0000;
+
+/// Look up the keysym of a character.
+///
+/// Characters are mapped to their legacy keysym if X11 defines one, as VNC servers are most
+/// likely to know them. All other characters are mapped to their Unicode keysym. Whitespace and
+/// control characters with a key of their own, like `'\n'`, are mapped to that key.
+///
+/// # Parameters
+///
+/// * c: `char` - The character to look up.
+///
+/// # Returns
+///
+/// * `Some(u32)` - The keysym of the character.
+/// * `None` - If the character is a control character without a key.
+pub fn from_char(c: char) -> Option<u32> {
+    let keysym: u32 = match c {
+        '\n' | '\r' => XK_Return,
+        '\t' => XK_Tab,
+        '\x08' => XK_BackSpace,
+        '\x1b' => XK_Escape,
+        '\x7f' => XK_Delete,
+        c if c.is_control() => return None,
+        c => {
+            let codepoint: u32 = c as u32;
+            match UNICODE_KEYSYMS.binary_search_by_key(&codepoint, |&(cp, _)| cp) {
+                Ok(i) => UNICODE_KEYSYMS[i].1,
+                Err(_) => UNICODE_OFFSET + codepoint,
+            }
+        }
+    };
+    Some(keysym)
+}
+
+/// Look up a keysym by its X11 name, without the `XK_` prefix.
+///
+/// Names are case-sensitive, e.g. `"Return"`, `"Henkan"` or `"dead_acute"`. Unicode keysyms can
+/// be given as `"U"` followed by the hexadecimal code point, e.g. `"U20AC"`.
+///
+/// # Parameters
+///
+/// * name: `&str` - The name of the keysym.
+///
+/// # Returns
+///
+/// * `Some(u32)` - The keysym.
+/// * `None` - If no keysym has the given name.
+pub fn from_name(name: &str) -> Option<u32> {
+    if let Ok(i) = KEYSYM_NAMES.binary_search_by_key(&name, |&(n, _)| n) {
+        return Some(KEYSYM_NAMES[i].1);
+    }
+
+    let hex: &str = name.strip_prefix('U')?;
+    if hex.len() < 4 || hex.len() > 6 {
+        return None;
+    }
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .and_then(char::from_u32)
+        .and_then(from_char)
+}
@@ -7,46 +7,34 @@
 //!
 //! ## Example
 //!
-//! To use this crate, you need to create a `VncClient` instance, which will connect you to your
-//! VNC server. This instance must be passed to any function which communicates with the VNC
-//! server.
+//! To use this crate, you need to create a [`Session`], which connects you to your VNC server and
+//! keeps track of its screen. Keyboard, mouse and screen actions are methods of the session.
 //!
 //! ``` no_run
-//! use isototest::connection::{create_vnc_client, kill_client};
-//! use isototest::action::keyboard::write_to_console;
-//! use isototest::action::view::read_screen;
-//! use tokio::{self};
-//! use std::process::exit;
+//! use std::path::Path;
+//! use std::time::Duration;
+//!
+//! use isototest::action::keyboard::layout::KeyboardLayout;
+//! use isototest::action::view::compare::DEFAULT_SIMILARITY;
+//! use isototest::connection::config::{PasswordSource, VncConfig};
+//! use isototest::Session;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!     let addr = "127.0.0.1:5900";
-//!     let psw = "password".to_string(); // Value irrelevant if the server does not use authentication.
-//!     let mut client = match create_vnc_client(addr.to_string(), Some(psw.clone())).await {
-//!         Ok(client) => {
-//!             println!("Client created. Handshake successful.");
-//!             client
-//!         },
-//!         Err(e) => {
-//!             eprintln!("[Error] {:?}", e);
-//!             exit(1)
-//!         }
-//!     };
+//!     // The password is irrelevant if the server does not use authentication.
+//!     let config = VncConfig::new().with_password(PasswordSource::Plain("password".to_string()));
+//!     let mut session = Session::connect("127.0.0.1:5900", &config).await?;
+//!     session.set_keyboard_layout(KeyboardLayout::Us);
 //!
 //!     // Send a series of keypresses to the VNC server to type out the given text.
 //!     // Can be used to execute commands on the Terminal.
-//!     match write_to_console(&client, "Hello World!\n".to_string(), None).await {
-//!         Ok(_) => {
-//!             println!("Test text sent!");
-//!         }
-//!         Err(e) => {
-//!             eprintln!("[error] {:?}", e);
-//!             exit(1);
-//!         }
-//!     }
+//!     session.write_to_console("Hello World!\n").await?;
+//!     let (stilltime, timeout) = (Duration::from_secs(1), Duration::from_secs(10));
+//!     session.wait_still_screen(stilltime, timeout, DEFAULT_SIMILARITY, &[]).await?;
+//!     session.save_screenshot(Path::new("hello.png"), None)?;
 //!
-//!     // Kill VNC connection and release resources.
-//!     kill_client(client).await?;
+//!     // Close the VNC connection and release resources.
+//!     session.close().await?;
 //!     Ok(())
 //! }
 //! ```
@@ -54,23 +42,38 @@
 //! ## Optional Features
 //!
 //! * `default-logging` - Provides you with a sensible logger configuration using the `env_logger`
-//! crate.
+//!   crate.
 
 // Organize library structure.
 pub mod action;
 pub mod connection;
 pub mod errors;
+pub mod keysym;
 pub mod logging;
+pub mod session;
 pub(crate) mod types;
 
+pub use errors::Error;
+pub use session::Session;
+
 // Provide code on the root level of the library
 #[cfg(feature = "default-logging")]
-use crate::errors::util_errors::LoggingError;
-#[cfg(feature = "default-logging")]
 use crate::logging::init_default_logging;
 
+/// Set up the default logging configuration, see the `default-logging` feature.
+///
+/// # Parameters
+///
+/// * level: `Option<&str>` - The log level, `"info"`, `"debug"` or `"trace"`. Defaults to
+///   `"info"`.
+///
+/// # Returns
+///
+/// * `Ok(())` - If logging has been set up.
+/// * `Err(Error)` - An `Error::LoggingError` if the log level is invalid or a logger has already
+///   been set up.
 #[cfg(feature = "default-logging")]
-pub fn init_logging(level: Option<&str>) -> Result<(), LoggingError> {
+pub fn init_logging(level: Option<&str>) -> Result<(), Error> {
     init_default_logging(level)?;
     Ok(())
 }
@@ -19,18 +19,9 @@ pub const LOG_TARGET: &str = "[isototest]";
 ///
 pub(crate) fn init_default_logging(level: Option<&str>) -> Result<(), LoggingError> {
     match level {
-        Some("info") | None => {
-            log_builder(log::LevelFilter::Info);
-            Ok(())
-        }
-        Some("debug") => {
-            log_builder(log::LevelFilter::Debug);
-            Ok(())
-        }
-        Some("trace") => {
-            log_builder(log::LevelFilter::Trace);
-            Ok(())
-        }
+        Some("info") | None => log_builder(log::LevelFilter::Info),
+        Some("debug") => log_builder(log::LevelFilter::Debug),
+        Some("trace") => log_builder(log::LevelFilter::Trace),
         Some(invalid) => Err(LoggingError::InvalidLogLevelError(format!(
             "Invalid log level '{}'!",
             invalid
@@ -39,7 +30,7 @@ pub(crate) fn init_default_logging(level: Option<&str>) -> Result<(), LoggingErr
 }
 
 #[cfg(feature = "default-logging")]
-fn log_builder(level: log::LevelFilter) {
+fn log_builder(level: log::LevelFilter) -> Result<(), LoggingError> {
     Builder::new()
         .filter_level(level)
         .format(|buf, record| {
@@ -52,5 +43,6 @@ fn log_builder(level: log::LevelFilter) {
                 record.args()
             )
         })
-        .init();
+        .try_init()
+        .map_err(|e| LoggingError::LoggingInitError(e.to_string()))
 }
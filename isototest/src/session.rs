@@ -0,0 +1,782 @@
+// SPDX-FileCopyrightText: Christopher Hock <christopher-hock@suse.com>
+// SPDX-LicenseIdentifier: GPL-2.0-or-later
+
+//! This module provides a session which keeps track of the remote screen.
+//!
+//! A [`Session`] takes ownership of a connected `VncClient` and dispatches the `VncEvent`s sent
+//! by the server with an [`EventDispatcher`]. Framebuffer updates are applied to an in-memory
+//! [`Framebuffer`] in a background task, so callers can take consistent snapshots of the whole
+//! screen at any time without touching the file system.
+//!
+//! The session also keeps the state the actions depend on, like the keyboard layout, the typing
+//! speed and the screen resolution, and offers the keyboard, mouse and view actions as methods.
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use log::{debug, info, warn};
+use tokio::{
+    sync::{broadcast, watch},
+    task::JoinHandle,
+};
+use vnc::{PixelFormat, VncClient, VncEvent, X11Event};
+
+use crate::action::keyboard::{self, layout::KeyboardLayout, speed::TypingSpeed};
+use crate::action::mouse::{self, MouseButton, ScrollDirection};
+use crate::action::text::{self, font::Font, TextGrid};
+use crate::action::view::compare::Region;
+use crate::action::view::framebuffer::Framebuffer;
+use crate::action::view::inspect::{self, ColorShare};
+use crate::action::view::needle::{Needle, NeedleMatch};
+use crate::action::view::{self, ScreenshotFormat};
+use crate::connection::reconnect::{connect_with_retry, RetryPolicy};
+use crate::connection::transport::{Target, VncStream};
+use crate::connection::{config::VncConfig, connect, connect_stream, kill_client, EventDispatcher};
+use crate::errors::Error;
+use crate::logging::LOG_TARGET;
+
+/// A VNC session keeping an up-to-date copy of the remote screen.
+pub struct Session {
+    dispatcher: EventDispatcher,
+    framebuffer: Arc<RwLock<Framebuffer>>,
+    updates: watch::Receiver<u64>,
+    pump: JoinHandle<()>,
+    keyboard_layout: KeyboardLayout,
+    typing_speed: TypingSpeed,
+    text_font: Arc<Font>,
+}
+
+impl Session {
+    /// Start a new session on a connected client.
+    ///
+    /// The session must be created directly after the client has been created, as the server
+    /// announces the screen resolution only once after the handshake. Functions consuming
+    /// `VncEvent`s themselves, like [`crate::action::view::read_screen`], must not be used on the
+    /// client afterwards.
+    ///
+    /// # Parameters
+    ///
+    /// * client: `VncClient` - The connected client. It must have been connected with the default
+    ///   pixel format, otherwise use [`Session::connect`].
+    ///
+    /// # Returns
+    ///
+    /// * `Session` - The new session. Its framebuffer is empty until the server sent the first
+    ///   frame.
+    pub fn new(client: VncClient) -> Session {
+        Session::start(
+            EventDispatcher::spawn(client),
+            VncConfig::default().pixel_format(),
+        )
+    }
+
+    /// Connect to a VNC server and start a session.
+    ///
+    /// # Parameters
+    ///
+    /// * target: `impl Into<Target>` - The VNC target server, e.g. `172.0.0.1:5900` or
+    ///   `unix:/run/qemu/vnc.sock`.
+    /// * config: `&VncConfig` - The configuration of the connection.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Session)` - The new session.
+    /// * `Err(Error)` - If the connection fails, see [`crate::connection::connect`].
+    pub async fn connect(target: impl Into<Target>, config: &VncConfig) -> Result<Session, Error> {
+        let client: VncClient = connect(target, config).await?;
+        Ok(Session::start(
+            EventDispatcher::spawn(client),
+            config.pixel_format(),
+        ))
+    }
+
+    /// Start a session on an established stream, e.g. an SSH tunnel.
+    ///
+    /// # Parameters
+    ///
+    /// * stream: `impl VncStream` - The stream connected to the VNC server.
+    /// * config: `&VncConfig` - The configuration of the connection.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Session)` - The new session.
+    /// * `Err(Error)` - If the connection fails, see [`crate::connection::connect_stream`].
+    pub async fn connect_stream(
+        stream: impl VncStream,
+        config: &VncConfig,
+    ) -> Result<Session, Error> {
+        let client: VncClient = connect_stream(stream, config).await?;
+        Ok(Session::start(
+            EventDispatcher::spawn(client),
+            config.pixel_format(),
+        ))
+    }
+
+    /// Connect to a VNC server and start a session which survives losing the connection.
+    ///
+    /// The connection is attempted according to `policy`, and re-established the same way
+    /// whenever it is lost, e.g. because the remote machine reboots. The screen is restored with a
+    /// full framebuffer update afterwards. Subscribe to [`EventDispatcher::subscribe_reconnect`]
+    /// to be notified about reconnects. Input sent while the connection is down fails.
+    ///
+    /// # Parameters
+    ///
+    /// * target: `impl Into<Target>` - The VNC target server, see [`Session::connect`].
+    /// * config: `&VncConfig` - The configuration of the connection.
+    /// * policy: `RetryPolicy` - How often and how fast to attempt connecting.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Session)` - The new session.
+    /// * `Err(Error)` - The error of the last connection attempt.
+    pub async fn connect_with_retry(
+        target: impl Into<Target>,
+        config: &VncConfig,
+        policy: RetryPolicy,
+    ) -> Result<Session, Error> {
+        let target: Target = target.into();
+        let (client, _) = connect_with_retry(&target, config, &policy).await?;
+        let dispatcher: EventDispatcher =
+            EventDispatcher::spawn_reconnecting(client, target, config, policy);
+        Ok(Session::start(dispatcher, config.pixel_format()))
+    }
+
+    /// Start a session on a dispatcher whose client is connected with the given pixel format.
+    fn start(dispatcher: EventDispatcher, pixel_format: Option<PixelFormat>) -> Session {
+        info!(target: LOG_TARGET, "Starting session...");
+        let events: broadcast::Receiver<Arc<VncEvent>> = dispatcher.subscribe_framebuffer();
+
+        // The resolution and the server's pixel format may have been announced before
+        // subscribing.
+        let (width, height): (u32, u32) = dispatcher.resolution();
+        let mut fb: Framebuffer = Framebuffer::new(width, height);
+        if let Some(pixel_format) = pixel_format.or(dispatcher.pixel_format()) {
+            fb.set_pixel_format(pixel_format);
+        }
+        let framebuffer: Arc<RwLock<Framebuffer>> = Arc::new(RwLock::new(fb));
+        let (updates_tx, updates) = watch::channel(0);
+        let pump: JoinHandle<()> = tokio::spawn(apply_updates(
+            dispatcher.shared_client(),
+            events,
+            framebuffer.clone(),
+            updates_tx,
+        ));
+
+        Session {
+            dispatcher,
+            framebuffer,
+            updates,
+            pump,
+            keyboard_layout: KeyboardLayout::default(),
+            typing_speed: TypingSpeed::default(),
+            text_font: Arc::new(Font::default()),
+        }
+    }
+
+    /// The client used by this session.
+    ///
+    /// Use it to send input to the remote machine, e.g. with
+    /// [`crate::action::keyboard::write_to_console`]. The client is replaced when the session
+    /// reconnects, so it should be fetched again for every action.
+    pub fn client(&self) -> VncClient {
+        self.dispatcher.client()
+    }
+
+    /// The dispatcher distributing the events of this session.
+    ///
+    /// Use it to subscribe to events which do not affect the screen, like bells or clipboard
+    /// changes.
+    pub fn dispatcher(&self) -> &EventDispatcher {
+        &self.dispatcher
+    }
+
+    /// The keyboard layout of the remote machine, `us` by default.
+    ///
+    /// Used by functions sending keys on behalf of the session, like
+    /// [`crate::action::keyboard::send_key_until_needlematch`].
+    pub fn keyboard_layout(&self) -> KeyboardLayout {
+        self.keyboard_layout
+    }
+
+    /// Set the keyboard layout of the remote machine.
+    ///
+    /// # Parameters
+    ///
+    /// * layout: `KeyboardLayout` - The layout the remote machine is configured with.
+    pub fn set_keyboard_layout(&mut self, layout: KeyboardLayout) {
+        info!(target: LOG_TARGET, "Keyboard layout set to '{}'.", layout);
+        self.keyboard_layout = layout;
+    }
+
+    /// The speed at which keys are sent on behalf of the session.
+    ///
+    /// Functions taking an optional typing speed use it unless they are given one.
+    pub fn typing_speed(&self) -> TypingSpeed {
+        self.typing_speed
+    }
+
+    /// Set the speed at which keys are sent on behalf of the session.
+    ///
+    /// # Parameters
+    ///
+    /// * speed: `TypingSpeed` - The new default typing speed.
+    pub fn set_typing_speed(&mut self, speed: TypingSpeed) {
+        info!(target: LOG_TARGET, "Typing speed set to {} characters per second.", speed.chars_per_sec());
+        self.typing_speed = speed;
+    }
+
+    /// The font of the remote machine's text console, the VGA 8x16 font by default.
+    ///
+    /// Used to read text from the screen, see [`Session::screen_text`].
+    pub fn text_font(&self) -> Arc<Font> {
+        self.text_font.clone()
+    }
+
+    /// Set the font of the remote machine's text console.
+    ///
+    /// # Parameters
+    ///
+    /// * font: `Font` - The console font, e.g. loaded with [`Font::load`].
+    pub fn set_text_font(&mut self, font: Font) {
+        info!(target: LOG_TARGET, "Text font set to {}x{}.", font.width(), font.height());
+        self.text_font = Arc::new(font);
+    }
+
+    /// The current screen resolution as `(width, height)`.
+    pub fn resolution(&self) -> (u32, u32) {
+        self.framebuffer().resolution()
+    }
+
+    /// The number of changes applied to the framebuffer so far.
+    pub fn generation(&self) -> u64 {
+        self.framebuffer().generation()
+    }
+
+    /// The regions of the screen changed after the given generation.
+    ///
+    /// See [`Framebuffer::damage_since`].
+    ///
+    /// # Parameters
+    ///
+    /// * generation: `u64` - A generation seen before, e.g. from [`Session::generation`].
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Region>` - The changed regions, empty if nothing changed.
+    pub fn damage_since(&self, generation: u64) -> Vec<Region> {
+        self.framebuffer().damage_since(generation)
+    }
+
+    /// Take a snapshot of the whole screen.
+    pub fn snapshot(&self) -> RgbaImage {
+        self.framebuffer().image().clone()
+    }
+
+    /// Get a shared handle to the current screen.
+    ///
+    /// Unlike [`Session::snapshot`], this does not copy the frame. The handle keeps showing the
+    /// screen at the time of the call.
+    pub fn frame(&self) -> Arc<RgbaImage> {
+        self.framebuffer().shared()
+    }
+
+    /// Subscribe to changes of the framebuffer.
+    ///
+    /// The channel carries the framebuffer generation and is updated whenever the screen
+    /// changes. It is closed once the connection to the server is lost.
+    pub fn frame_updates(&self) -> watch::Receiver<u64> {
+        self.updates.clone()
+    }
+
+    /// Stop the session and close the connection to the server.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the connection was closed correctly.
+    /// * `Err(Error)` - If closing the client failed.
+    pub async fn close(self) -> Result<(), Error> {
+        info!(target: LOG_TARGET, "Closing session...");
+        self.pump.abort();
+        self.dispatcher.stop();
+        kill_client(self.client()).await
+    }
+
+    /// Type text with the session's keyboard layout and typing speed.
+    ///
+    /// See [`keyboard::write_to_console`].
+    ///
+    /// # Parameters
+    ///
+    /// * text: `&str` - The text to type.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the text has been sent.
+    /// * `Err(Error)` - If a character cannot be typed with the layout or the transaction fails.
+    pub async fn write_to_console(&self, text: &str) -> Result<(), Error> {
+        keyboard::write_to_console(
+            &self.client(),
+            text.to_string(),
+            self.keyboard_layout,
+            Some(self.typing_speed),
+        )
+        .await
+    }
+
+    /// Type text in chunks, verifying that the remote machine echoes each chunk.
+    ///
+    /// See [`keyboard::type_string`].
+    ///
+    /// # Parameters
+    ///
+    /// * text: `&str` - The text to type.
+    /// * chunk_size: `usize` - Number of characters typed before waiting for the echo.
+    /// * timeout: `Duration` - How long to wait for the screen to change after each chunk.
+    /// * ignore: `&[Region]` - Regions whose changes do not count as the echo, e.g. a blinking
+    ///   cursor or a clock.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the text has been typed and every chunk was echoed.
+    /// * `Err(Error)` - If typing fails or the screen stopped changing.
+    pub async fn type_string(
+        &self,
+        text: &str,
+        chunk_size: usize,
+        timeout: Duration,
+        ignore: &[Region],
+    ) -> Result<(), Error> {
+        keyboard::type_string(self, text, chunk_size, timeout, ignore, None).await
+    }
+
+    /// Send a key or key combination with the session's keyboard layout and typing speed.
+    ///
+    /// See [`keyboard::send_key`].
+    ///
+    /// # Parameters
+    ///
+    /// * spec: `&str` - The openQA key specification, e.g. `"ret"` or `"ctrl-alt-f2"`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the key combination has been sent.
+    /// * `Err(Error)` - If the specification is invalid or the transaction fails.
+    pub async fn send_key(&self, spec: &str) -> Result<(), Error> {
+        keyboard::send_key(
+            &self.client(),
+            spec,
+            self.keyboard_layout,
+            Some(self.typing_speed),
+        )
+        .await
+    }
+
+    /// Send a key combination until the screen matches a needle with one of the given tags.
+    ///
+    /// See [`keyboard::send_key_until_needlematch`].
+    ///
+    /// # Parameters
+    ///
+    /// * needles: `&[Needle]` - The needles to consider.
+    /// * tags: `&[&str]` - The tags to look for.
+    /// * spec: `&str` - The openQA key specification to send.
+    /// * counter: `u32` - Maximum number of times the key is sent.
+    /// * timeout: `Duration` - How long to wait for a matching screen after each key.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NeedleMatch)` - The matching needle.
+    /// * `Err(Error)` - If sending a key fails or no needle matched after the last key.
+    pub async fn send_key_until_needlematch(
+        &self,
+        needles: &[Needle],
+        tags: &[&str],
+        spec: &str,
+        counter: u32,
+        timeout: Duration,
+    ) -> Result<NeedleMatch, Error> {
+        keyboard::send_key_until_needlematch(self, needles, tags, spec, counter, timeout, None)
+            .await
+    }
+
+    /// Move the pointer to an absolute position on the current screen.
+    ///
+    /// # Parameters
+    ///
+    /// * x: `u32` - Horizontal target position.
+    /// * y: `u32` - Vertical target position.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the pointer event has been sent.
+    /// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+    pub async fn mouse_move(&self, x: u32, y: u32) -> Result<(), Error> {
+        mouse::mouse_move(&self.client(), self.resolution(), x, y).await
+    }
+
+    /// Click a mouse button at an absolute position on the current screen.
+    ///
+    /// # Parameters
+    ///
+    /// * x: `u32` - Horizontal position of the click.
+    /// * y: `u32` - Vertical position of the click.
+    /// * button: `MouseButton` - The button to click.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the click has been sent.
+    /// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+    pub async fn mouse_click(&self, x: u32, y: u32, button: MouseButton) -> Result<(), Error> {
+        mouse::mouse_click(&self.client(), self.resolution(), x, y, button).await
+    }
+
+    /// Double click a mouse button at an absolute position on the current screen.
+    ///
+    /// # Parameters
+    ///
+    /// * x: `u32` - Horizontal position of the double click.
+    /// * y: `u32` - Vertical position of the double click.
+    /// * button: `MouseButton` - The button to click.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If both clicks have been sent.
+    /// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+    pub async fn mouse_dclick(&self, x: u32, y: u32, button: MouseButton) -> Result<(), Error> {
+        mouse::mouse_dclick(&self.client(), self.resolution(), x, y, button).await
+    }
+
+    /// Drag the pointer from one position to another while holding a button.
+    ///
+    /// # Parameters
+    ///
+    /// * from: `(u32, u32)` - Position at which the button is pressed.
+    /// * to: `(u32, u32)` - Position at which the button is released.
+    /// * button: `MouseButton` - The button to hold.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the drag has been sent.
+    /// * `Err(Error)` - If a position is outside of the screen or the transaction fails.
+    pub async fn mouse_drag(
+        &self,
+        from: (u32, u32),
+        to: (u32, u32),
+        button: MouseButton,
+    ) -> Result<(), Error> {
+        mouse::mouse_drag(&self.client(), self.resolution(), from, to, button).await
+    }
+
+    /// Move the pointer into the bottom right corner of the current screen.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the pointer event has been sent.
+    /// * `Err(Error)` - If no resolution has been received yet or the transaction fails.
+    pub async fn mouse_hide(&self) -> Result<(), Error> {
+        mouse::mouse_hide(&self.client(), self.resolution()).await
+    }
+
+    /// Turn the scroll wheel at an absolute position on the current screen.
+    ///
+    /// # Parameters
+    ///
+    /// * x: `u32` - Horizontal position of the pointer.
+    /// * y: `u32` - Vertical position of the pointer.
+    /// * direction: `ScrollDirection` - The direction to scroll in.
+    /// * steps: `u32` - Number of wheel steps.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all steps have been sent.
+    /// * `Err(Error)` - If the position is outside of the screen or the transaction fails.
+    pub async fn mouse_scroll(
+        &self,
+        x: u32,
+        y: u32,
+        direction: ScrollDirection,
+        steps: u32,
+    ) -> Result<(), Error> {
+        mouse::mouse_scroll(&self.client(), self.resolution(), x, y, direction, steps).await
+    }
+
+    /// Wait for the screen to match a needle with one of the given tags.
+    ///
+    /// See [`view::assert_screen`].
+    ///
+    /// # Parameters
+    ///
+    /// * needles: `&[Needle]` - The needles to consider.
+    /// * tags: `&[&str]` - The tags to look for.
+    /// * timeout: `Duration` - How long to wait for a matching screen.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(NeedleMatch)` - The matching needle.
+    /// * `Err(Error)` - If the connection is lost or no needle matched in time.
+    pub async fn assert_screen(
+        &self,
+        needles: &[Needle],
+        tags: &[&str],
+        timeout: Duration,
+    ) -> Result<NeedleMatch, Error> {
+        view::assert_screen(self, needles, tags, timeout).await
+    }
+
+    /// Check whether the screen matches a needle with one of the given tags.
+    ///
+    /// See [`view::check_screen`].
+    ///
+    /// # Parameters
+    ///
+    /// * needles: `&[Needle]` - The needles to consider.
+    /// * tags: `&[&str]` - The tags to look for.
+    /// * timeout: `Duration` - How long to wait for a matching screen.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(NeedleMatch))` - The matching needle.
+    /// * `Ok(None)` - If no needle matched in time.
+    /// * `Err(Error)` - If the connection is lost.
+    pub async fn check_screen(
+        &self,
+        needles: &[Needle],
+        tags: &[&str],
+        timeout: Duration,
+    ) -> Result<Option<NeedleMatch>, Error> {
+        view::check_screen(self, needles, tags, timeout).await
+    }
+
+    /// Wait until the screen has not changed for `stilltime`.
+    ///
+    /// See [`view::wait_still_screen`].
+    ///
+    /// # Parameters
+    ///
+    /// * stilltime: `Duration` - How long the screen must not change.
+    /// * timeout: `Duration` - How long to wait for the screen to become still.
+    /// * similarity: `f64` - Similarity in dB from which two frames are considered equal.
+    /// * ignore: `&[Region]` - Regions which are ignored.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the screen was still for `stilltime`.
+    /// * `Ok(false)` - If the screen kept changing until `timeout`.
+    /// * `Err(Error)` - If the connection is lost.
+    pub async fn wait_still_screen(
+        &self,
+        stilltime: Duration,
+        timeout: Duration,
+        similarity: f64,
+        ignore: &[Region],
+    ) -> Result<bool, Error> {
+        view::wait_still_screen(self, stilltime, timeout, similarity, ignore).await
+    }
+
+    /// Run an action and wait until the screen changes.
+    ///
+    /// See [`view::wait_screen_change`].
+    ///
+    /// # Parameters
+    ///
+    /// * action: `impl Future<Output = Result<(), Error>>` - The action expected to change the
+    ///   screen, e.g. `session.send_key("ret")`.
+    /// * timeout: `Duration` - How long to wait for the screen to change after the action.
+    /// * similarity: `f64` - Similarity in dB below which the screen is considered changed.
+    /// * ignore: `&[Region]` - Regions which are ignored.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the screen changed.
+    /// * `Ok(false)` - If the screen did not change until `timeout`.
+    /// * `Err(Error)` - If the action fails or the connection is lost.
+    pub async fn wait_screen_change(
+        &self,
+        action: impl Future<Output = Result<(), Error>>,
+        timeout: Duration,
+        similarity: f64,
+        ignore: &[Region],
+    ) -> Result<bool, Error> {
+        view::wait_screen_change(self, action, timeout, similarity, ignore).await
+    }
+
+    /// Copy a region of the current screen.
+    ///
+    /// See [`inspect::capture_region`].
+    ///
+    /// # Parameters
+    ///
+    /// * region: `Region` - The region to copy.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(RgbaImage)` - The content of the region.
+    /// * `Err(Error)` - If the region is empty or not within the current resolution.
+    pub fn capture_region(&self, region: Region) -> Result<RgbaImage, Error> {
+        Ok(inspect::capture_region(&self.frame(), region)?)
+    }
+
+    /// Get the colour of a single pixel of the current screen.
+    ///
+    /// # Parameters
+    ///
+    /// * x: `u32` - Horizontal position of the pixel.
+    /// * y: `u32` - Vertical position of the pixel.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Rgba<u8>)` - The colour of the pixel.
+    /// * `Err(Error)` - If the pixel is not within the current resolution.
+    pub fn pixel_at(&self, x: u32, y: u32) -> Result<Rgba<u8>, Error> {
+        Ok(inspect::pixel_at(&self.frame(), x, y)?)
+    }
+
+    /// Get the average colour of a region of the current screen.
+    ///
+    /// See [`inspect::region_mean_color`].
+    ///
+    /// # Parameters
+    ///
+    /// * region: `Region` - The region to evaluate.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Rgba<u8>)` - The mean colour.
+    /// * `Err(Error)` - If the region is empty or not within the current resolution.
+    pub fn region_mean_color(&self, region: Region) -> Result<Rgba<u8>, Error> {
+        Ok(inspect::region_mean_color(&self.frame(), region)?)
+    }
+
+    /// Assert that the colours of a region of the current screen meet all expectations.
+    ///
+    /// See [`inspect::assert_colors`].
+    ///
+    /// # Parameters
+    ///
+    /// * region: `Region` - The region to evaluate.
+    /// * expected: `&[ColorShare]` - The expected shares of colours.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all expectations are met.
+    /// * `Err(Error)` - An `Error::ColorMismatch` for the first expectation which is not met, or an
+    ///   `Error::ScreenCaptureError` if the region is empty or not within the current resolution.
+    pub fn assert_colors(&self, region: Region, expected: &[ColorShare]) -> Result<(), Error> {
+        Ok(inspect::assert_colors(&self.frame(), region, expected)?)
+    }
+
+    /// Read the text on the screen.
+    ///
+    /// See [`text::screen_text`].
+    ///
+    /// # Returns
+    ///
+    /// * `TextGrid` - The characters on the screen.
+    pub fn screen_text(&self) -> TextGrid {
+        text::screen_text(self)
+    }
+
+    /// Wait for text matching a regular expression to appear on the screen.
+    ///
+    /// See [`text::wait_for_text`].
+    ///
+    /// # Parameters
+    ///
+    /// * pattern: `&str` - The regular expression, e.g. `r"login:\s*$"`.
+    /// * timeout: `Duration` - How long to wait for the text.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(String)` - The matching text.
+    /// * `Err(Error)` - If the pattern is invalid, the connection is lost or the text did not
+    ///   appear in time.
+    pub async fn wait_for_text(&self, pattern: &str, timeout: Duration) -> Result<String, Error> {
+        text::wait_for_text(self, pattern, timeout).await
+    }
+
+    /// Save the current screen to the file system.
+    ///
+    /// # Parameters
+    ///
+    /// * path: `&Path` - The file to write. Existing files are overwritten.
+    /// * format: `Option<ScreenshotFormat>` - The file format. If `None`, it is determined from
+    ///   the file extension.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the screenshot has been saved.
+    /// * `Err(Error)` - If the format cannot be determined or the file cannot be written.
+    pub fn save_screenshot(
+        &self,
+        path: &Path,
+        format: Option<ScreenshotFormat>,
+    ) -> Result<(), Error> {
+        Ok(view::save_screenshot(&self.frame(), path, format)?)
+    }
+
+    /// The framebuffer shared with the task applying the updates.
+    pub(crate) fn shared_framebuffer(&self) -> Arc<RwLock<Framebuffer>> {
+        self.framebuffer.clone()
+    }
+
+    /// Read access to the framebuffer.
+    ///
+    /// The framebuffer is only written to in short, non-panicking sections, so a poisoned lock
+    /// still holds a consistent frame.
+    fn framebuffer(&self) -> std::sync::RwLockReadGuard<'_, Framebuffer> {
+        self.framebuffer.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.pump.abort();
+    }
+}
+
+/// Apply framebuffer updates to the framebuffer until the dispatcher stops.
+///
+/// If updates were missed because this task fell behind, a full framebuffer update is requested
+/// to restore a consistent screen.
+async fn apply_updates(
+    client: Arc<RwLock<VncClient>>,
+    mut events: broadcast::Receiver<Arc<VncEvent>>,
+    framebuffer: Arc<RwLock<Framebuffer>>,
+    updates: watch::Sender<u64>,
+) {
+    let current = || client.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Err(e) = current().input(X11Event::FullRefresh).await {
+        warn!(target: LOG_TARGET, "Unable to request full screen update: {}", e);
+    }
+
+    loop {
+        match events.recv().await {
+            Ok(event) => {
+                let mut fb = framebuffer.write().unwrap_or_else(|e| e.into_inner());
+                match fb.apply(&event) {
+                    Ok(true) => {
+                        updates.send_replace(fb.generation());
+                    }
+                    Ok(false) => {
+                        debug!(target: LOG_TARGET, "Session ignored event '{:?}'.", event);
+                    }
+                    Err(e) => {
+                        warn!(target: LOG_TARGET, "Unable to apply screen update: {}", e);
+                    }
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!(target: LOG_TARGET, "Session missed {} screen updates; requesting full update.", missed);
+                if let Err(e) = current().input(X11Event::FullRefresh).await {
+                    warn!(target: LOG_TARGET, "Unable to request full screen update: {}", e);
+                }
+            }
+            Err(broadcast::error::RecvError::Closed) => {
+                info!(target: LOG_TARGET, "Session stopped: connection closed.");
+                break;
+            }
+        }
+    }
+}
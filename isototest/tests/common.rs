@@ -1,4 +1,5 @@
 //! This module contains common setup code required for the testuite.
+#![allow(dead_code)]
 use std::io;
 
 use tokio::{
@@ -111,5 +112,285 @@ pub async fn mock_vnc_handshake(mut socket: TcpStream) -> Result<(), io::Error>
 
 /// Kill server connection.
 pub async fn kill_connection(mut socket: tokio::net::TcpStream) {
-    let _ = socket.shutdown();
+    let _ = socket.shutdown().await;
+}
+
+/// Messages sent by the client, as recorded by the [`MockServer`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    ClientInit { shared: bool },
+    SetPixelFormat,
+    SetEncodings(Vec<i32>),
+    UpdateRequest { incremental: bool },
+    Key { keysym: u32, down: bool },
+    Pointer { x: u16, y: u16, buttons: u8 },
+    CutText(String),
+}
+
+/// Commands to make the [`MockServer`] send something to the client.
+#[derive(Debug, Clone)]
+pub enum ServerCommand {
+    Bell,
+    CutText(String),
+    /// Replace the screen content. The new screen is sent with the next update request.
+    SetScreen(image::RgbaImage),
+    /// Move a rectangle `(x, y, width, height)` of the screen to `(x, y)` with a `CopyRect`
+    /// update, which is sent right away.
+    CopyRect((u16, u16, u16, u16), (u16, u16)),
+    /// Send an image as a raw rectangle at `(x, y)` right away, without changing the screen. The
+    /// rectangle may reach beyond the screen.
+    Rect((u16, u16), image::RgbaImage),
+    /// Close the connection to the client.
+    Disconnect,
+}
+
+/// A mock VNC server speaking RFB 3.8 without authentication.
+///
+/// The server serves a single client, records all client messages and answers framebuffer
+/// update requests with raw encoded rectangles of its screen.
+pub struct MockServer {
+    pub addr: String,
+    pub messages: tokio::sync::mpsc::UnboundedReceiver<ClientMessage>,
+    pub control: tokio::sync::mpsc::UnboundedSender<ServerCommand>,
+}
+
+impl MockServer {
+    /// Wait for the next client message matching the predicate, skipping all others.
+    pub async fn expect(&mut self, pred: impl Fn(&ClientMessage) -> bool) -> ClientMessage {
+        let wait = async {
+            loop {
+                match self.messages.recv().await {
+                    Some(msg) if pred(&msg) => return msg,
+                    Some(_) => continue,
+                    None => panic!("Mock server closed before the expected message arrived"),
+                }
+            }
+        };
+        tokio::time::timeout(std::time::Duration::from_secs(5), wait)
+            .await
+            .expect("Timed out waiting for client message")
+    }
+
+    /// Collect all key events received until the client has been quiet for a moment.
+    pub async fn collect_keys(&mut self) -> Vec<(u32, bool)> {
+        let mut keys = Vec::new();
+        while let Ok(Some(msg)) =
+            tokio::time::timeout(std::time::Duration::from_millis(300), self.messages.recv()).await
+        {
+            if let ClientMessage::Key { keysym, down } = msg {
+                keys.push((keysym, down));
+            }
+        }
+        keys
+    }
+}
+
+/// Start a mock VNC server on a random local port serving the given screen.
+pub async fn start_mock_server(screen: image::RgbaImage) -> MockServer {
+    let srv = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Failed to bind mock server");
+    let addr = srv.local_addr().unwrap().to_string();
+    let (msg_tx, messages) = tokio::sync::mpsc::unbounded_channel();
+    let (control, cmd_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    tokio::spawn(async move {
+        if let Ok((socket, _)) = srv.accept().await {
+            let _ = serve_rfb(socket, screen, msg_tx, cmd_rx).await;
+        }
+    });
+
+    MockServer {
+        addr,
+        messages,
+        control,
+    }
+}
+
+/// Serve the RFB protocol on an established stream.
+pub async fn serve_rfb<S>(
+    mut stream: S,
+    screen: image::RgbaImage,
+    messages: tokio::sync::mpsc::UnboundedSender<ClientMessage>,
+    control: tokio::sync::mpsc::UnboundedReceiver<ServerCommand>,
+) -> Result<(), io::Error>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    // Handshake: version, security type "None", security result.
+    stream.write_all(b"RFB 003.008\n").await?;
+    let mut version = [0; 12];
+    stream.read_exact(&mut version).await?;
+    stream.write_all(&[1, 1]).await?;
+    let _chosen = stream.read_u8().await?;
+    stream.write_u32(0).await?;
+    serve_session(stream, screen, messages, control).await
+}
+
+/// Serve the RFB protocol on a stream whose security handshake has been completed.
+pub async fn serve_session<S>(
+    mut stream: S,
+    mut screen: image::RgbaImage,
+    messages: tokio::sync::mpsc::UnboundedSender<ClientMessage>,
+    mut control: tokio::sync::mpsc::UnboundedReceiver<ServerCommand>,
+) -> Result<(), io::Error>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    // Initialisation: shared flag, server init with a 32bpp true colour pixel format.
+    let shared = stream.read_u8().await? != 0;
+    let _ = messages.send(ClientMessage::ClientInit { shared });
+    let mut pf: [u8; 16] = [32, 24, 0, 1, 0, 255, 0, 255, 0, 255, 16, 8, 0, 0, 0, 0];
+    stream.write_u16(screen.width() as u16).await?;
+    stream.write_u16(screen.height() as u16).await?;
+    stream.write_all(&pf).await?;
+    let name = b"mock";
+    stream.write_u32(name.len() as u32).await?;
+    stream.write_all(name).await?;
+
+    let mut dirty = true;
+    let mut pending = false;
+    loop {
+        tokio::select! {
+            msg_type = stream.read_u8() => {
+                let msg = match msg_type? {
+                    0 => {
+                        let mut buf = [0; 19];
+                        stream.read_exact(&mut buf).await?;
+                        pf.copy_from_slice(&buf[3..19]);
+                        ClientMessage::SetPixelFormat
+                    }
+                    2 => {
+                        let _pad = stream.read_u8().await?;
+                        let count = stream.read_u16().await?;
+                        let mut encodings = Vec::new();
+                        for _ in 0..count {
+                            encodings.push(stream.read_i32().await?);
+                        }
+                        ClientMessage::SetEncodings(encodings)
+                    }
+                    3 => {
+                        let incremental = stream.read_u8().await? != 0;
+                        let mut buf = [0; 8];
+                        stream.read_exact(&mut buf).await?;
+                        if !incremental || dirty {
+                            send_screen(&mut stream, &screen, &pf).await?;
+                            dirty = false;
+                        } else {
+                            pending = true;
+                        }
+                        ClientMessage::UpdateRequest { incremental }
+                    }
+                    4 => {
+                        let down = stream.read_u8().await? != 0;
+                        let _pad = stream.read_u16().await?;
+                        ClientMessage::Key { keysym: stream.read_u32().await?, down }
+                    }
+                    5 => {
+                        let buttons = stream.read_u8().await?;
+                        let x = stream.read_u16().await?;
+                        let y = stream.read_u16().await?;
+                        ClientMessage::Pointer { x, y, buttons }
+                    }
+                    6 => {
+                        let mut pad = [0; 3];
+                        stream.read_exact(&mut pad).await?;
+                        let len = stream.read_u32().await?;
+                        let mut text = vec![0; len as usize];
+                        stream.read_exact(&mut text).await?;
+                        ClientMessage::CutText(String::from_utf8_lossy(&text).into_owned())
+                    }
+                    other => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Unknown client message {}", other),
+                        ))
+                    }
+                };
+                let _ = messages.send(msg);
+            }
+            cmd = control.recv() => match cmd {
+                Some(ServerCommand::Bell) => stream.write_u8(2).await?,
+                Some(ServerCommand::CutText(text)) => {
+                    stream.write_all(&[3, 0, 0, 0]).await?;
+                    stream.write_u32(text.len() as u32).await?;
+                    stream.write_all(text.as_bytes()).await?;
+                }
+                Some(ServerCommand::SetScreen(image)) => {
+                    screen = image;
+                    dirty = true;
+                    if pending {
+                        send_screen(&mut stream, &screen, &pf).await?;
+                        dirty = false;
+                        pending = false;
+                    }
+                }
+                Some(ServerCommand::CopyRect((x, y, width, height), (dst_x, dst_y))) => {
+                    let src = image::imageops::crop_imm(&screen, x as u32, y as u32, width as u32, height as u32)
+                        .to_image();
+                    image::imageops::replace(&mut screen, &src, dst_x as i64, dst_y as i64);
+                    stream.write_all(&[0, 0, 0, 1]).await?;
+                    for value in [dst_x, dst_y, width, height] {
+                        stream.write_u16(value).await?;
+                    }
+                    stream.write_i32(1).await?;
+                    stream.write_u16(x).await?;
+                    stream.write_u16(y).await?;
+                }
+                Some(ServerCommand::Rect((x, y), image)) => {
+                    send_rect(&mut stream, (x, y), &image, &pf).await?;
+                }
+                Some(ServerCommand::Disconnect) | None => return Ok(()),
+            },
+        }
+    }
+}
+
+/// Send the whole screen as a single raw encoded rectangle in the given pixel format.
+async fn send_screen<S>(stream: &mut S, screen: &image::RgbaImage, pf: &[u8; 16]) -> io::Result<()>
+where
+    S: tokio::io::AsyncWrite + Unpin,
+{
+    send_rect(stream, (0, 0), screen, pf).await
+}
+
+/// Send an image as a raw encoded rectangle at `(x, y)` in the given pixel format.
+///
+/// Only true colour formats are supported.
+async fn send_rect<S>(
+    stream: &mut S,
+    (x, y): (u16, u16),
+    image: &image::RgbaImage,
+    pf: &[u8; 16],
+) -> io::Result<()>
+where
+    S: tokio::io::AsyncWrite + Unpin,
+{
+    let bpp = pf[0] as usize / 8;
+    let big_endian = pf[2] != 0;
+    let max = |i: usize| u16::from_be_bytes([pf[i], pf[i + 1]]) as u32;
+    let (rm, gm, bm) = (max(4), max(6), max(8));
+    let (rs, gs, bs) = (pf[10], pf[11], pf[12]);
+
+    let mut data = Vec::with_capacity(image.width() as usize * image.height() as usize * bpp);
+    for px in image.pixels() {
+        let value = (px[0] as u32 * rm / 255) << rs
+            | (px[1] as u32 * gm / 255) << gs
+            | (px[2] as u32 * bm / 255) << bs;
+        if big_endian {
+            data.extend_from_slice(&value.to_be_bytes()[4 - bpp..]);
+        } else {
+            data.extend_from_slice(&value.to_le_bytes()[..bpp]);
+        }
+    }
+
+    stream.write_all(&[0, 0]).await?;
+    stream.write_u16(1).await?;
+    stream.write_u16(x).await?;
+    stream.write_u16(y).await?;
+    stream.write_u16(image.width() as u16).await?;
+    stream.write_u16(image.height() as u16).await?;
+    stream.write_i32(0).await?;
+    stream.write_all(&data).await?;
+    stream.flush().await
 }
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::connection::config::VncConfig;
+use isototest::connection::reconnect::{connect_with_retry, RetryPolicy};
+use isototest::connection::{create_vnc_client, EventDispatcher};
+use isototest::session::Session;
+use isototest::Error;
+use tokio::net::TcpListener;
+use vnc::VncEvent;
+
+mod common;
+use common::{serve_rfb, start_mock_server, ServerCommand};
+
+#[tokio::test]
+async fn test_dispatcher_fans_out_events() {
+    let screen = RgbaImage::from_pixel(16, 8, Rgba([10, 20, 30, 255]));
+    let server = start_mock_server(screen).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    let dispatcher = EventDispatcher::spawn(client);
+    let mut framebuffer = dispatcher.subscribe_framebuffer();
+    let mut bell = dispatcher.subscribe_bell();
+    let mut cut_text = dispatcher.subscribe_cut_text();
+
+    server.control.send(ServerCommand::Bell).unwrap();
+    server
+        .control
+        .send(ServerCommand::CutText("clipboard".to_string()))
+        .unwrap();
+
+    let timeout = Duration::from_secs(5);
+    tokio::time::timeout(timeout, bell.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let text = tokio::time::timeout(timeout, cut_text.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(text, "clipboard");
+
+    let event = tokio::time::timeout(timeout, async {
+        loop {
+            let event = framebuffer.recv().await.unwrap();
+            if let VncEvent::RawImage(..) = *event {
+                return event;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(matches!(*event, VncEvent::RawImage(..)));
+    assert_eq!(dispatcher.resolution(), (16, 8));
+    assert!(dispatcher.is_running());
+
+    dispatcher.stop();
+    assert!(framebuffer.recv().await.is_err());
+}
+
+#[tokio::test]
+async fn test_session_tracks_screen() {
+    let screen = RgbaImage::from_pixel(16, 8, Rgba([10, 20, 30, 255]));
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    let session = Session::new(client);
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != screen {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    // Changes of the remote screen arrive without any further requests by the caller.
+    let changed = RgbaImage::from_pixel(16, 8, Rgba([200, 100, 50, 255]));
+    server
+        .control
+        .send(ServerCommand::SetScreen(changed.clone()))
+        .unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != changed {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(session.resolution(), (16, 8));
+
+    session.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_session_reconnects() {
+    let before = RgbaImage::from_pixel(16, 8, Rgba([10, 20, 30, 255]));
+    let after = RgbaImage::from_pixel(8, 8, Rgba([30, 20, 10, 255]));
+
+    // Serve two connections in a row, like a VNC server restarting with the remote machine.
+    let srv = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = srv.local_addr().unwrap().to_string();
+    let (control, first_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (messages, _messages_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (first, second) = (before.clone(), after.clone());
+    tokio::spawn(async move {
+        let (socket, _) = srv.accept().await.unwrap();
+        let _ = serve_rfb(socket, first, messages.clone(), first_rx).await;
+        let (socket, _) = srv.accept().await.unwrap();
+        // Keep the sender alive, so the second connection stays open.
+        let (_control, second_rx) = tokio::sync::mpsc::unbounded_channel();
+        let _ = serve_rfb(socket, second, messages, second_rx).await;
+    });
+
+    let policy = RetryPolicy::new()
+        .with_delays(Duration::from_millis(10), Duration::from_millis(50), 2.0)
+        .unwrap();
+    let session = Session::connect_with_retry(&addr, &VncConfig::new(), policy)
+        .await
+        .unwrap();
+    let mut reconnects = session.dispatcher().subscribe_reconnect();
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != before {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    control.send(ServerCommand::Disconnect).unwrap();
+    let event = tokio::time::timeout(Duration::from_secs(5), reconnects.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.attempts, 1);
+
+    // The screen of the new connection replaces the old one.
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != after {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(session.resolution(), (8, 8));
+    assert!(session.dispatcher().is_running());
+}
+
+#[tokio::test]
+async fn test_connect_with_retry_gives_up() {
+    // Reserve a port and close it again, so connections are refused.
+    let addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+    let policy = RetryPolicy::new()
+        .with_max_attempts(3)
+        .unwrap()
+        .with_delays(Duration::from_millis(1), Duration::from_millis(5), 1.0)
+        .unwrap();
+    let result = connect_with_retry(&addr, &VncConfig::new(), &policy).await;
+    assert!(matches!(result, Err(Error::ConnectionError(_))));
+}
+
+#[test]
+fn test_retry_policy_backoff() {
+    let policy = RetryPolicy::new()
+        .with_delays(Duration::from_millis(100), Duration::from_secs(1), 2.0)
+        .unwrap();
+    assert_eq!(policy.delay(1), Duration::from_millis(100));
+    assert_eq!(policy.delay(3), Duration::from_millis(400));
+    assert_eq!(policy.delay(5), Duration::from_secs(1));
+    assert_eq!(policy.delay(u32::MAX), Duration::from_secs(1));
+
+    assert!(RetryPolicy::new().with_max_attempts(0).is_err());
+    assert!(RetryPolicy::new()
+        .with_delays(Duration::from_secs(1), Duration::from_secs(1), 0.5)
+        .is_err());
+    assert!(RetryPolicy::new()
+        .with_delays(Duration::from_secs(2), Duration::from_secs(1), 2.0)
+        .is_err());
+}
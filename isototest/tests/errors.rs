@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use image::Rgba;
+use isototest::action::view::compare::Region;
+use isototest::action::view::inspect::ColorShare;
+use isototest::errors::needle_errors::NeedleError;
+use isototest::errors::view_errors::{ColorMismatch, ViewError};
+use isototest::Error;
+use vnc::VncError;
+
+#[test]
+fn test_error_from_vnc_error() {
+    assert!(matches!(
+        Error::from(VncError::WrongPassword),
+        Error::AuthenticationError(_)
+    ));
+    assert!(matches!(
+        Error::from(VncError::NoPassword),
+        Error::AuthenticationError(_)
+    ));
+    assert!(matches!(
+        Error::from(VncError::ClientNotRunning),
+        Error::ConnectionError(_)
+    ));
+    assert!(matches!(
+        Error::from(VncError::IoError(
+            std::io::ErrorKind::ConnectionRefused.into()
+        )),
+        Error::ConnectionError(_)
+    ));
+    assert!(matches!(
+        Error::from(VncError::InvalidImageData),
+        Error::ScreenCaptureError(ViewError::ImageError(_))
+    ));
+    match Error::from(VncError::General("unexpected message".to_string())) {
+        Error::ProtocolError(msg) => assert_eq!(msg, "unexpected message"),
+        e => panic!("Expected a protocol error, got {:?}", e),
+    }
+}
+
+#[test]
+fn test_error_from_view_error() {
+    // Failures of the client keep their cause, everything else is a capture failure.
+    assert!(matches!(
+        Error::from(ViewError::ProtocolError(VncError::ClientNotRunning)),
+        Error::ConnectionError(_)
+    ));
+    let err = Error::from(ViewError::Timeout(Duration::from_secs(1)));
+    assert!(matches!(
+        err,
+        Error::ScreenCaptureError(ViewError::Timeout(_))
+    ));
+    assert!(std::error::Error::source(&err).is_some());
+    assert_eq!(err.to_string(), "[error] No screen data received within 1s");
+
+    // A failed colour assertion is not a capture failure.
+    let mismatch = ColorMismatch {
+        region: Region::new(8, 2, 4, 4),
+        expected: ColorShare::new(Rgba([0, 200, 0, 255]), 16)
+            .with_range(90.0, 100.0)
+            .unwrap(),
+        share: 25.0,
+    };
+    let err = Error::from(ViewError::ColorMismatch(Box::new(mismatch.clone())));
+    match &err {
+        Error::ColorMismatch(m) => assert_eq!(**m, mismatch),
+        e => panic!("Expected a colour mismatch, got {:?}", e),
+    }
+    assert_eq!(
+        err.to_string(),
+        "[error] 25.00% of region 4x4+8+2 match colour #00c800 (±16), expected 90% to 100%"
+    );
+}
+
+#[cfg(feature = "default-logging")]
+#[test]
+fn test_error_from_logging_error() {
+    use isototest::errors::util_errors::LoggingError;
+
+    match isototest::init_logging(Some("verbose")) {
+        Err(Error::LoggingError(LoggingError::InvalidLogLevelError(msg))) => {
+            assert!(msg.contains("verbose"))
+        }
+        other => panic!("Expected an invalid log level, got {:?}", other),
+    }
+    isototest::init_logging(Some("debug")).unwrap();
+    // Only one logger can be set up per process.
+    let err = isototest::init_logging(None).unwrap_err();
+    assert!(matches!(
+        err,
+        Error::LoggingError(LoggingError::LoggingInitError(_))
+    ));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn test_error_from_needle_error() {
+    let err = Error::from(NeedleError::ParseError("login: no match area".to_string()));
+    assert!(matches!(
+        err,
+        Error::NeedleError(NeedleError::ParseError(_))
+    ));
+    assert!(std::error::Error::source(&err).is_some());
+    assert_eq!(
+        err.to_string(),
+        "[error] Invalid needle definition: 'login: no match area'"
+    );
+}
@@ -0,0 +1,160 @@
+use image::Rgba;
+use isototest::action::view::compare::Region;
+use isototest::action::view::damage::DamageTracker;
+use isototest::action::view::framebuffer::{to_rgba, Framebuffer};
+use vnc::{PixelFormat, Rect, VncEvent};
+
+fn rect(x: u16, y: u16, width: u16, height: u16) -> Rect {
+    Rect {
+        x,
+        y,
+        width,
+        height,
+    }
+}
+
+#[test]
+fn test_framebuffer_resolution_event() {
+    let mut fb = Framebuffer::default();
+    assert_eq!(fb.resolution(), (0, 0));
+
+    let changed = fb.apply(&VncEvent::SetResolution((8, 4).into())).unwrap();
+    assert!(changed);
+    assert_eq!(fb.resolution(), (8, 4));
+    assert_eq!(fb.generation(), 1);
+
+    assert!(!fb.apply(&VncEvent::Bell).unwrap());
+    assert_eq!(fb.generation(), 1);
+}
+
+#[test]
+fn test_framebuffer_draw_and_clip() {
+    let mut fb = Framebuffer::new(4, 4);
+    // A 3x2 rectangle at (2, 3) only partially fits into the framebuffer.
+    let data: Vec<u8> = (0..6).flat_map(|i| [i * 10, 1, 2, 0]).collect();
+    fb.apply(&VncEvent::RawImage(rect(2, 3, 3, 2), data))
+        .unwrap();
+
+    assert_eq!(fb.image().get_pixel(2, 3), &Rgba([0, 1, 2, 255]));
+    assert_eq!(fb.image().get_pixel(3, 3), &Rgba([10, 1, 2, 255]));
+    assert_eq!(fb.image().get_pixel(1, 3), &Rgba([0, 0, 0, 255]));
+
+    // Truncated data must be rejected.
+    assert!(fb.draw_rect(&rect(0, 0, 2, 2), &[0; 8]).is_err());
+}
+
+#[test]
+fn test_framebuffer_rect_outside_screen() {
+    let mut fb = Framebuffer::new(4, 4);
+    // Rectangles right of the last row, e.g. sent before the screen shrank, are ignored.
+    assert!(!fb
+        .apply(&VncEvent::RawImage(rect(6, 3, 2, 1), vec![255; 8]))
+        .unwrap());
+    assert!(!fb
+        .apply(&VncEvent::Copy(rect(0, 3, 2, 1), rect(6, 3, 2, 1)))
+        .unwrap());
+    assert!(!fb
+        .apply(&VncEvent::Copy(rect(6, 3, 2, 1), rect(0, 3, 2, 1)))
+        .unwrap());
+    assert_eq!(fb.generation(), 0);
+    assert!(fb.damage_since(0).is_empty());
+    assert!(fb.image().pixels().all(|px| *px == Rgba([0, 0, 0, 255])));
+}
+
+#[test]
+fn test_framebuffer_overlapping_copy() {
+    let mut fb = Framebuffer::new(1, 4);
+    for y in 0..3u16 {
+        fb.draw_rect(&rect(0, y, 1, 1), &[y as u8 + 1, 0, 0, 0])
+            .unwrap();
+    }
+
+    // Move the top three rows one row down.
+    fb.apply(&VncEvent::Copy(rect(0, 1, 1, 3), rect(0, 0, 1, 3)))
+        .unwrap();
+    let column: Vec<u8> = (0..4).map(|y| fb.image().get_pixel(0, y)[0]).collect();
+    assert_eq!(column, vec![1, 1, 2, 3]);
+}
+
+#[test]
+fn test_pixel_format_conversion() {
+    // BGRA: red in bits 16..24 of a little endian word.
+    let bgra = PixelFormat::bgra();
+    assert_eq!(
+        to_rgba(&bgra, &[0x30, 0x20, 0x10, 0x00]).unwrap().as_ref(),
+        &[0x10, 0x20, 0x30, 255]
+    );
+
+    // Big endian RGB565.
+    let mut rgb565 = PixelFormat::bgra();
+    rgb565.bits_per_pixel = 16;
+    rgb565.depth = 16;
+    rgb565.big_endian_flag = 1;
+    (rgb565.red_max, rgb565.green_max, rgb565.blue_max) = (31, 63, 31);
+    (rgb565.red_shift, rgb565.green_shift, rgb565.blue_shift) = (11, 5, 0);
+    assert_eq!(
+        to_rgba(&rgb565, &[0xf8, 0x00, 0x07, 0xe0])
+            .unwrap()
+            .as_ref(),
+        &[255, 0, 0, 255, 0, 255, 0, 255]
+    );
+
+    let mut color_map = PixelFormat::rgba();
+    color_map.true_color_flag = 0;
+    assert!(to_rgba(&color_map, &[0; 4]).is_err());
+}
+
+#[test]
+fn test_framebuffer_pixel_format_event() {
+    let mut fb = Framebuffer::new(1, 1);
+    assert!(!fb
+        .apply(&VncEvent::SetPixelFormat(PixelFormat::bgra()))
+        .unwrap());
+    fb.draw_rect(&rect(0, 0, 1, 1), &[0x30, 0x20, 0x10, 0x00])
+        .unwrap();
+    assert_eq!(fb.image().get_pixel(0, 0), &Rgba([0x10, 0x20, 0x30, 255]));
+}
+
+#[test]
+fn test_framebuffer_damage() {
+    let mut fb = Framebuffer::new(64, 32);
+    assert!(fb.damage_since(0).is_empty());
+
+    fb.draw_rect(&rect(0, 0, 4, 4), &[0; 64]).unwrap();
+    let seen = fb.generation();
+    // Overlapping rectangles are merged, distant ones reported separately.
+    fb.draw_rect(&rect(10, 10, 4, 4), &[0; 64]).unwrap();
+    fb.draw_rect(&rect(12, 12, 4, 4), &[0; 64]).unwrap();
+    fb.apply(&VncEvent::Copy(rect(40, 0, 8, 8), rect(0, 0, 8, 8)))
+        .unwrap();
+    let mut damage = fb.damage_since(seen);
+    damage.sort_by_key(|r| r.x);
+    assert_eq!(
+        damage,
+        vec![Region::new(10, 10, 6, 6), Region::new(40, 0, 8, 8)]
+    );
+    assert_eq!(fb.damage_since(0).len(), 3);
+    assert!(fb.damage_since(fb.generation()).is_empty());
+
+    // Rectangles are clipped to the screen.
+    let seen = fb.generation();
+    fb.draw_rect(&rect(60, 30, 8, 8), &[0; 256]).unwrap();
+    assert_eq!(fb.damage_since(seen), vec![Region::new(60, 30, 4, 2)]);
+
+    // A new resolution changes the whole screen.
+    fb.apply(&VncEvent::SetResolution((16, 8).into())).unwrap();
+    assert_eq!(fb.damage_since(seen), vec![Region::new(0, 0, 16, 8)]);
+    assert!(fb.damage_since(fb.generation()).is_empty());
+}
+
+#[test]
+fn test_damage_history_limit() {
+    let mut damage = DamageTracker::new(32, 32, 2);
+    for generation in 1..=3 {
+        damage.add(generation, Region::new(generation as u32 * 8, 0, 2, 2));
+    }
+    // The change of generation 1 is forgotten, so older queries cover the whole screen.
+    assert_eq!(damage.since(0), vec![Region::new(0, 0, 32, 32)]);
+    assert_eq!(damage.since(1).len(), 2);
+    assert_eq!(damage.since(2), vec![Region::new(24, 0, 2, 2)]);
+}
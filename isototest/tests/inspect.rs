@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::action::view::compare::Region;
+use isototest::action::view::inspect::{
+    assert_colors, capture_region, color_histogram, color_share, pixel_at, region_mean_color,
+    ColorShare,
+};
+use isototest::connection::create_vnc_client;
+use isototest::errors::view_errors::ViewError;
+use isototest::session::Session;
+use isototest::Error;
+
+mod common;
+use common::start_mock_server;
+
+const GREEN: Rgba<u8> = Rgba([0, 200, 0, 255]);
+const GREY: Rgba<u8> = Rgba([100, 100, 100, 255]);
+
+/// A grey screen with a green "LED" of 4x4 pixels at (8, 2).
+fn screen() -> RgbaImage {
+    RgbaImage::from_fn(32, 16, |x, y| {
+        if (8..12).contains(&x) && (2..6).contains(&y) {
+            GREEN
+        } else {
+            GREY
+        }
+    })
+}
+
+#[test]
+fn test_capture_region() {
+    let screen = screen();
+    let led = capture_region(&screen, Region::new(8, 2, 4, 4)).unwrap();
+    assert_eq!(led, RgbaImage::from_pixel(4, 4, GREEN));
+    assert_eq!(pixel_at(&screen, 9, 3).unwrap(), GREEN);
+    assert_eq!(pixel_at(&screen, 31, 15).unwrap(), GREY);
+
+    // Regions beyond the screen are rejected.
+    for region in [
+        Region::new(30, 0, 4, 4),
+        Region::new(0, 16, 1, 1),
+        Region::new(0, 0, 0, 4),
+        Region::new(u32::MAX, 0, 2, 2),
+    ] {
+        match capture_region(&screen, region) {
+            Err(ViewError::OutOfBounds(r, resolution)) => {
+                assert_eq!((r, resolution), (region, (32, 16)))
+            }
+            other => panic!("Expected an out of bounds error, got {:?}", other),
+        }
+    }
+    assert!(matches!(
+        pixel_at(&screen, 32, 0),
+        Err(ViewError::OutOfBounds(..))
+    ));
+    assert_eq!(
+        capture_region(&screen, Region::new(30, 0, 4, 4))
+            .unwrap_err()
+            .to_string(),
+        "[error] Region 4x4+30+0 is not within the screen resolution 32x16"
+    );
+}
+
+#[test]
+fn test_region_colors() {
+    let screen = screen();
+    // A quarter of the region is green.
+    let region = Region::new(8, 2, 8, 8);
+    assert_eq!(
+        region_mean_color(&screen, region).unwrap(),
+        Rgba([75, 125, 75, 255])
+    );
+    assert_eq!(
+        color_histogram(&screen, region).unwrap(),
+        vec![(GREY, 48), (GREEN, 16)]
+    );
+    assert_eq!(color_share(&screen, region, GREEN, 0).unwrap(), 25.0);
+    assert_eq!(
+        color_share(&screen, region, Rgba([10, 190, 10, 255]), 10).unwrap(),
+        25.0
+    );
+    assert_eq!(
+        color_share(&screen, region, Rgba([10, 190, 10, 255]), 9).unwrap(),
+        0.0
+    );
+    assert!(region_mean_color(&screen, Region::new(0, 0, 33, 1)).is_err());
+}
+
+#[test]
+fn test_assert_colors() {
+    let screen = screen();
+    let led = Region::new(6, 0, 8, 8);
+    let green = ColorShare::new(Rgba([0, 190, 10, 255]), 16);
+    let red = ColorShare::new(Rgba([200, 0, 0, 255]), 16)
+        .with_range(0.0, 0.0)
+        .unwrap();
+
+    assert_colors(&screen, led, &[green.with_range(20.0, 30.0).unwrap(), red]).unwrap();
+    match assert_colors(&screen, led, &[red, green.with_range(50.0, 100.0).unwrap()]) {
+        Err(ViewError::ColorMismatch(mismatch)) => {
+            assert_eq!(mismatch.region, led);
+            assert_eq!(mismatch.share, 25.0);
+            assert_eq!(mismatch.expected.min, 50.0);
+        }
+        other => panic!("Expected a colour mismatch, got {:?}", other),
+    }
+
+    assert!(matches!(
+        green.with_range(50.0, 20.0),
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(matches!(
+        green.with_range(0.0, 101.0),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_session_inspection() {
+    let screen = screen();
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != screen {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(session.pixel_at(10, 4).unwrap(), GREEN);
+    assert_eq!(
+        session.region_mean_color(Region::new(8, 2, 4, 4)).unwrap(),
+        GREEN
+    );
+    assert_eq!(
+        session.capture_region(Region::new(0, 0, 4, 2)).unwrap(),
+        RgbaImage::from_pixel(4, 2, GREY)
+    );
+    session
+        .assert_colors(
+            Region::new(8, 2, 4, 4),
+            &[ColorShare::new(GREEN, 0).with_range(100.0, 100.0).unwrap()],
+        )
+        .unwrap();
+    match session.assert_colors(
+        Region::new(8, 2, 4, 4),
+        &[ColorShare::new(GREY, 0).with_range(50.0, 100.0).unwrap()],
+    ) {
+        Err(Error::ColorMismatch(mismatch)) => assert_eq!(mismatch.share, 0.0),
+        other => panic!("Expected a colour mismatch, got {:?}", other),
+    }
+    assert!(matches!(
+        session.capture_region(Region::new(16, 8, 17, 8)),
+        Err(Error::ScreenCaptureError(ViewError::OutOfBounds(..)))
+    ));
+
+    session.close().await.unwrap();
+}
@@ -0,0 +1,445 @@
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::action::keyboard::layout::{KeyStroke, KeyboardLayout};
+use isototest::action::keyboard::speed::TypingSpeed;
+use isototest::action::keyboard::{
+    parse_key_spec, send_key, send_key_until_needlematch, type_string, write_to_console,
+};
+use isototest::action::view::compare::Region;
+use isototest::action::view::needle::Needle;
+use isototest::connection::create_vnc_client;
+use isototest::keysym;
+use isototest::session::Session;
+use isototest::Error;
+
+mod common;
+use common::{start_mock_server, ClientMessage, ServerCommand};
+
+const CTRL: u32 = 0xffe3;
+const ALT: u32 = 0xffe9;
+const F2: u32 = 0xffbf;
+
+#[test]
+fn test_parse_key_spec() {
+    assert_eq!(
+        parse_key_spec("ctrl-alt-f2", KeyboardLayout::Us).unwrap(),
+        vec![CTRL, ALT, F2]
+    );
+    assert_eq!(
+        parse_key_spec("Super-Up", KeyboardLayout::Us).unwrap(),
+        vec![0xffeb, 0xff52]
+    );
+    assert_eq!(
+        parse_key_spec("kp_5", KeyboardLayout::Us).unwrap(),
+        vec![0xffb5]
+    );
+    assert_eq!(
+        parse_key_spec("ctrl-c", KeyboardLayout::Us).unwrap(),
+        vec![CTRL, 'c' as u32]
+    );
+    assert_eq!(
+        parse_key_spec("-", KeyboardLayout::Us).unwrap(),
+        vec!['-' as u32]
+    );
+    assert_eq!(
+        parse_key_spec("ctrl-minus", KeyboardLayout::Us).unwrap(),
+        vec![CTRL, '-' as u32]
+    );
+
+    assert_eq!(
+        parse_key_spec("Henkan", KeyboardLayout::Us).unwrap(),
+        vec![keysym::XK_Henkan]
+    );
+
+    assert!(matches!(
+        parse_key_spec("ctrl-foo", KeyboardLayout::Us),
+        Err(Error::KeyboardMappingError(_))
+    ));
+    assert!(parse_key_spec("ctrl--", KeyboardLayout::Us).is_err());
+    assert!(parse_key_spec("", KeyboardLayout::Us).is_err());
+}
+
+#[test]
+fn test_keysym_lookup() {
+    assert_eq!(keysym::from_char('a'), Some(0x61));
+    assert_eq!(keysym::from_char('ä'), Some(keysym::XK_adiaeresis));
+    assert_eq!(keysym::from_char('ő'), Some(keysym::XK_odoubleacute));
+    assert_eq!(keysym::from_char('€'), Some(keysym::XK_EuroSign));
+    assert_eq!(keysym::from_char('中'), Some(0x0100_4e2d));
+    assert_eq!(keysym::from_char('\n'), Some(keysym::XK_Return));
+    assert_eq!(keysym::from_char('\x03'), None);
+
+    assert_eq!(keysym::from_name("Return"), Some(0xff0d));
+    assert_eq!(keysym::from_name("dead_acute"), Some(keysym::XK_dead_acute));
+    assert_eq!(keysym::from_name("U4E2D"), Some(0x0100_4e2d));
+    assert_eq!(keysym::from_name("return"), None);
+}
+
+const SHIFT: u32 = 0xffe1;
+const ALTGR: u32 = 0xffea;
+
+fn stroke(layout: KeyboardLayout, c: char) -> (u32, Vec<u32>, bool) {
+    let KeyStroke {
+        keysym,
+        modifiers,
+        dead,
+    } = layout.lookup(c).unwrap();
+    (keysym, modifiers.to_vec(), dead)
+}
+
+#[test]
+fn test_keyboard_layouts() {
+    let us = KeyboardLayout::Us;
+    assert_eq!(stroke(us, 'A'), ('A' as u32, vec![SHIFT], false));
+    assert_eq!(stroke(us, 'ä'), (keysym::XK_adiaeresis, vec![], false));
+
+    let de = KeyboardLayout::from_name("de").unwrap();
+    assert_eq!(stroke(de, 'z'), ('y' as u32, vec![], false));
+    assert_eq!(stroke(de, 'Z'), ('y' as u32, vec![SHIFT], false));
+    assert_eq!(stroke(de, 'ä'), ('\'' as u32, vec![], false));
+    assert_eq!(stroke(de, '/'), ('7' as u32, vec![SHIFT], false));
+    assert_eq!(stroke(de, '_'), ('/' as u32, vec![SHIFT], false));
+    assert_eq!(stroke(de, '@'), ('q' as u32, vec![ALTGR], false));
+    assert_eq!(stroke(de, '|'), (keysym::XK_less, vec![ALTGR], false));
+    assert_eq!(stroke(de, '^'), ('`' as u32, vec![], true));
+
+    let fr = KeyboardLayout::from_name("fr").unwrap();
+    assert_eq!(stroke(fr, 'a'), ('q' as u32, vec![], false));
+    assert_eq!(stroke(fr, '1'), ('1' as u32, vec![SHIFT], false));
+    assert_eq!(stroke(fr, '!'), ('/' as u32, vec![], false));
+    assert_eq!(stroke(fr, '}'), ('=' as u32, vec![ALTGR], false));
+
+    let cz = KeyboardLayout::from_name("cz").unwrap();
+    assert_eq!(stroke(cz, 'ř'), ('5' as u32, vec![], false));
+    assert_eq!(stroke(cz, 'Ř'), ('5' as u32, vec![SHIFT, ALTGR], false));
+    assert_eq!(stroke(cz, '\\'), (keysym::XK_less, vec![], false));
+
+    let jp = KeyboardLayout::from_name("jp").unwrap();
+    assert_eq!(stroke(jp, '@'), ('[' as u32, vec![], false));
+    assert_eq!(stroke(jp, '?'), ('/' as u32, vec![SHIFT], false));
+    assert_eq!(stroke(jp, '中'), (0x0100_4e2d, vec![], false));
+    // The JIS keys for these characters are not reachable through the en-us keymap.
+    for c in ['\\', '|', '_'] {
+        assert_eq!(jp.lookup(c), None);
+    }
+    assert!(matches!(
+        parse_key_spec("shift-_", jp),
+        Err(Error::KeyboardMappingError(_))
+    ));
+
+    // Control characters use the letter's position on the layout.
+    assert_eq!(stroke(de, '\x19'), ('z' as u32, vec![0xffe3], false));
+    assert!(KeyboardLayout::from_name("xx").is_none());
+
+    assert_eq!(
+        parse_key_spec("ctrl-z", de).unwrap(),
+        vec![0xffe3, 'y' as u32]
+    );
+    assert_eq!(parse_key_spec("minus", de).unwrap(), vec!['/' as u32]);
+}
+
+#[tokio::test]
+async fn test_write_with_layout() {
+    let mut server = start_mock_server(RgbaImage::new(8, 8)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    write_to_console(&client, "Z^".to_string(), KeyboardLayout::De, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        server.collect_keys().await,
+        vec![
+            (SHIFT, true),
+            ('y' as u32, true),
+            ('y' as u32, false),
+            (SHIFT, false),
+            ('`' as u32, true),
+            ('`' as u32, false),
+            (keysym::XK_space, true),
+            (keysym::XK_space, false),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_write_unicode_text() {
+    let mut server = start_mock_server(RgbaImage::new(8, 8)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    write_to_console(&client, "é中\x03".to_string(), KeyboardLayout::Us, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        server.collect_keys().await,
+        vec![
+            (keysym::XK_eacute, true),
+            (keysym::XK_eacute, false),
+            (0x0100_4e2d, true),
+            (0x0100_4e2d, false),
+            (keysym::XK_Control_L, true),
+            (keysym::XK_c, true),
+            (keysym::XK_c, false),
+            (keysym::XK_Control_L, false),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_send_key_releases_in_reverse_order() {
+    let mut server = start_mock_server(RgbaImage::new(8, 8)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    send_key(&client, "ctrl-alt-f2", KeyboardLayout::Us, None)
+        .await
+        .unwrap();
+    assert_eq!(
+        server.collect_keys().await,
+        vec![
+            (CTRL, true),
+            (ALT, true),
+            (F2, true),
+            (F2, false),
+            (ALT, false),
+            (CTRL, false)
+        ]
+    );
+
+    // Invalid specifications must not send anything.
+    assert!(send_key(&client, "ctrl-nokey", KeyboardLayout::Us, None)
+        .await
+        .is_err());
+    assert!(server.collect_keys().await.is_empty());
+}
+
+#[tokio::test]
+async fn test_write_stops_when_connection_drops() {
+    let mut server = start_mock_server(RgbaImage::new(8, 8)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    // Drop the connection while Shift is held for the first character.
+    let control = server.control.clone();
+    let keys = tokio::spawn(async move {
+        let mut keys = Vec::new();
+        while let Some(msg) = server.messages.recv().await {
+            if let ClientMessage::Key { keysym, down } = msg {
+                keys.push((keysym, down));
+                control.send(ServerCommand::Disconnect).unwrap();
+            }
+        }
+        keys
+    });
+
+    // Slow typing gives the client time to notice the closed connection.
+    let speed = TypingSpeed::new(2.0).unwrap();
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        write_to_console(&client, "AB".to_string(), KeyboardLayout::Us, Some(speed)),
+    )
+    .await
+    .unwrap();
+    assert!(result.is_err());
+    assert_eq!(keys.await.unwrap(), vec![(SHIFT, true)]);
+
+    // Later key combinations fail as well instead of pressing further modifiers.
+    assert!(send_key(&client, "ctrl-c", KeyboardLayout::Us, None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_send_key_until_needlematch_gives_up() {
+    let mut server = start_mock_server(RgbaImage::from_pixel(16, 16, Rgba([0, 0, 0, 255]))).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+
+    let json = r#"{"tags": ["menu"], "area": [{"xpos": 0, "ypos": 0, "width": 8, "height": 8, "type": "match"}]}"#;
+    let needle = Needle::from_parts(
+        "menu".to_string(),
+        json,
+        RgbaImage::from_pixel(16, 16, Rgba([255, 255, 255, 255])),
+    )
+    .unwrap();
+
+    let result = send_key_until_needlematch(
+        &session,
+        &[needle],
+        &["menu"],
+        "down",
+        3,
+        Duration::from_millis(50),
+        None,
+    )
+    .await;
+    match result {
+        Err(Error::NeedleMismatch(mismatch)) => {
+            assert_eq!(mismatch.closest.unwrap().needle, "menu");
+        }
+        _ => panic!("Expected a screen mismatch"),
+    }
+
+    // The session keeps requesting screen updates, so drain the messages received so far.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let mut down = Vec::new();
+    while let Ok(msg) = server.messages.try_recv() {
+        if let ClientMessage::Key { keysym, down: true } = msg {
+            down.push(keysym);
+        }
+    }
+    assert_eq!(down, vec![0xff54; 3]);
+}
+
+#[test]
+fn test_typing_speed_validation() {
+    assert_eq!(
+        TypingSpeed::default().key_interval(),
+        Duration::from_millis(20)
+    );
+    assert!(TypingSpeed::new(0.0).is_err());
+    assert!(TypingSpeed::new(-3.0).is_err());
+    assert!(TypingSpeed::new(f64::NAN).is_err());
+    // The delays would not fit into a `Duration`.
+    assert!(matches!(
+        TypingSpeed::new(1e-300),
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(TypingSpeed::new(f64::MIN_POSITIVE).is_err());
+    assert!(TypingSpeed::new(10.0).unwrap().with_jitter(1.5).is_err());
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_typing_speed_delays() {
+    // The paused clock only advances while the runtime is waiting on timers.
+    let speed = TypingSpeed::new(10.0).unwrap();
+    let start = tokio::time::Instant::now();
+    for _ in 0..4 {
+        speed.wait().await;
+    }
+    assert_eq!(start.elapsed(), Duration::from_millis(200));
+
+    let jittery = speed.with_jitter(0.5).unwrap();
+    let delays: Vec<Duration> = (0..100).map(|_| jittery.next_delay()).collect();
+    assert!(delays
+        .iter()
+        .all(|d| *d >= Duration::from_millis(25) && *d <= Duration::from_millis(75)));
+    assert!(delays.iter().any(|d| *d != delays[0]));
+
+    let start = tokio::time::Instant::now();
+    for _ in 0..10 {
+        jittery.wait().await;
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(250) && elapsed <= Duration::from_millis(750));
+}
+
+#[tokio::test]
+async fn test_type_string_waits_for_echo() {
+    let mut server = start_mock_server(RgbaImage::new(8, 8)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+
+    // Echo every key press by changing the screen.
+    let control = server.control.clone();
+    let echo = tokio::spawn(async move {
+        let mut typed = String::new();
+        while let Some(msg) = server.messages.recv().await {
+            if let ClientMessage::Key { keysym, down: true } = msg {
+                typed.push(char::from_u32(keysym).unwrap());
+                let shade = typed.len() as u8 * 10;
+                let screen = RgbaImage::from_pixel(8, 8, Rgba([shade, 0, 0, 255]));
+                control.send(ServerCommand::SetScreen(screen)).unwrap();
+            }
+            if typed == "echo hello" {
+                break;
+            }
+        }
+        typed
+    });
+
+    type_string(&session, "echo hello", 3, Duration::from_secs(5), &[], None)
+        .await
+        .unwrap();
+    let typed = tokio::time::timeout(Duration::from_secs(5), echo)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(typed, "echo hello");
+}
+
+#[tokio::test]
+async fn test_type_string_detects_stalled_screen() {
+    let screen = RgbaImage::from_pixel(8, 8, Rgba([1, 2, 3, 255]));
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != screen {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    let err = type_string(&session, "ls -l", 2, Duration::from_millis(300), &[], None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::Timeout(_)));
+    assert!(err.to_string().contains("Screen did not change"));
+    assert!(err.to_string().contains("2 of 5 characters"));
+    assert!(matches!(
+        type_string(&session, "ls", 0, Duration::ZERO, &[], None).await,
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_type_string_ignores_blinking_cursor() {
+    let screen = RgbaImage::from_pixel(8, 8, Rgba([1, 2, 3, 255]));
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != screen {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+
+    // Keep redrawing a "cursor" of 2x2 pixels, while the typed text is never echoed.
+    let control = server.control.clone();
+    let blink = tokio::spawn(async move {
+        loop {
+            if control
+                .send(ServerCommand::CopyRect((0, 0, 2, 2), (4, 4)))
+                .is_err()
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    });
+
+    // Without ignoring the cursor, its changes are taken for the echo.
+    type_string(&session, "ls", 1, Duration::from_millis(300), &[], None)
+        .await
+        .unwrap();
+    let cursor = Region::new(4, 4, 2, 2);
+    assert!(matches!(
+        type_string(
+            &session,
+            "ls",
+            1,
+            Duration::from_millis(300),
+            &[cursor],
+            None
+        )
+        .await,
+        Err(Error::Timeout(_))
+    ));
+    blink.abort();
+}
@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use image::RgbaImage;
+use isototest::action::mouse::{
+    mouse_click, mouse_drag, mouse_hide, mouse_move, mouse_scroll, MouseButton, ScrollDirection,
+};
+use isototest::connection::create_vnc_client;
+use isototest::Error;
+
+mod common;
+use common::{start_mock_server, ClientMessage, ServerCommand};
+
+const RESOLUTION: (u32, u32) = (64, 48);
+
+fn is_pointer(msg: &ClientMessage) -> bool {
+    matches!(msg, ClientMessage::Pointer { .. })
+}
+
+#[tokio::test]
+async fn test_mouse_click_and_drag() {
+    let mut server = start_mock_server(RgbaImage::new(RESOLUTION.0, RESOLUTION.1)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    mouse_click(&client, RESOLUTION, 10, 20, MouseButton::Right)
+        .await
+        .unwrap();
+    let mut masks = Vec::new();
+    for _ in 0..3 {
+        match server.expect(is_pointer).await {
+            ClientMessage::Pointer { x, y, buttons } => {
+                assert_eq!((x, y), (10, 20));
+                masks.push(buttons);
+            }
+            _ => unreachable!(),
+        }
+    }
+    assert_eq!(masks, vec![0, 4, 0]);
+
+    mouse_drag(&client, RESOLUTION, (1, 2), (30, 40), MouseButton::Left)
+        .await
+        .unwrap();
+    let mut events = Vec::new();
+    for _ in 0..4 {
+        events.push(server.expect(is_pointer).await);
+    }
+    assert_eq!(
+        events,
+        vec![
+            ClientMessage::Pointer {
+                x: 1,
+                y: 2,
+                buttons: 0
+            },
+            ClientMessage::Pointer {
+                x: 1,
+                y: 2,
+                buttons: 1
+            },
+            ClientMessage::Pointer {
+                x: 30,
+                y: 40,
+                buttons: 1
+            },
+            ClientMessage::Pointer {
+                x: 30,
+                y: 40,
+                buttons: 0
+            },
+        ]
+    );
+
+    mouse_scroll(&client, RESOLUTION, 5, 5, ScrollDirection::Down, 1)
+        .await
+        .unwrap();
+    assert_eq!(
+        server.expect(is_pointer).await,
+        ClientMessage::Pointer {
+            x: 5,
+            y: 5,
+            buttons: 16
+        }
+    );
+
+    mouse_hide(&client, RESOLUTION).await.unwrap();
+    server
+        .expect(|m| {
+            *m == ClientMessage::Pointer {
+                x: 63,
+                y: 47,
+                buttons: 0,
+            }
+        })
+        .await;
+}
+
+#[tokio::test]
+async fn test_mouse_drag_stops_when_connection_drops() {
+    let mut server = start_mock_server(RgbaImage::new(RESOLUTION.0, RESOLUTION.1)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    // Drop the connection once the button is pressed.
+    let control = server.control.clone();
+    let events = tokio::spawn(async move {
+        let mut events = Vec::new();
+        while let Some(msg) = server.messages.recv().await {
+            if let ClientMessage::Pointer { buttons, .. } = msg {
+                events.push(msg);
+                if buttons != 0 {
+                    control.send(ServerCommand::Disconnect).unwrap();
+                }
+            }
+        }
+        events
+    });
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        mouse_drag(&client, RESOLUTION, (1, 2), (30, 40), MouseButton::Left),
+    )
+    .await
+    .unwrap();
+    assert!(result.is_err());
+    assert_eq!(
+        events.await.unwrap(),
+        vec![
+            ClientMessage::Pointer {
+                x: 1,
+                y: 2,
+                buttons: 0
+            },
+            ClientMessage::Pointer {
+                x: 1,
+                y: 2,
+                buttons: 1
+            },
+        ]
+    );
+
+    // Later pointer events fail as well instead of pressing further buttons.
+    assert!(mouse_click(&client, RESOLUTION, 10, 20, MouseButton::Left)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_mouse_validates_position() {
+    let server = start_mock_server(RgbaImage::new(RESOLUTION.0, RESOLUTION.1)).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    assert!(matches!(
+        mouse_move(&client, RESOLUTION, 64, 0).await,
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(mouse_move(&client, RESOLUTION, 0, 48).await.is_err());
+    assert!(mouse_hide(&client, (0, 0)).await.is_err());
+    assert!(mouse_move(&client, RESOLUTION, 63, 47).await.is_ok());
+}
@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::action::view::needle::{
+    find_needle, load_needles, match_needle, search_needles, AreaType, Needle,
+};
+use isototest::action::view::{assert_screen, check_screen};
+use isototest::connection::create_vnc_client;
+use isototest::errors::needle_errors::NeedleError;
+use isototest::session::Session;
+use isototest::Error;
+
+mod common;
+use common::{start_mock_server, MockServer, ServerCommand};
+
+/// Create a noisy test image, so displaced areas cannot match by accident.
+fn pattern(width: u32, height: u32, seed: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        let v = (x.wrapping_mul(31) ^ y.wrapping_mul(17) ^ seed).wrapping_mul(2654435761) >> 24;
+        Rgba([v as u8, (v >> 1) as u8, (255 - v) as u8, 255])
+    })
+}
+
+/// Copy `src` onto `dst` at the given offset.
+fn paste(dst: &mut RgbaImage, src: &RgbaImage, ox: u32, oy: u32) {
+    for (x, y, px) in src.enumerate_pixels() {
+        if x + ox < dst.width() && y + oy < dst.height() {
+            dst.put_pixel(x + ox, y + oy, *px);
+        }
+    }
+}
+
+const NEEDLE_JSON: &str = r#"{
+    "tags": ["login", "desktop"],
+    "area": [{ "xpos": 20, "ypos": 10, "width": 16, "height": 12, "type": "match", "match": 90 }],
+    "properties": ["glyph", { "name": "workaround", "value": "bsc#1" }]
+}"#;
+
+#[test]
+fn test_needle_exact_match() {
+    let screen = pattern(64, 48, 1);
+    let needle = Needle::from_parts("login".to_string(), NEEDLE_JSON, screen.clone()).unwrap();
+
+    assert_eq!(needle.properties, vec!["glyph", "workaround"]);
+    assert_eq!(needle.areas[0].kind, AreaType::Match);
+
+    let result = match_needle(&screen, &needle);
+    assert!(result.is_match());
+    assert_eq!(result.similarity, 100.0);
+    assert_eq!((result.areas[0].x, result.areas[0].y), (20, 10));
+}
+
+#[test]
+fn test_needle_displaced_match() {
+    let reference = pattern(64, 48, 1);
+    let needle = Needle::from_parts("login".to_string(), NEEDLE_JSON, reference.clone()).unwrap();
+
+    // Move the content of the match area 5 pixels right and 3 pixels down.
+    let mut screen = pattern(64, 48, 2);
+    let area = image::imageops::crop_imm(&reference, 20, 10, 16, 12).to_image();
+    paste(&mut screen, &area, 25, 13);
+
+    let result = match_needle(&screen, &needle);
+    assert!(result.is_match());
+    assert_eq!((result.areas[0].x, result.areas[0].y), (25, 13));
+}
+
+#[test]
+fn test_needle_exclude_area() {
+    let json = r#"{
+        "tags": ["clock"],
+        "area": [
+            { "xpos": 0, "ypos": 0, "width": 32, "height": 32, "type": "match" },
+            { "xpos": 8, "ypos": 8, "width": 8, "height": 8, "type": "exclude" }
+        ]
+    }"#;
+    let reference = pattern(32, 32, 3);
+    let needle = Needle::from_parts("clock".to_string(), json, reference.clone()).unwrap();
+
+    let mut screen = reference.clone();
+    paste(&mut screen, &pattern(8, 8, 4), 8, 8);
+
+    assert!(match_needle(&screen, &needle).is_match());
+}
+
+#[test]
+fn test_needle_mismatch_reports_closest() {
+    let reference = pattern(64, 48, 1);
+    let needle = Needle::from_parts("login".to_string(), NEEDLE_JSON, reference).unwrap();
+    let other = Needle::from_parts("other".to_string(), NEEDLE_JSON, pattern(64, 48, 7)).unwrap();
+    let screen = pattern(64, 48, 5);
+
+    let needles = [needle, other];
+    assert!(find_needle(&screen, &needles, &["login"]).is_none());
+
+    let results = search_needles(&screen, &needles, &["desktop"]);
+    assert_eq!(results.len(), 2);
+    assert!(!results[0].is_match());
+    assert!(results[0].similarity >= results[1].similarity);
+    assert!(search_needles(&screen, &needles, &["unknown"]).is_empty());
+}
+
+#[test]
+fn test_load_needles_from_dir() {
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("isototest-needles-{}", std::process::id()));
+    let sub = dir.join("boot");
+    fs::create_dir_all(&sub).unwrap();
+
+    fs::write(sub.join("login.json"), NEEDLE_JSON).unwrap();
+    pattern(64, 48, 1).save(sub.join("login.png")).unwrap();
+
+    let needles = load_needles(&dir).unwrap();
+    assert_eq!(needles.len(), 1);
+    assert_eq!(needles[0].name, "login");
+    assert_eq!(needles[0].tags, vec!["login", "desktop"]);
+
+    // An area outside of the image must be rejected.
+    fs::write(
+        sub.join("broken.json"),
+        r#"{ "tags": ["x"], "area": [{ "xpos": 60, "ypos": 0, "width": 10, "height": 10 }] }"#,
+    )
+    .unwrap();
+    pattern(64, 48, 1).save(sub.join("broken.png")).unwrap();
+    assert!(matches!(
+        load_needles(&dir),
+        Err(Error::NeedleError(NeedleError::InvalidArea(_)))
+    ));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_needle_area_overflow() {
+    let json = r#"{ "tags": ["x"], "area": [{ "xpos": 4294967295, "ypos": 0, "width": 2, "height": 2 }] }"#;
+    assert!(matches!(
+        Needle::from_parts("overflow".to_string(), json, pattern(64, 48, 1)),
+        Err(Error::NeedleError(NeedleError::InvalidArea(_)))
+    ));
+}
+
+/// Start a session on a mock server and wait until the initial screen has arrived.
+async fn start_session(screen: &RgbaImage) -> (MockServer, Session) {
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != *screen {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    (server, session)
+}
+
+/// The needles `login` and `other`, which both carry the tag `desktop`.
+fn needles() -> Vec<Needle> {
+    vec![
+        Needle::from_parts("login".to_string(), NEEDLE_JSON, pattern(64, 48, 1)).unwrap(),
+        Needle::from_parts("other".to_string(), NEEDLE_JSON, pattern(64, 48, 7)).unwrap(),
+    ]
+}
+
+#[tokio::test]
+async fn test_assert_screen_after_update() {
+    let (server, session) = start_session(&pattern(64, 48, 5)).await;
+    let needles = needles();
+    assert!(check_screen(&session, &needles, &["login"], Duration::ZERO)
+        .await
+        .unwrap()
+        .is_none());
+
+    // The login screen only appears after a while.
+    let control = server.control.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        control
+            .send(ServerCommand::SetScreen(pattern(64, 48, 1)))
+            .unwrap();
+    });
+    let found = assert_screen(&session, &needles, &["login"], Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(found.needle, "login");
+    assert_eq!(found.similarity, 100.0);
+
+    let found = session
+        .check_screen(&needles, &["desktop"], Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(found.unwrap().needle, "login");
+    session.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_check_screen_timeout() {
+    let (_server, session) = start_session(&pattern(64, 48, 5)).await;
+    let needles = needles();
+
+    let start = tokio::time::Instant::now();
+    let found = check_screen(&session, &needles, &["login"], Duration::from_millis(300))
+        .await
+        .unwrap();
+    assert!(found.is_none());
+    assert!(start.elapsed() >= Duration::from_millis(300));
+
+    // Needles without any of the tags are never considered.
+    let found = session
+        .check_screen(&needles, &["unknown"], Duration::from_millis(100))
+        .await
+        .unwrap();
+    assert!(found.is_none());
+    session.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_assert_screen_mismatch() {
+    let screen = pattern(64, 48, 5);
+    let (_server, session) = start_session(&screen).await;
+    let needles = needles();
+
+    match session
+        .assert_screen(&needles, &["desktop"], Duration::from_millis(300))
+        .await
+    {
+        Err(Error::NeedleMismatch(mismatch)) => {
+            assert_eq!(mismatch.tags, vec!["desktop"]);
+            assert_eq!(mismatch.last_frame, screen);
+            let closest = mismatch.closest.unwrap();
+            assert!(["login", "other"].contains(&closest.needle.as_str()));
+            assert!(!closest.is_match());
+            let best = search_needles(&screen, &needles, &["desktop"]);
+            assert_eq!(closest.similarity, best[0].similarity);
+        }
+        other => panic!(
+            "Expected a needle mismatch, got {:?}",
+            other.map(|m| m.needle)
+        ),
+    }
+    session.close().await.unwrap();
+}
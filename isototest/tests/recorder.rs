@@ -0,0 +1,129 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::action::view::recorder::{Recorder, RecordingConfig, RecordingIndex};
+use isototest::connection::create_vnc_client;
+use isototest::session::Session;
+use isototest::Error;
+
+mod common;
+use common::{start_mock_server, ServerCommand};
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap())
+}
+
+/// Wait until the session shows the expected screen.
+async fn wait_for_screen(session: &Session, expected: &RgbaImage) {
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != *expected {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_record_session() {
+    let first = RgbaImage::from_pixel(32, 16, Rgba([200, 40, 40, 255]));
+    let second = RgbaImage::from_pixel(32, 16, Rgba([40, 40, 200, 255]));
+    let server = start_mock_server(first.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+    wait_for_screen(&session, &first).await;
+
+    let dir = std::env::temp_dir().join(format!("isototest-recorder-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("test.avi");
+    let config = RecordingConfig::new().with_fps(20).unwrap();
+    let mut recorder = Recorder::start(&session, &path, &config).unwrap();
+
+    recorder.mark_step("boot");
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    recorder.mark_step("login");
+    server
+        .control
+        .send(ServerCommand::SetScreen(second.clone()))
+        .unwrap();
+    wait_for_screen(&session, &second).await;
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let index = recorder.stop().await.unwrap();
+    session.close().await.unwrap();
+
+    assert_eq!(index.video, "test.avi");
+    assert_eq!(index.fps, 20);
+    assert!(index.frames >= 10);
+    // Both screens are encoded once, repeated frames only refer to them.
+    assert!(index.unique_frames >= 2 && index.unique_frames < index.frames);
+    assert_eq!(index.duration, index.frames as f64 / 20.0);
+    let names: Vec<&str> = index.steps.iter().map(|step| step.name.as_str()).collect();
+    assert_eq!(names, ["boot", "login"]);
+    assert!(index.steps[0].offset < index.steps[1].offset);
+    assert!(index.steps[1].frame >= 5);
+
+    let json = std::fs::read_to_string(RecordingIndex::path_for(&path)).unwrap();
+    assert_eq!(
+        serde_json::from_str::<RecordingIndex>(&json).unwrap(),
+        index
+    );
+
+    let data = std::fs::read(&path).unwrap();
+    assert_eq!(&data[0..4], b"RIFF");
+    assert_eq!(u32_at(&data, 4) as usize, data.len() - 8);
+    assert_eq!(&data[8..12], b"AVI ");
+    // Resolution and number of frames in the main header.
+    assert_eq!(u32_at(&data, 48) as u64, index.frames);
+    assert_eq!((u32_at(&data, 64), u32_at(&data, 68)), (32, 16));
+    assert_eq!(&data[220..224], b"movi");
+
+    let movi_size = u32_at(&data, 216) as usize;
+    let idx1 = 216 + 4 + movi_size;
+    assert_eq!(&data[idx1..idx1 + 4], b"idx1");
+    let entries: Vec<(usize, usize)> = data[idx1 + 8..]
+        .chunks_exact(16)
+        .map(|entry| {
+            assert_eq!(&entry[0..4], b"00dc");
+            (u32_at(entry, 8) as usize, u32_at(entry, 12) as usize)
+        })
+        .collect();
+    assert_eq!(entries.len() as u64, index.frames);
+    let unique: BTreeSet<(usize, usize)> = entries.iter().copied().collect();
+    assert_eq!(unique.len() as u64, index.unique_frames);
+
+    // The first and last frames show the two screens.
+    for ((offset, size), expected) in [(entries[0], &first), (entries[entries.len() - 1], &second)]
+    {
+        let chunk = 220 + offset;
+        assert_eq!(&data[chunk..chunk + 4], b"00dc");
+        let frame = image::load_from_memory(&data[chunk + 8..chunk + 8 + size])
+            .unwrap()
+            .to_rgba8();
+        let pixel = frame.get_pixel(16, 8);
+        for channel in 0..3 {
+            assert!(pixel[channel].abs_diff(expected.get_pixel(16, 8)[channel]) < 16);
+        }
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_recording_config() {
+    let config = RecordingConfig::new();
+    assert_eq!((config.fps(), config.quality()), (10, 75));
+    assert!(matches!(
+        RecordingConfig::new().with_fps(0),
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(matches!(
+        RecordingConfig::new().with_quality(101),
+        Err(Error::InvalidInput(_))
+    ));
+    assert_eq!(
+        RecordingIndex::path_for(std::path::Path::new("/tmp/test.avi")),
+        std::path::Path::new("/tmp/test.avi.json")
+    );
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::action::mouse::MouseButton;
+use isototest::connection::config::VncConfig;
+use isototest::{keysym, Error, Session};
+
+mod common;
+use common::{start_mock_server, ClientMessage};
+
+/// Wait until the session received the screen's resolution.
+async fn wait_for_resolution(session: &Session, resolution: (u32, u32)) {
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.resolution() != resolution {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_session_actions() {
+    let screen = RgbaImage::from_pixel(32, 24, Rgba([90, 90, 90, 255]));
+    let mut server = start_mock_server(screen.clone()).await;
+    let session = Session::connect(&server.addr, &VncConfig::new())
+        .await
+        .unwrap();
+    wait_for_resolution(&session, (32, 24)).await;
+
+    // Positions are validated against the resolution tracked by the session.
+    session
+        .mouse_click(31, 23, MouseButton::Left)
+        .await
+        .unwrap();
+    assert!(matches!(
+        session.mouse_move(32, 0).await,
+        Err(Error::InvalidInput(_))
+    ));
+    session.send_key("ret").await.unwrap();
+
+    // The session keeps requesting screen updates, so drain the messages received so far.
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let mut pointer = Vec::new();
+    let mut keys = Vec::new();
+    while let Ok(msg) = server.messages.try_recv() {
+        match msg {
+            ClientMessage::Pointer { x, y, buttons } => pointer.push((x, y, buttons)),
+            ClientMessage::Key { keysym, down } => keys.push((keysym, down)),
+            _ => {}
+        }
+    }
+    assert_eq!(pointer, vec![(31, 23, 0), (31, 23, 1), (31, 23, 0)]);
+    assert_eq!(
+        keys,
+        vec![(keysym::XK_Return, true), (keysym::XK_Return, false)]
+    );
+
+    let path = std::env::temp_dir().join(format!("isototest-session-{}.png", std::process::id()));
+    session.save_screenshot(&path, None).unwrap();
+    assert_eq!(image::open(&path).unwrap().to_rgba8(), screen);
+    std::fs::remove_file(&path).unwrap();
+
+    session.close().await.unwrap();
+}
@@ -1,11 +1,16 @@
-use std::net::SocketAddr;
-use tokio::net::TcpListener;
-use vnc::client;
-use vnc::PixelFormat;
-use vnc::VncError;
+use std::time::Duration;
 
+use image::{Rgba, RgbaImage};
+use isototest::connection::config::{PasswordSource, VncConfig};
 use isototest::connection::{create_vnc_client, kill_client};
+use isototest::session::Session;
+use isototest::Error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use vnc::{PixelFormat, VncEncoding};
 mod common;
+use common::{start_mock_server, ClientMessage};
 
 #[tokio::test]
 #[ignore = "Broken, needs to be fixed."]
@@ -28,7 +33,7 @@ async fn test_create_success() {
     // Create the VNC client
     let result = create_vnc_client(addr, psw).await;
     match result {
-        Ok(_) => assert!(true),
+        Ok(_) => {}
         Err(e) => panic!("{}", e),
     };
 
@@ -43,7 +48,7 @@ async fn test_create_invalid_ip() {
     let target_ip = "256.256.256.256:5900".to_string();
     let psw = Some("pass".to_string());
     let result = create_vnc_client(target_ip, psw).await;
-    assert!(matches!(result, Err(VncError::IoError(_))));
+    assert!(matches!(result, Err(Error::ConnectionError(_))));
 }
 
 #[tokio::test]
@@ -80,7 +85,7 @@ async fn test_create_connect_fail() {
     let psw = Some("password".to_string());
 
     let result = create_vnc_client(target_ip, psw).await;
-    assert!(matches!(result, Err(VncError::IoError(_))));
+    assert!(matches!(result, Err(Error::ConnectionError(_))));
 }
 
 #[tokio::test]
@@ -98,3 +103,138 @@ async fn test_client_kill() {
 
     assert!(result.is_ok());
 }
+
+#[tokio::test]
+async fn test_connect_timeouts() {
+    let srv = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = srv.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        // The first connection never starts the handshake.
+        let (_silent, _) = srv.accept().await.unwrap();
+        // The second one requests VNC authentication, but never sends the challenge.
+        let (mut stalled, _) = srv.accept().await.unwrap();
+        stalled.write_all(b"RFB 003.008\n").await.unwrap();
+        let mut version = [0; 12];
+        stalled.read_exact(&mut version).await.unwrap();
+        stalled.write_all(&[1, 2]).await.unwrap();
+        let mut security_type = [0; 1];
+        stalled.read_exact(&mut security_type).await.unwrap();
+        std::future::pending::<()>().await;
+    });
+
+    let config = VncConfig::new()
+        .with_handshake_timeout(Duration::from_millis(100))
+        .with_auth_timeout(Duration::from_millis(100))
+        .with_password(PasswordSource::Plain("password".to_string()));
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(matches!(result, Err(Error::HandshakeTimeout(_))));
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(matches!(result, Err(Error::AuthenticationTimeout(_))));
+}
+
+#[tokio::test]
+async fn test_connect_cancelled() {
+    let srv = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = srv.local_addr().unwrap().to_string();
+    let token = CancellationToken::new();
+    let config = VncConfig::new().with_cancellation_token(token.clone());
+
+    let cancel = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        token.cancel();
+    });
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(matches!(result, Err(Error::Cancelled)));
+    cancel.await.unwrap();
+
+    // Connecting with a cancelled token fails immediately.
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(matches!(result, Err(Error::Cancelled)));
+}
+
+#[tokio::test]
+async fn test_config_negotiation() {
+    let mut server = start_mock_server(RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 255]))).await;
+    let config = VncConfig::new()
+        .with_encodings(vec![VncEncoding::Raw, VncEncoding::DesktopSizePseudo])
+        .with_shared(false);
+    let client = isototest::connection::connect(&server.addr, &config)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        server
+            .expect(|m| matches!(m, ClientMessage::ClientInit { .. }))
+            .await,
+        ClientMessage::ClientInit { shared: false }
+    );
+    assert_eq!(
+        server
+            .expect(|m| matches!(m, ClientMessage::SetEncodings(_)))
+            .await,
+        ClientMessage::SetEncodings(vec![0, -223])
+    );
+    kill_client(client).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_session_pixel_formats() {
+    let screen = RgbaImage::from_pixel(4, 4, Rgba([255, 128, 0, 255]));
+
+    let mut rgb565 = PixelFormat::bgra();
+    rgb565.bits_per_pixel = 16;
+    rgb565.depth = 16;
+    (rgb565.red_max, rgb565.green_max, rgb565.blue_max) = (31, 63, 31);
+    (rgb565.red_shift, rgb565.green_shift, rgb565.blue_shift) = (11, 5, 0);
+
+    // The server's native format is announced by the server, the others are requested.
+    for (pixel_format, expected) in [
+        (None, [255, 128, 0]),
+        (Some(PixelFormat::bgra()), [255, 128, 0]),
+        (Some(rgb565), [255, 125, 0]),
+    ] {
+        let server = start_mock_server(screen.clone()).await;
+        let config = VncConfig::new().with_pixel_format(pixel_format);
+        let session = Session::connect(&server.addr, &config).await.unwrap();
+
+        let mut updates = session.frame_updates();
+        while session
+            .snapshot()
+            .get_pixel_checked(0, 0)
+            .is_none_or(|px| px.0[..3] != expected)
+        {
+            tokio::time::timeout(Duration::from_secs(2), updates.changed())
+                .await
+                .expect("Screen was not converted correctly")
+                .unwrap();
+        }
+    }
+}
+
+#[test]
+fn test_password_sources() {
+    assert_eq!(PasswordSource::Empty.resolve().unwrap(), "");
+    assert_eq!(
+        PasswordSource::Plain("secret".to_string())
+            .resolve()
+            .unwrap(),
+        "secret"
+    );
+
+    let path = std::env::temp_dir().join(format!("isototest-password-{}", std::process::id()));
+    std::fs::write(&path, "from-file\nignored\n").unwrap();
+    assert_eq!(
+        PasswordSource::File(path.clone()).resolve().unwrap(),
+        "from-file"
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(
+        PasswordSource::File(path).resolve(),
+        Err(Error::AuthenticationError(_))
+    ));
+
+    assert!(matches!(
+        PasswordSource::Env("ISOTOTEST_UNSET_PASSWORD_VARIABLE".to_string()).resolve(),
+        Err(Error::AuthenticationError(_))
+    ));
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::action::text::font::Font;
+use isototest::action::text::{read_text, UNKNOWN_CHAR};
+use isototest::connection::create_vnc_client;
+use isototest::session::Session;
+use isototest::Error;
+
+mod common;
+use common::{start_mock_server, ServerCommand};
+
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// Render lines of text like a text console with cells `cell_width` pixels wide.
+fn render(
+    font: &Font,
+    lines: &[&str],
+    size: (u32, u32),
+    cell_width: u32,
+    fg: Rgba<u8>,
+    bg: Rgba<u8>,
+) -> RgbaImage {
+    let mut image = RgbaImage::from_pixel(size.0, size.1, bg);
+    let stride = font.width().div_ceil(8) as usize;
+    for (row, line) in lines.iter().enumerate() {
+        for (column, ch) in line.chars().enumerate() {
+            let glyph = font.glyph(ch).unwrap();
+            for y in 0..font.height() {
+                for x in 0..font.width() {
+                    if glyph[y as usize * stride + x as usize / 8] & (0x80 >> (x % 8)) != 0 {
+                        image.put_pixel(
+                            column as u32 * cell_width + x,
+                            row as u32 * font.height() + y,
+                            fg,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    image
+}
+
+#[test]
+fn test_read_text() {
+    let font = Font::vga_8x16();
+    let lines = ["Welcome to openSUSE!", "", "linux login: root", "Il1| O0o"];
+    let image = render(&font, &lines, (200, 64), 8, WHITE, BLACK);
+
+    let text = read_text(&image, &font);
+    assert_eq!((text.columns(), text.rows()), (25, 4));
+    assert_eq!(text.lines(), lines);
+    assert_eq!(text.to_string(), lines.join("\n"));
+    assert_eq!(text.char_at(6, 2), Some('l'));
+    assert_eq!(text.char_at(25, 0), None);
+
+    // Colours do not matter, and inverse video is recognized as well.
+    let image = render(
+        &font,
+        &lines,
+        (200, 64),
+        8,
+        BLACK,
+        Rgba([170, 170, 170, 255]),
+    );
+    assert_eq!(read_text(&image, &font).lines(), lines);
+    let image = render(&font, &lines, (200, 64), 8, Rgba([0, 0, 170, 255]), WHITE);
+    assert_eq!(read_text(&image, &font).lines(), lines);
+}
+
+#[test]
+fn test_read_text_vga_cells() {
+    // The VGA text mode renders 80x25 cells of 9x16 pixels.
+    let font = Font::vga_8x16();
+    let lines = ["GNU GRUB  version 2.12", "*openSUSE Tumbleweed"];
+    let image = render(&font, &lines, (720, 400), 9, WHITE, BLACK);
+
+    let text = read_text(&image, &font);
+    assert_eq!((text.columns(), text.rows()), (80, 25));
+    assert_eq!(text.lines()[..2], lines);
+}
+
+#[test]
+fn test_read_text_unknown() {
+    let font = Font::vga_8x16();
+    let mut image = render(&font, &["ok"], (24, 16), 8, WHITE, BLACK);
+    for y in 0..16 {
+        for x in 16..20 {
+            image.put_pixel(x, y, WHITE);
+        }
+    }
+    assert_eq!(
+        read_text(&image, &font).to_string(),
+        format!("ok{}", UNKNOWN_CHAR)
+    );
+}
+
+#[test]
+fn test_font_from_psf() {
+    // A PSF2 font with 4x6 glyphs for 'a', 'b' and, through the unicode table, 'c' and 'ĉ'.
+    let glyphs: [[u8; 6]; 3] = [
+        [0x00, 0x60, 0x90, 0x90, 0x70, 0x00],
+        [0x80, 0xe0, 0x90, 0x90, 0xe0, 0x00],
+        [0x00, 0x70, 0x80, 0x80, 0x70, 0x00],
+    ];
+    let mut data: Vec<u8> = vec![0x72, 0xb5, 0x4a, 0x86];
+    for field in [0u32, 32, 1, 3, 6, 6, 4] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    for glyph in glyphs {
+        data.extend_from_slice(&glyph);
+    }
+    data.extend_from_slice(b"a\xff");
+    data.extend_from_slice(b"b\xff");
+    data.extend_from_slice("cĉ".as_bytes());
+    data.push(0xff);
+
+    let font = Font::from_psf(&data).unwrap();
+    assert_eq!((font.width(), font.height()), (4, 6));
+    assert_eq!(font.glyph('b'), Some(&glyphs[1][..]));
+    assert_eq!(font.glyph('ĉ'), Some(&glyphs[2][..]));
+    assert_eq!(font.glyph('d'), None);
+
+    let image = render(&font, &["cab"], (12, 6), 4, WHITE, BLACK);
+    assert_eq!(read_text(&image, &font).to_string(), "cab");
+
+    assert!(matches!(
+        Font::from_psf(b"not a font"),
+        Err(Error::InvalidInput(_))
+    ));
+    assert!(matches!(
+        Font::from_psf(&data[..40]),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_wait_for_text() {
+    let font = Font::vga_8x16();
+    let blank = RgbaImage::from_pixel(160, 32, BLACK);
+    let server = start_mock_server(blank).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+
+    let result = session
+        .wait_for_text("login:", Duration::from_millis(100))
+        .await;
+    assert!(matches!(result, Err(Error::Timeout(_))));
+    let result = session.wait_for_text("(", Duration::ZERO).await;
+    assert!(matches!(result, Err(Error::InvalidInput(_))));
+
+    let screen = render(
+        &font,
+        &["Welcome", "linux login:"],
+        (160, 32),
+        8,
+        WHITE,
+        BLACK,
+    );
+    server
+        .control
+        .send(ServerCommand::SetScreen(screen))
+        .unwrap();
+    let found = session
+        .wait_for_text(r"(\w+) login:\s*$", Duration::from_secs(5))
+        .await
+        .unwrap();
+    assert_eq!(found, "linux login:");
+    assert_eq!(session.screen_text().lines()[0], "Welcome");
+
+    session.close().await.unwrap();
+}
@@ -0,0 +1,316 @@
+#![cfg(feature = "tls")]
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::connection::config::{PasswordSource, VncConfig};
+use isototest::connection::tls::{CertificateVerification, TlsConfig};
+use isototest::session::Session;
+use isototest::Error;
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+
+mod common;
+use common::serve_session;
+
+/// A CA and the certificate it issued to the mock server for `localhost` and `127.0.0.1`.
+struct Pki {
+    /// PEM file with the certificate of the CA.
+    ca_file: PathBuf,
+    acceptor: TlsAcceptor,
+}
+
+impl Pki {
+    fn new(name: &str) -> Pki {
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params
+            .distinguished_name
+            .push(DnType::CommonName, format!("isototest {} CA", name));
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+        let ca_file =
+            std::env::temp_dir().join(format!("isototest-{}-ca-{}.pem", name, std::process::id()));
+        std::fs::write(&ca_file, ca.pem()).unwrap();
+
+        let key = KeyPair::generate().unwrap();
+        let params =
+            CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()]).unwrap();
+        let cert: CertificateDer = params.signed_by(&key, &ca, &ca_key).unwrap().into();
+        let config = rustls::ServerConfig::builder_with_provider(Arc::new(
+            rustls::crypto::ring::default_provider(),
+        ))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(
+            vec![cert],
+            PrivatePkcs8KeyDer::from(key.serialize_der()).into(),
+        )
+        .unwrap();
+        Pki {
+            ca_file,
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+        }
+    }
+
+    fn verification(&self) -> CertificateVerification {
+        CertificateVerification::CaFile(self.ca_file.clone())
+    }
+}
+
+/// The PKI of the mock servers.
+fn pki() -> &'static Pki {
+    static PKI: OnceLock<Pki> = OnceLock::new();
+    PKI.get_or_init(|| Pki::new("server"))
+}
+
+/// How the mock server completes the security handshake after TLS is established.
+#[derive(Clone, Copy)]
+enum Auth {
+    None,
+    Vnc,
+    Plain(&'static str, &'static str),
+}
+
+/// Serve VeNCrypt with the given subtypes, then the session with `screen`.
+///
+/// Returns the subtype chosen by the client, or an error if the TLS handshake fails.
+async fn serve_vencrypt(
+    mut stream: TcpStream,
+    subtypes: &[u32],
+    auth: Auth,
+    screen: RgbaImage,
+) -> io::Result<u32> {
+    stream.write_all(b"RFB 003.008\n").await?;
+    let mut version = [0; 12];
+    stream.read_exact(&mut version).await?;
+    stream.write_all(&[2, 2, 19]).await?;
+    assert_eq!(stream.read_u8().await?, 19);
+    stream.write_all(&[0, 2]).await?;
+    let mut vencrypt_version = [0; 2];
+    stream.read_exact(&mut vencrypt_version).await?;
+    assert_eq!(vencrypt_version, [0, 2]);
+    stream.write_u8(0).await?;
+    stream.write_u8(subtypes.len() as u8).await?;
+    for subtype in subtypes {
+        stream.write_u32(*subtype).await?;
+    }
+    let chosen = stream.read_u32().await?;
+    stream.write_u8(1).await?;
+
+    let mut stream = pki().acceptor.accept(stream).await?;
+
+    let accepted = match auth {
+        Auth::Vnc => {
+            stream.write_all(&[7; 16]).await?;
+            let mut response = [0; 16];
+            stream.read_exact(&mut response).await?;
+            true
+        }
+        Auth::Plain(username, password) => {
+            let username_len = stream.read_u32().await? as usize;
+            let password_len = stream.read_u32().await? as usize;
+            let mut credentials = vec![0; username_len + password_len];
+            stream.read_exact(&mut credentials).await?;
+            credentials == [username.as_bytes(), password.as_bytes()].concat()
+        }
+        Auth::None => true,
+    };
+    if !accepted {
+        let reason = b"invalid credentials";
+        stream.write_u32(1).await?;
+        stream.write_u32(reason.len() as u32).await?;
+        stream.write_all(reason).await?;
+        return Ok(chosen);
+    }
+    stream.write_u32(0).await?;
+
+    let (messages, _messages_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (_control, control_rx) = tokio::sync::mpsc::unbounded_channel();
+    let _ = serve_session(stream, screen, messages, control_rx).await;
+    Ok(chosen)
+}
+
+/// Start a mock VeNCrypt server for a single connection.
+async fn start_vencrypt_server(
+    subtypes: &'static [u32],
+    auth: Auth,
+    screen: RgbaImage,
+) -> (String, tokio::task::JoinHandle<io::Result<u32>>) {
+    let srv = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = srv.local_addr().unwrap().to_string();
+    let server = tokio::spawn(async move {
+        let (socket, _) = srv.accept().await.unwrap();
+        serve_vencrypt(socket, subtypes, auth, screen).await
+    });
+    (addr, server)
+}
+
+/// Wait until the session shows the expected screen.
+async fn wait_for_screen(session: &Session, expected: &RgbaImage) {
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != *expected {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn test_vencrypt_x509() {
+    let screen = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+    let (addr, server) = start_vencrypt_server(&[257, 260], Auth::None, screen.clone()).await;
+    // The certificate is verified against the host of the target.
+    let config = VncConfig::new().with_tls(
+        TlsConfig::default()
+            .with_anonymous(true)
+            .with_verification(pki().verification()),
+    );
+
+    let session = Session::connect(&addr, &config).await.unwrap();
+    wait_for_screen(&session, &screen).await;
+    session.close().await.unwrap();
+
+    // rustls does not support anonymous TLS, so the X509 subtype is chosen.
+    assert_eq!(server.await.unwrap().unwrap(), 260);
+}
+
+#[tokio::test]
+async fn test_vencrypt_vnc_auth() {
+    let screen = RgbaImage::from_pixel(4, 4, Rgba([30, 20, 10, 255]));
+    let (addr, server) = start_vencrypt_server(&[258, 261], Auth::Vnc, screen.clone()).await;
+    let config = VncConfig::new()
+        .with_password(PasswordSource::Plain("password".to_string()))
+        .with_tls(
+            TlsConfig::default()
+                .with_server_name("localhost")
+                .with_verification(pki().verification()),
+        );
+
+    let session = Session::connect(&addr, &config).await.unwrap();
+    wait_for_screen(&session, &screen).await;
+    session.close().await.unwrap();
+    assert_eq!(server.await.unwrap().unwrap(), 261);
+}
+
+#[tokio::test]
+async fn test_vencrypt_plain() {
+    let screen = RgbaImage::from_pixel(4, 4, Rgba([50, 60, 70, 255]));
+    let tls_config = TlsConfig::default()
+        .with_verification(pki().verification())
+        .with_username("admin");
+
+    let (addr, server) =
+        start_vencrypt_server(&[262], Auth::Plain("admin", "secret"), screen.clone()).await;
+    let config = VncConfig::new()
+        .with_password(PasswordSource::Plain("secret".to_string()))
+        .with_tls(tls_config.clone());
+    let session = Session::connect(&addr, &config).await.unwrap();
+    wait_for_screen(&session, &screen).await;
+    session.close().await.unwrap();
+    assert_eq!(server.await.unwrap().unwrap(), 262);
+
+    let (addr, _server) =
+        start_vencrypt_server(&[262], Auth::Plain("admin", "secret"), screen).await;
+    let config = VncConfig::new()
+        .with_password(PasswordSource::Plain("wrong".to_string()))
+        .with_tls(tls_config);
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(
+        matches!(result, Err(Error::AuthenticationError(reason)) if reason == "invalid credentials")
+    );
+}
+
+#[tokio::test]
+async fn test_vencrypt_insecure() {
+    let screen = RgbaImage::from_pixel(4, 4, Rgba([70, 80, 90, 255]));
+    let (addr, server) = start_vencrypt_server(&[260], Auth::None, screen.clone()).await;
+    // Neither the CA nor the name of the certificate is checked.
+    let config = VncConfig::new().with_tls(
+        TlsConfig::default()
+            .with_server_name("bmc.test")
+            .with_verification(CertificateVerification::Insecure),
+    );
+
+    let session = Session::connect(&addr, &config).await.unwrap();
+    wait_for_screen(&session, &screen).await;
+    session.close().await.unwrap();
+    assert_eq!(server.await.unwrap().unwrap(), 260);
+}
+
+#[tokio::test]
+async fn test_vencrypt_verification_fails() {
+    let screen = RgbaImage::new(1, 1);
+    let other = Pki::new("other");
+    let configs = [
+        // The CA of the server is not among the system roots.
+        TlsConfig::default(),
+        // The certificate was issued by another CA.
+        TlsConfig::default().with_verification(other.verification()),
+        // The certificate was issued for another name.
+        TlsConfig::default()
+            .with_server_name("bmc.test")
+            .with_verification(pki().verification()),
+    ];
+    for tls_config in configs {
+        let (addr, server) = start_vencrypt_server(&[260], Auth::None, screen.clone()).await;
+        let config = VncConfig::new().with_tls(tls_config.clone());
+        let result = isototest::connection::connect(&addr, &config).await;
+        assert!(
+            matches!(result, Err(Error::TlsError(_))),
+            "Expected {:?} to fail, got {:?}",
+            tls_config,
+            result.map(|_| ())
+        );
+        assert!(server.await.unwrap().is_err());
+    }
+
+    // The CA file does not exist.
+    let (addr, _server) = start_vencrypt_server(&[260], Auth::None, screen).await;
+    let config = VncConfig::new().with_tls(TlsConfig::default().with_verification(
+        CertificateVerification::CaFile("/nonexistent/ca.pem".into()),
+    ));
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(matches!(result, Err(Error::TlsError(reason)) if reason.contains("ca.pem")));
+}
+
+#[tokio::test]
+async fn test_vencrypt_failures() {
+    let screen = RgbaImage::new(1, 1);
+    let config = VncConfig::new().with_tls(TlsConfig::default().with_anonymous(true));
+
+    // Only anonymous TLS is offered, which rustls does not support.
+    let (addr, _server) = start_vencrypt_server(&[257], Auth::None, screen.clone()).await;
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(matches!(result, Err(Error::ProtocolError(_))));
+
+    // The server does not offer VeNCrypt at all.
+    let server = common::start_mock_server(screen).await;
+    let result = isototest::connection::connect(&server.addr, &config).await;
+    assert!(matches!(result, Err(Error::ProtocolError(_))));
+
+    // The server announces a failure reason too long to be read.
+    let srv = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = srv.local_addr().unwrap().to_string();
+    let _server = tokio::spawn(async move {
+        let (mut stream, _) = srv.accept().await.unwrap();
+        stream.write_all(b"RFB 003.008\n").await.unwrap();
+        let mut version = [0; 12];
+        stream.read_exact(&mut version).await.unwrap();
+        stream.write_u8(0).await.unwrap();
+        stream.write_u32(u32::MAX).await.unwrap();
+        // Keep the connection open until the client gives up.
+        let _ = stream.read_u8().await;
+    });
+    let result = isototest::connection::connect(&addr, &config).await;
+    assert!(matches!(result, Err(Error::ProtocolError(_))));
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::connection::config::VncConfig;
+use isototest::connection::transport::Target;
+use isototest::session::Session;
+use tokio::net::UnixListener;
+
+mod common;
+use common::serve_rfb;
+
+/// Wait until the session shows the expected screen.
+async fn wait_for_screen(session: &Session, expected: &RgbaImage) {
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != *expected {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[test]
+fn test_target_parse() {
+    assert_eq!(
+        Target::parse("127.0.0.1:5900"),
+        Target::Tcp("127.0.0.1:5900".to_string())
+    );
+    assert_eq!(
+        Target::parse("unix:/run/qemu/vnc.sock"),
+        Target::Unix(PathBuf::from("/run/qemu/vnc.sock"))
+    );
+    assert_eq!(
+        Target::parse("unix:/run/qemu/vnc.sock").to_string(),
+        "unix:/run/qemu/vnc.sock"
+    );
+}
+
+#[tokio::test]
+async fn test_session_over_unix_socket() {
+    let screen = RgbaImage::from_pixel(8, 4, Rgba([40, 80, 120, 255]));
+    let path = std::env::temp_dir().join(format!("isototest-vnc-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let srv = UnixListener::bind(&path).unwrap();
+    let (_control, control_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (messages, _messages_rx) = tokio::sync::mpsc::unbounded_channel();
+    let served = screen.clone();
+    tokio::spawn(async move {
+        let (socket, _) = srv.accept().await.unwrap();
+        let _ = serve_rfb(socket, served, messages, control_rx).await;
+    });
+
+    let target = format!("unix:{}", path.display());
+    let session = Session::connect(target.as_str(), &VncConfig::new())
+        .await
+        .unwrap();
+    wait_for_screen(&session, &screen).await;
+    session.close().await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_session_over_established_stream() {
+    let screen = RgbaImage::from_pixel(4, 4, Rgba([200, 100, 50, 255]));
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let (_control, control_rx) = tokio::sync::mpsc::unbounded_channel();
+    let (messages, _messages_rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(serve_rfb(server, screen.clone(), messages, control_rx));
+
+    let session = Session::connect_stream(client, &VncConfig::new())
+        .await
+        .unwrap();
+    wait_for_screen(&session, &screen).await;
+    session.close().await.unwrap();
+}
@@ -0,0 +1,395 @@
+use std::time::Duration;
+
+use image::{Rgba, RgbaImage};
+use isototest::action::view::compare::{diff, screen_similarity, Region, DEFAULT_SIMILARITY};
+use isototest::action::view::framebuffer::Framebuffer;
+use isototest::action::view::{
+    read_screen, save_screenshot, wait_screen_change, wait_still_screen, ScreenshotFormat,
+};
+use isototest::connection::create_vnc_client;
+use isototest::errors::view_errors::ViewError;
+use isototest::session::Session;
+
+mod common;
+use common::{start_mock_server, MockServer, ServerCommand};
+
+/// Start a session on a mock server and wait until the initial screen has arrived.
+async fn start_session(screen: &RgbaImage) -> (MockServer, Session) {
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let session = Session::new(client);
+    let mut updates = session.frame_updates();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while session.snapshot() != *screen {
+            updates.changed().await.unwrap();
+        }
+    })
+    .await
+    .unwrap();
+    (server, session)
+}
+
+#[test]
+fn test_screen_similarity() {
+    let a = RgbaImage::from_pixel(32, 16, Rgba([100, 100, 100, 255]));
+    let mut b = a.clone();
+    assert_eq!(screen_similarity(&a, &b, &[]), f64::INFINITY);
+
+    // A small "cursor" changes the screen noticeably.
+    for x in 4..6 {
+        for y in 2..10 {
+            b.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+    }
+    let changed = screen_similarity(&a, &b, &[]);
+    assert!(changed < DEFAULT_SIMILARITY);
+    assert!(changed > 0.0);
+    assert_eq!(
+        screen_similarity(&a, &b, &[Region::new(4, 2, 2, 8)]),
+        f64::INFINITY
+    );
+
+    let other_size = RgbaImage::new(16, 16);
+    assert_eq!(screen_similarity(&a, &other_size, &[]), 0.0);
+}
+
+/// Draw the letter `A` of the 8x16 VGA font at a character cell of a text console.
+fn draw_glyph(screen: &mut RgbaImage, column: u32, row: u32) {
+    const GLYPH: [u8; 16] = [
+        0x00, 0x00, 0x10, 0x38, 0x6c, 0xc6, 0xc6, 0xfe, 0xc6, 0xc6, 0xc6, 0xc6, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+    for (dy, bits) in GLYPH.iter().enumerate() {
+        for dx in 0..8 {
+            if bits & (0x80 >> dx) != 0 {
+                let (x, y) = (column * 8 + dx, row * 16 + dy as u32);
+                screen.put_pixel(x, y, Rgba([170, 170, 170, 255]));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_screen_similarity_glyph() {
+    // A single typed character is a change on the screens of real machines.
+    for (width, height) in [(1024, 768), (1280, 1024)] {
+        let a = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+        let mut b = a.clone();
+        draw_glyph(&mut b, 10, 20);
+        let similarity = screen_similarity(&a, &b, &[]);
+        assert!(
+            similarity < DEFAULT_SIMILARITY,
+            "{}x{}: {} dB",
+            width,
+            height,
+            similarity
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_wait_screen_change_glyph() {
+    let screen = RgbaImage::from_pixel(1024, 768, Rgba([0, 0, 0, 255]));
+    let (server, session) = start_session(&screen).await;
+
+    let mut typed = screen.clone();
+    draw_glyph(&mut typed, 0, 0);
+    let control = server.control.clone();
+    let result = wait_screen_change(
+        &session,
+        async move {
+            control.send(ServerCommand::SetScreen(typed)).unwrap();
+            Ok(())
+        },
+        Duration::from_secs(5),
+        DEFAULT_SIMILARITY,
+        &[],
+    )
+    .await
+    .unwrap();
+    assert!(result);
+}
+
+#[test]
+fn test_frame_diff() {
+    let a = RgbaImage::from_pixel(64, 32, Rgba([100, 100, 100, 255]));
+    assert!(diff(&a, &a).is_empty());
+    assert!(diff(&a, &a).regions.is_empty());
+
+    // A changed block spanning several tiles and a single distant pixel.
+    let mut b = a.clone();
+    for x in 10..22 {
+        for y in 12..20 {
+            b.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+        }
+    }
+    b.put_pixel(60, 2, Rgba([255, 255, 255, 255]));
+    let result = diff(&a, &b);
+    let mut regions = result.regions.clone();
+    regions.sort_by_key(|r| r.x);
+    assert_eq!(
+        regions,
+        vec![Region::new(10, 12, 12, 8), Region::new(60, 2, 1, 1)]
+    );
+    assert_eq!(result.changed_pixels, 97);
+    assert_eq!(result.percentage, 97.0 * 100.0 / 2048.0);
+
+    let other_size = RgbaImage::new(16, 16);
+    let result = diff(&a, &other_size);
+    assert_eq!(result.regions, vec![Region::new(0, 0, 16, 16)]);
+    assert_eq!(result.percentage, 100.0);
+}
+
+#[tokio::test]
+async fn test_wait_screen_change() {
+    let screen = RgbaImage::from_pixel(16, 8, Rgba([0, 0, 0, 255]));
+    let (server, session) = start_session(&screen).await;
+
+    let mut changed = screen.clone();
+    changed.put_pixel(10, 4, Rgba([255, 255, 255, 255]));
+
+    // A change inside an ignored region does not count.
+    let control = server.control.clone();
+    let update = changed.clone();
+    let result = wait_screen_change(
+        &session,
+        async move {
+            control.send(ServerCommand::SetScreen(update)).unwrap();
+            Ok(())
+        },
+        Duration::from_millis(500),
+        DEFAULT_SIMILARITY,
+        &[Region::new(8, 0, 8, 8)],
+    )
+    .await
+    .unwrap();
+    assert!(!result);
+
+    let control = server.control.clone();
+    let result = wait_screen_change(
+        &session,
+        async move {
+            control.send(ServerCommand::SetScreen(screen)).unwrap();
+            Ok(())
+        },
+        Duration::from_secs(5),
+        DEFAULT_SIMILARITY,
+        &[],
+    )
+    .await
+    .unwrap();
+    assert!(result);
+}
+
+#[tokio::test]
+async fn test_wait_still_screen() {
+    let screen = RgbaImage::from_pixel(16, 8, Rgba([0, 0, 0, 255]));
+    let (server, session) = start_session(&screen).await;
+
+    // Keep changing the screen for a while.
+    let control = server.control.clone();
+    let animation = tokio::spawn(async move {
+        for i in 0..15u8 {
+            let frame = RgbaImage::from_pixel(16, 8, Rgba([i * 16, 0, 0, 255]));
+            control.send(ServerCommand::SetScreen(frame)).unwrap();
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    });
+
+    let still = wait_still_screen(
+        &session,
+        Duration::from_millis(400),
+        Duration::from_millis(600),
+        DEFAULT_SIMILARITY,
+        &[],
+    )
+    .await
+    .unwrap();
+    assert!(!still);
+
+    animation.await.unwrap();
+    let still = wait_still_screen(
+        &session,
+        Duration::from_millis(400),
+        Duration::from_secs(5),
+        DEFAULT_SIMILARITY,
+        &[],
+    )
+    .await
+    .unwrap();
+    assert!(still);
+}
+
+#[tokio::test]
+async fn test_read_screen_returns_frame() {
+    let screen = RgbaImage::from_pixel(12, 6, Rgba([40, 80, 120, 255]));
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+
+    let mut framebuffer = Framebuffer::default();
+    let frame = read_screen(&client, &mut framebuffer, Duration::from_millis(500))
+        .await
+        .unwrap();
+    assert_eq!(frame.dimensions(), (12, 6));
+    assert_eq!(frame.get_pixel(3, 3).0[..3], [40, 80, 120]);
+
+    // The resolution is only announced once, so a second call without a frame times out.
+    let result = read_screen(
+        &client,
+        &mut Framebuffer::default(),
+        Duration::from_millis(200),
+    )
+    .await;
+    assert!(matches!(result, Err(ViewError::Timeout(_))));
+}
+
+#[tokio::test]
+async fn test_read_screen_applies_updates() {
+    let screen = RgbaImage::from_fn(12, 6, |x, _| Rgba([x as u8 * 20, 0, 0, 255]));
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let timeout = Duration::from_millis(200);
+
+    let mut framebuffer = Framebuffer::default();
+    let frame = read_screen(&client, &mut framebuffer, timeout)
+        .await
+        .unwrap();
+    assert_eq!(*frame, screen);
+    drop(frame);
+    let generation = framebuffer.generation();
+
+    // Nothing changed, so the framebuffer is left alone.
+    let frame = read_screen(&client, &mut framebuffer, timeout)
+        .await
+        .unwrap();
+    assert_eq!(*frame, screen);
+    assert_eq!(framebuffer.generation(), generation);
+
+    // Moved pixels are copied within the framebuffer.
+    server
+        .control
+        .send(ServerCommand::CopyRect((0, 0, 4, 2), (8, 4)))
+        .unwrap();
+    let moved = read_screen(&client, &mut framebuffer, timeout)
+        .await
+        .unwrap();
+    assert_eq!(moved.get_pixel(9, 5).0[..3], [20, 0, 0]);
+    assert_eq!(moved.get_pixel(9, 3).0[..3], [180, 0, 0]);
+    // The frame returned before keeps showing the screen of its time.
+    assert_eq!(*frame, screen);
+    assert_eq!(
+        framebuffer.damage_since(generation),
+        vec![Region::new(8, 4, 4, 2)]
+    );
+
+    let changed = RgbaImage::from_pixel(12, 6, Rgba([1, 2, 3, 255]));
+    server
+        .control
+        .send(ServerCommand::SetScreen(changed.clone()))
+        .unwrap();
+    let frame = read_screen(&client, &mut framebuffer, timeout)
+        .await
+        .unwrap();
+    assert_eq!(*frame, changed);
+}
+
+#[tokio::test]
+async fn test_read_screen_clips_rects() {
+    let screen = RgbaImage::from_pixel(12, 6, Rgba([0, 0, 0, 255]));
+    let server = start_mock_server(screen.clone()).await;
+    let client = create_vnc_client(server.addr.clone(), None).await.unwrap();
+    let timeout = Duration::from_millis(200);
+    let mut framebuffer = Framebuffer::default();
+    read_screen(&client, &mut framebuffer, timeout)
+        .await
+        .unwrap();
+    let generation = framebuffer.generation();
+
+    // A rectangle reaching beyond the bottom right corner, and rectangles completely outside of
+    // the screen, e.g. sent before the screen shrank.
+    let red = Rgba([200, 0, 0, 255]);
+    server
+        .control
+        .send(ServerCommand::Rect(
+            (10, 4),
+            RgbaImage::from_pixel(4, 4, red),
+        ))
+        .unwrap();
+    for position in [(14, 5), (0, 6), (20, 20)] {
+        server
+            .control
+            .send(ServerCommand::Rect(
+                position,
+                RgbaImage::from_pixel(2, 1, red),
+            ))
+            .unwrap();
+    }
+    let frame = read_screen(&client, &mut framebuffer, timeout)
+        .await
+        .unwrap();
+
+    let expected = RgbaImage::from_fn(12, 6, |x, y| {
+        if x >= 10 && y >= 4 {
+            red
+        } else {
+            screen[(x, y)]
+        }
+    });
+    assert_eq!(*frame, expected);
+    assert_eq!(
+        framebuffer.damage_since(generation),
+        vec![Region::new(10, 4, 2, 2)]
+    );
+}
+
+#[test]
+fn test_save_screenshot_formats() {
+    let dir = std::env::temp_dir().join(format!("isototest-screenshots-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let screen = RgbaImage::from_fn(8, 4, |x, y| Rgba([x as u8 * 30, y as u8 * 60, 7, 255]));
+
+    for name in ["shot.png", "shot.webp", "shot.ppm"] {
+        let path = dir.join(name);
+        save_screenshot(&screen, &path, None).unwrap();
+        let loaded = image::open(&path).unwrap().to_rgba8();
+        assert_eq!(loaded, screen, "{} differs", name);
+    }
+
+    // An explicit format takes precedence over the extension.
+    let path = dir.join("shot.img");
+    assert!(matches!(
+        save_screenshot(&screen, &path, None),
+        Err(ViewError::ImageError(_))
+    ));
+    assert!(matches!(
+        save_screenshot(&screen, &dir.join("missing").join("shot.png"), None),
+        Err(ViewError::IoError(_))
+    ));
+    save_screenshot(&screen, &path, Some(ScreenshotFormat::Png)).unwrap();
+    assert_eq!(
+        image::ImageFormat::from_path(dir.join("x.png")).unwrap(),
+        image::guess_format(&std::fs::read(&path).unwrap()).unwrap()
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_framebuffer_shared_handle() {
+    let mut fb = Framebuffer::new(2, 2);
+    let handle = fb.shared();
+    fb.draw_rect(
+        &vnc::Rect {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        },
+        &[9, 9, 9, 0],
+    )
+    .unwrap();
+
+    // The handle keeps the old frame, the framebuffer has moved on.
+    assert_eq!(handle.get_pixel(0, 0), &Rgba([0, 0, 0, 255]));
+    assert_eq!(fb.image().get_pixel(0, 0), &Rgba([9, 9, 9, 255]));
+}
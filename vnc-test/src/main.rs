@@ -1,6 +1,6 @@
-use isototest::action::keyboard::write_to_console;
-use isototest::action::view::read_screen;
-use isototest::connection::create_vnc_client;
+use isototest::action::keyboard::layout::KeyboardLayout;
+use isototest::connection::config::{PasswordSource, VncConfig};
+use isototest::Session;
 use nix::sys::socket::{self, sockaddr_in, AddressFamily, SockType};
 use std::path::{Path, PathBuf};
 use std::process::exit;
@@ -82,39 +82,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // let addr = vnc_server.srv.as_ref().unwrap().local_addr()?;
     let addr = "";
     let psw = "password".to_string();
-    let mut client = match create_vnc_client(addr.to_string(), Some(psw.clone())).await {
-        Ok(client) => {
-            println!("Client created. Handshake successful.");
-            client
+    let config = VncConfig::new().with_password(PasswordSource::Plain(psw));
+    let mut session = match Session::connect(addr, &config).await {
+        Ok(session) => {
+            println!("Session created. Handshake successful.");
+            session
         }
         Err(e) => {
             eprintln!("[Error] {:?}.", e);
             exit(1);
         }
     };
+    session.set_keyboard_layout(KeyboardLayout::Us);
 
     let dir = Path::new("screenshots/");
-
-    let mut res;
-    match read_screen(
-        &client,
-        Some(dir),
-        None,
-        Duration::from_secs(1)
-    )
-    .await
+    session
+        .wait_still_screen(Duration::from_secs(1), Duration::from_secs(10), 99.0, &[])
+        .await?;
+    session.save_screenshot(&dir.join("screenshot-0.png"), None)?;
+    println!("Screenshot saved!");
+
+    match session
+        .write_to_console(include_str!("test_commands.txt"))
+        .await
     {
-        Ok(x) => {
-            println!("Screenshot saved!");
-            res = x;
-        }
-        Err(e) => {
-            eprintln!("{}", e);
-            exit(1);
-        }
-    }
-
-    match write_to_console(&client, include_str!("test_commands.txt").to_string(), None).await {
         Ok(_) => {
             println!("Test text sent!");
         }
@@ -124,37 +115,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
-    match read_screen(
-        &client,
-        Some(dir),
-        Some(res),
-        Duration::from_secs(1)
-    )
-    .await
-    {
-        Ok(_) => {
-            println!("Screenshot saved!");
-        }
-        Err(e) => {
-            eprintln!("{}", e);
-            exit(1);
-        }
-    }
-    match read_screen(
-        &client,
-        Some(dir),
-        Some(res),
-        Duration::from_secs(1)
-    )
-    .await
-    {
-        Ok(_) => {
-            println!("Screenshot saved!");
-        }
-        Err(e) => {
-            eprintln!("{}", e);
-            exit(1);
-        }
+    for step in 1..3 {
+        session
+            .wait_still_screen(Duration::from_secs(1), Duration::from_secs(10), 99.0, &[])
+            .await?;
+        session.save_screenshot(&dir.join(format!("screenshot-{}.png", step)), None)?;
+        println!("Screenshot saved!");
     }
+    session.close().await?;
     Ok(())
 }